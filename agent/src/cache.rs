@@ -0,0 +1,314 @@
+use crate::filesystem;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use stignore_lib::{AgentData, CategorySummary, ItemGroup};
+use tokio::sync::mpsc;
+
+/// How long filesystem events are collected before the affected subtrees are rebuilt
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(250);
+
+/// A configured category and where it lives on disk
+#[derive(Debug, Clone)]
+struct CategoryRoot {
+    id: String,
+    name: String,
+    path: PathBuf,
+    rules: filesystem::ScanRules,
+}
+
+/// What a batch of filesystem events means for the cache
+enum CacheUpdate {
+    Paths(Vec<PathBuf>),
+    Rescan,
+}
+
+/// In-memory ItemGroup tree for every category, built once at startup and kept
+/// current from inotify events so listing requests never walk the disk.
+/// Ignore flags are part of the cached trees and recomputed whenever .stignore changes.
+#[derive(Clone)]
+pub struct TreeCache {
+    roots: Arc<Vec<CategoryRoot>>,
+    trees: Arc<RwLock<HashMap<String, ItemGroup>>>,
+    /// Serialises rebuilds so a slow refresh can't overwrite a newer one
+    refresh_lock: Arc<Mutex<()>>,
+    watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
+}
+
+impl TreeCache {
+    /// Creates an empty cache for the agent's categories, call `rebuild_all` to populate it
+    pub fn new(data: &AgentData) -> Self {
+        let roots = data
+            .categories
+            .iter()
+            .map(|c| {
+                let path = Path::new(&data.agent.base_path).join(&c.relative_path);
+                // Already checked when the config was loaded
+                let excludes = data.exclude_rules(c).unwrap_or_else(|err| {
+                    tracing::warn!("Ignoring exclude patterns of '{}': {}", c.name, err);
+                    Default::default()
+                });
+                CategoryRoot {
+                    id: c.id.clone(),
+                    name: c.name.clone(),
+                    rules: filesystem::ScanRules {
+                        root: path.clone(),
+                        leaf_depth: c.leaf_depth,
+                        excludes,
+                    },
+                    path,
+                }
+            })
+            .collect();
+
+        TreeCache {
+            roots: Arc::new(roots),
+            trees: Arc::new(RwLock::new(HashMap::new())),
+            refresh_lock: Arc::new(Mutex::new(())),
+            watcher: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Walks every category on disk and replaces the cached trees
+    pub fn rebuild_all(&self) {
+        let _guard = self.refresh_lock.lock().unwrap();
+        for root in self.roots.iter() {
+            let mut tree = build_category(root);
+            filesystem::mark_ignored_items(&root.path, &mut tree);
+            self.trees.write().unwrap().insert(root.id.clone(), tree);
+        }
+    }
+
+    /// Recomputes the ignore flags of a category from its .stignore without walking the disk
+    pub fn refresh_ignored(&self, category_id: &str) {
+        let Some(root) = self.roots.iter().find(|root| root.id == category_id) else {
+            return;
+        };
+
+        let _guard = self.refresh_lock.lock().unwrap();
+        let Some(mut tree) = self.trees.read().unwrap().get(category_id).cloned() else {
+            return;
+        };
+        filesystem::mark_ignored_items(&root.path, &mut tree);
+        self.trees.write().unwrap().insert(root.id.clone(), tree);
+    }
+
+    /// Returns every category tree in configuration order
+    pub fn categories(&self) -> Vec<ItemGroup> {
+        let trees = self.trees.read().unwrap();
+        self.roots
+            .iter()
+            .filter_map(|root| trees.get(&root.id).cloned())
+            .collect()
+    }
+
+    /// Top level totals of every category in configuration order, cheap enough to
+    /// poll since neither the trees nor .stignore are touched
+    pub fn summaries(&self) -> Vec<CategorySummary> {
+        let trees = self.trees.read().unwrap();
+        self.roots
+            .iter()
+            .filter_map(|root| {
+                let tree = trees.get(&root.id)?;
+                Some(CategorySummary {
+                    id: root.id.clone(),
+                    name: root.name.clone(),
+                    size_kb: tree.size_kb,
+                    size_bytes: tree.size_bytes,
+                    file_count: tree.file_count,
+                    dir_count: tree.dir_count,
+                    item_count: tree.items.len(),
+                    has_conflicts: tree.has_conflicts,
+                    conflict_count: tree.conflict_count,
+                    is_syncing: tree.is_syncing,
+                    stversions_size_kb: tree.stversions_size_kb,
+                    stfolder_present: tree.stfolder_present,
+                })
+            })
+            .collect()
+    }
+
+    pub fn category(&self, category_id: &str) -> Option<ItemGroup> {
+        self.trees.read().unwrap().get(category_id).cloned()
+    }
+
+    /// Walks the cached tree of a category following the given item names
+    pub fn get_item(&self, category_id: &str, path: &[&str]) -> Option<ItemGroup> {
+        if path.is_empty() {
+            return None;
+        }
+
+        let trees = self.trees.read().unwrap();
+        let mut current = trees.get(category_id)?;
+        for name in path {
+            // Leaf directories hold files, which aren't addressable as items
+            if current.leaf {
+                return None;
+            }
+            current = current.items.iter().find(|child| child.name == *name)?;
+        }
+        Some(current.clone())
+    }
+
+    /// Rebuilds the parts of the cache affected by changes to the given paths
+    pub fn refresh_paths<I>(&self, paths: I)
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        // category index -> top level entries that need rebuilding
+        let mut affected: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+
+        for path in paths {
+            let Some((index, relative)) = self.locate(&path) else {
+                continue;
+            };
+            // Thumbnails and the like come and go without changing what's listed
+            if self.roots[index].rules.is_excluded(&path) {
+                continue;
+            }
+
+            let dirty = affected.entry(index).or_default();
+            if let Some(top_level) = relative.components().next() {
+                let name = top_level.as_os_str().to_string_lossy().to_string();
+                // Syncthing files only change the category's own metadata and ignore flags
+                if !filesystem::is_syncthing_system_name(&name) {
+                    dirty.insert(name);
+                }
+            }
+        }
+
+        if affected.is_empty() {
+            return;
+        }
+
+        let _guard = self.refresh_lock.lock().unwrap();
+        for (index, dirty) in affected {
+            let root = &self.roots[index];
+            let current = self.trees.read().unwrap().get(&root.id).cloned();
+            let mut tree = match current {
+                Some(current) => refresh_category(root, current, &dirty),
+                None => build_category(root),
+            };
+            // Cheaper than working out whether .stignore or one of its includes changed
+            filesystem::mark_ignored_items(&root.path, &mut tree);
+            self.trees.write().unwrap().insert(root.id.clone(), tree);
+        }
+    }
+
+    /// Starts watching every category root, the watcher lives as long as the cache
+    pub fn start_watching(&self) -> notify::Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                let update = match res {
+                    // Our own directory walks generate access events, skip them to avoid a refresh loop
+                    Ok(event) if matches!(event.kind, EventKind::Access(_)) => return,
+                    Ok(event) if event.need_rescan() => CacheUpdate::Rescan,
+                    Ok(event) => CacheUpdate::Paths(event.paths),
+                    Err(err) => {
+                        tracing::warn!("Filesystem watcher error: {}", err);
+                        CacheUpdate::Rescan
+                    }
+                };
+                let _ = tx.send(update);
+            })?;
+
+        for root in self.roots.iter() {
+            if let Err(err) = watcher.watch(&root.path, RecursiveMode::Recursive) {
+                tracing::warn!("Unable to watch category path {:?}: {}", root.path, err);
+            }
+        }
+
+        *self.watcher.lock().unwrap() = Some(watcher);
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            while let Some(first) = rx.recv().await {
+                tokio::time::sleep(DEBOUNCE_WINDOW).await;
+
+                let mut updates = vec![first];
+                while let Ok(update) = rx.try_recv() {
+                    updates.push(update);
+                }
+
+                let cache = cache.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    if updates.iter().any(|u| matches!(u, CacheUpdate::Rescan)) {
+                        cache.rebuild_all();
+                    } else {
+                        cache.refresh_paths(updates.into_iter().flat_map(|u| match u {
+                            CacheUpdate::Paths(paths) => paths,
+                            CacheUpdate::Rescan => vec![],
+                        }));
+                    }
+                })
+                .await;
+
+                if let Err(err) = result {
+                    tracing::error!("Tree cache refresh failed: {}", err);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Finds the category containing a path, preferring the most specific root
+    fn locate(&self, path: &Path) -> Option<(usize, PathBuf)> {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, root)| {
+                path.strip_prefix(&root.path)
+                    .ok()
+                    .map(|relative| (index, relative.to_path_buf()))
+            })
+            .min_by_key(|(_, relative)| relative.components().count())
+    }
+}
+
+fn build_category(root: &CategoryRoot) -> ItemGroup {
+    let children = filesystem::build_children(&root.path, 1, &root.rules);
+    filesystem::create_dir_item_group(
+        root.id.clone(),
+        root.name.clone(),
+        &root.path,
+        children,
+        false,
+    )
+}
+
+/// Rebuilds only the dirty top level entries of a category and recomputes its totals
+fn refresh_category(
+    root: &CategoryRoot,
+    current: ItemGroup,
+    dirty: &BTreeSet<String>,
+) -> ItemGroup {
+    let mut children: Vec<ItemGroup> = current
+        .items
+        .into_iter()
+        .filter(|child| !dirty.contains(&child.name))
+        .collect();
+
+    for name in dirty {
+        let path = root.path.join(name);
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => {
+                children.push(filesystem::dir_path_to_item(&path, 1, &root.rules));
+            }
+            Ok(_) => children.push(filesystem::file_path_to_item(&path)),
+            Err(_) => {}
+        }
+    }
+
+    filesystem::create_dir_item_group(
+        root.id.clone(),
+        root.name.clone(),
+        &root.path,
+        children,
+        false,
+    )
+}
@@ -0,0 +1,143 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use stignore_lib::{
+    AGENT_API_VERSION, AgentData, EnrolmentConfig, HeartbeatRequest, HeartbeatResponse,
+};
+
+/// Announces the agent to the manager and keeps sending heartbeats for as long as the
+/// process runs. The first heartbeat doubles as the registration request.
+pub fn spawn_heartbeat_task(enrolment: EnrolmentConfig, data: AgentData) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build HTTP client");
+    let url = format!(
+        "{}/api/v1/heartbeat",
+        enrolment.manager_url.trim().trim_end_matches('/')
+    );
+    let interval = Duration::from_secs(enrolment.heartbeat_interval_seconds.max(1));
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_outcome: Option<Result<bool, ()>> = None;
+        loop {
+            ticker.tick().await;
+            let Some(hostname) = advertised_hostname(&enrolment, data.agent.port).await else {
+                tracing::warn!(
+                    "Unable to work out an address for the manager at {} to reach this agent",
+                    enrolment.manager_url
+                );
+                continue;
+            };
+
+            let request = HeartbeatRequest {
+                name: data.agent.name.clone(),
+                hostname,
+                api_key: data.agent.api_key.clone(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                api_version: AGENT_API_VERSION,
+                categories: data.categories.iter().map(|c| c.name.clone()).collect(),
+                heartbeat_interval_seconds: interval.as_secs(),
+            };
+            let outcome = send_heartbeat(&client, &url, &enrolment.token, &request).await;
+
+            // Only log changes, a laptop that's offline for a day shouldn't flood the log
+            let summary = outcome.as_ref().map(|r| r.approved).map_err(|_| ());
+            if last_outcome != Some(summary) {
+                match &outcome {
+                    Ok(response) if response.approved => {
+                        tracing::info!("Registered with manager as '{}'", request.name)
+                    }
+                    Ok(response) => tracing::info!("{}", response.message),
+                    Err(e) => tracing::warn!("Heartbeat to {} failed: {}", url, e),
+                }
+            }
+            last_outcome = Some(summary);
+        }
+    });
+}
+
+async fn send_heartbeat(
+    client: &reqwest::Client,
+    url: &str,
+    token: &str,
+    request: &HeartbeatRequest,
+) -> Result<HeartbeatResponse, String> {
+    let response = client
+        .post(url)
+        .header("X-Enrolment-Token", token)
+        .json(request)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    let body: HeartbeatResponse = response.json().await.map_err(|e| e.to_string())?;
+    if !status.is_success() || !body.success {
+        return Err(format!("HTTP {}: {}", status, body.message));
+    }
+    Ok(body)
+}
+
+async fn advertised_hostname(enrolment: &EnrolmentConfig, port: u16) -> Option<String> {
+    if let Some(hostname) = &enrolment.advertised_hostname {
+        return Some(hostname.clone());
+    }
+    let ip = local_address_towards(&enrolment.manager_url).await?;
+    Some(SocketAddr::new(ip, port).to_string())
+}
+
+/// The local IP the OS would use to talk to the manager. Connecting a UDP socket only
+/// picks a route, nothing is sent.
+async fn local_address_towards(manager_url: &str) -> Option<IpAddr> {
+    let (scheme, rest) = manager_url
+        .trim()
+        .split_once("://")
+        .unwrap_or(("http", manager_url.trim()));
+    let authority = rest.split('/').next()?;
+    let default_port = if scheme == "https" { 443 } else { 80 };
+    let target = if authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+    {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, default_port)
+    };
+
+    // Resolve without blocking the runtime, then route from a socket of the same family
+    tokio::net::lookup_host(target)
+        .await
+        .ok()?
+        .find_map(|manager| {
+            let unspecified = match manager {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+            socket.connect(manager).ok()?;
+            socket.local_addr().ok().map(|addr| addr.ip())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_address_follows_the_manager_address_family() {
+        assert_eq!(
+            local_address_towards("http://127.0.0.1:8000").await,
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        );
+        // Not every sandbox has IPv6, but when it does the route must be an IPv6 one
+        if UdpSocket::bind("[::1]:0").is_ok() {
+            assert_eq!(
+                local_address_towards("http://[::1]:8000/").await,
+                Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+            );
+        }
+        assert!(local_address_towards("http://localhost").await.is_some());
+    }
+}
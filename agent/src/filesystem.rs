@@ -1,39 +1,49 @@
+use crate::history::{CategoryHistory, HistoryError};
+use crate::trash::Trash;
+use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use stignore_lib::ItemGroup;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use stignore_lib::{
+    Category, DeletePreview, IgnoreFile, IgnoreMatch, IgnorePreview, ItemGroup, ManagedStignore,
+    MatchedIgnoreRule, StignoreChange, StignoreRevision, escape_pattern,
+};
 
 /* generic functions - keeping for backward compatibility if needed */
 
 /// Checks if a directory entry represents a Syncthing system file, folder, or temporary transfer file
 /// These include .stignore, .stfolder, .stversions, any other .st* items, and .syncthing.* temporary files
 fn is_syncthing_system_item(entry: &fs::DirEntry) -> bool {
-    let name = entry.file_name();
-    let name_str = name.to_string_lossy();
-    name_str.starts_with(".st") || name_str.starts_with(".syncthing.")
+    is_syncthing_system_name(&entry.file_name().to_string_lossy())
 }
 
-/// Recursively calculates total size of a directory in kilobytes
-pub fn calculate_dir_size_kb(path: &Path) -> u64 {
+/// Name-based variant of `is_syncthing_system_item` for paths that may no longer exist on disk
+pub fn is_syncthing_system_name(name: &str) -> bool {
+    name.starts_with(".st") || name.starts_with(".syncthing.")
+}
+
+/// Recursively calculates total size of a directory in bytes
+pub fn calculate_dir_size_bytes(path: &Path) -> u64 {
     let mut total_bytes = 0u64;
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.filter_map(|e| e.ok()) {
             let p = entry.path();
             if let Ok(ft) = entry.file_type() {
                 if ft.is_dir() {
-                    total_bytes += calculate_dir_size_kb(&p) * 1024;
+                    total_bytes += calculate_dir_size_bytes(&p);
                 } else if let Ok(meta) = entry.metadata() {
                     total_bytes += meta.len();
                 }
             }
         }
     }
-    total_bytes / 1024
+    total_bytes
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct SyncthingMeta {
     pub has_conflicts: bool,
-    pub conflict_count: u32,
     pub is_syncing: bool,
     pub stversions_size_kb: u64,
     pub stfolder_present: bool,
@@ -41,7 +51,7 @@ pub struct SyncthingMeta {
 
 /// Scans a directory for immediate Syncthing metadata (conflicts, active syncing, .stversions, .stfolder)
 pub fn scan_syncthing_meta(dir_path: &Path) -> SyncthingMeta {
-    let mut conflict_count = 0u32;
+    let mut has_conflicts = false;
     let mut is_syncing = false;
     let mut stfolder_present = false;
     let mut stversions_size_kb = 0u64;
@@ -53,7 +63,7 @@ pub fn scan_syncthing_meta(dir_path: &Path) -> SyncthingMeta {
             if name == ".stfolder" {
                 stfolder_present = true;
             } else if name == ".stversions" {
-                stversions_size_kb = calculate_dir_size_kb(&entry.path());
+                stversions_size_kb = calculate_dir_size_bytes(&entry.path()) / 1024;
             }
 
             if name.starts_with(".syncthing.") {
@@ -61,14 +71,13 @@ pub fn scan_syncthing_meta(dir_path: &Path) -> SyncthingMeta {
             }
 
             if name.contains(".sync-conflict-") {
-                conflict_count += 1;
+                has_conflicts = true;
             }
         }
     }
 
     SyncthingMeta {
-        has_conflicts: conflict_count > 0,
-        conflict_count,
+        has_conflicts,
         is_syncing,
         stversions_size_kb,
         stfolder_present,
@@ -85,12 +94,8 @@ pub fn create_dir_item_group(
 ) -> ItemGroup {
     let meta = scan_syncthing_meta(dir_path);
 
-    let child_conflicts_count: u32 = children.iter().map(|c| c.conflict_count).sum();
-    let total_conflict_count = if leaf {
-        child_conflicts_count
-    } else {
-        meta.conflict_count + child_conflicts_count
-    };
+    // Conflict copies are files, which are listed at every level
+    let total_conflict_count: u32 = children.iter().map(|c| c.conflict_count).sum();
     let has_conflicts =
         total_conflict_count > 0 || meta.has_conflicts || children.iter().any(|c| c.has_conflicts);
 
@@ -103,13 +108,27 @@ pub fn create_dir_item_group(
     let child_stfolder = children.iter().any(|c| c.stfolder_present);
     let stfolder_present = meta.stfolder_present || child_stfolder;
 
+    // Summed in bytes so lots of small files don't round away to nothing
+    let size_bytes: u64 = children.iter().map(|c| c.size_bytes).sum();
+    let file_count = children.iter().map(|c| c.file_count).sum();
+    let dir_count = children
+        .iter()
+        .map(|c| c.dir_count + u64::from(c.is_dir()))
+        .sum();
+
     ItemGroup {
         id,
         name,
-        size_kb: children.iter().map(|c| c.size_kb).sum(),
+        size_kb: size_bytes / 1024,
         items: children,
         leaf,
+        size_bytes,
+        file_count,
+        dir_count,
         copy_count: 1,
+        present_on: Default::default(),
+        ignored: false,
+        ignored_on: Default::default(),
         has_conflicts,
         conflict_count: total_conflict_count,
         is_syncing,
@@ -118,16 +137,57 @@ pub fn create_dir_item_group(
     }
 }
 
-/// Helper function to convert folder path components to a full filesystem path
-fn build_full_path(base_path: &Path, folder_path_components: &[String]) -> PathBuf {
+/// Validates folder path components and resolves them to a full filesystem path that is
+/// guaranteed to stay inside the category, even when symlinks are involved.
+/// Returns a human readable reason when the path is rejected.
+pub fn resolve_folder_path(
+    base_path: &Path,
+    folder_path_components: &[String],
+) -> Result<PathBuf, String> {
+    for component in folder_path_components {
+        if component.is_empty() {
+            return Err("path components cannot be empty".to_string());
+        }
+        if component == "." || component == ".." {
+            return Err(format!(
+                "'{}' is not allowed as a path component",
+                component
+            ));
+        }
+        if component.contains(['/', '\\', '\0']) {
+            return Err(format!(
+                "path component '{}' contains a separator or NUL byte",
+                component
+            ));
+        }
+    }
+
+    let canonical_root = std::fs::canonicalize(base_path)
+        .map_err(|err| format!("category path is not accessible: {}", err))?;
+
     let mut full_path = base_path.to_path_buf();
+    let mut deepest_existing = canonical_root.clone();
     for component in folder_path_components {
         full_path = full_path.join(component);
+
+        // Non-existent paths are allowed (e.g. ignoring something not synced yet), so only
+        // resolve the part that exists on disk
+        if full_path.symlink_metadata().is_err() {
+            break;
+        }
+        deepest_existing = std::fs::canonicalize(&full_path)
+            .map_err(|err| format!("unable to resolve '{}': {}", full_path.display(), err))?;
+    }
+
+    if !deepest_existing.starts_with(&canonical_root) {
+        return Err("path resolves outside of the category".to_string());
     }
-    full_path
+
+    Ok(full_path)
 }
 
-/// Helper function to convert folder path components to Unix-style string for .stignore
+/// Helper function to convert folder path components to the Unix-style path .stignore
+/// patterns are matched against
 fn build_unix_path_string(folder_path_components: &[String]) -> String {
     if folder_path_components.is_empty() {
         "".to_string()
@@ -136,33 +196,115 @@ fn build_unix_path_string(folder_path_components: &[String]) -> String {
     }
 }
 
-fn dir_to_item(entry: fs::DirEntry) -> ItemGroup {
-    let filename = entry.file_name().to_string_lossy().to_string();
-    let entry_path = entry.path();
+/// The .stignore line matching exactly one path: anchored to the folder root and with
+/// every glob character escaped, so names like `Movie [Director's Cut]` match themselves
+fn stignore_entry(folder_path: &str) -> String {
+    format!("/{}", escape_pattern(folder_path))
+}
+
+/// The lines that stand for exactly this path, as written now and as older versions of
+/// the agent wrote it, verbatim
+fn stignore_entry_forms(folder_path: &str) -> [String; 2] {
+    [stignore_entry(folder_path), folder_path.to_string()]
+}
+
+/// Whether the content already has a line ignoring exactly this path. A verbatim line
+/// only counts when Syncthing reads it literally, `Movie [Cut]` is a character class.
+fn has_stignore_entry(content: &str, folder_path: &str) -> bool {
+    let entry = stignore_entry(folder_path);
+    content
+        .lines()
+        .map(str::trim)
+        .any(|line| line == entry || (line == folder_path && is_literal_path(folder_path)))
+}
 
-    let mut children = build_items(&entry_path, false);
-    let mut leaf = false;
+/// How the entries of a category are turned into items
+#[derive(Debug, Clone, Default)]
+pub struct ScanRules {
+    /// The category's directory, exclude patterns are matched relative to it
+    pub root: PathBuf,
+    /// See `Category::leaf_depth`
+    pub leaf_depth: Option<usize>,
+    pub excludes: IgnoreFile,
+}
 
-    if children.is_empty() {
-        children = build_items(&entry_path, true);
-        leaf = true;
+impl ScanRules {
+    /// Whether a path below the root, or one of its ancestors, is excluded from listings
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.excludes
+            .evaluate(&relative)
+            .is_some_and(|m| m.ignored())
     }
+}
+
+/// Builds the ItemGroup subtree for a single directory, used to refresh part of a cached tree.
+/// `depth` is the directory's depth in its category, see `Category::leaf_depth`.
+pub fn dir_path_to_item(entry_path: &Path, depth: usize, rules: &ScanRules) -> ItemGroup {
+    let filename = entry_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
 
-    create_dir_item_group(filename.clone(), filename, &entry_path, children, leaf)
+    let (children, leaf) = match rules.leaf_depth {
+        Some(leaf_depth) if depth >= leaf_depth => (build_leaf_items(entry_path, rules), true),
+        Some(_) => {
+            let children = build_children(entry_path, depth + 1, rules);
+            // Nothing inside means nothing to line up, an empty directory is always a leaf
+            let leaf = children.is_empty();
+            (children, leaf)
+        }
+        None => {
+            let children = build_children(entry_path, depth + 1, rules);
+            let leaf = !children.iter().any(ItemGroup::is_dir);
+            (children, leaf)
+        }
+    };
+
+    create_dir_item_group(filename.clone(), filename, entry_path, children, leaf)
+}
+
+/// Directories below a leaf are kept as groups of their files so nothing goes uncounted
+fn nested_dir_to_item(entry_path: &Path, rules: &ScanRules) -> ItemGroup {
+    let filename = entry_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let children = build_leaf_items(entry_path, rules);
+    create_dir_item_group(filename.clone(), filename, entry_path, children, true)
 }
 
-fn file_to_item(entry: fs::DirEntry) -> ItemGroup {
-    let filename = entry.file_name().to_string_lossy().to_string();
+/// Builds the ItemGroup of a single file
+pub fn file_path_to_item(path: &Path) -> ItemGroup {
+    let filename = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
     let is_conflict = filename.contains(".sync-conflict-");
     let is_syncing = filename.starts_with(".syncthing.");
 
+    let size_bytes = path.symlink_metadata().map(|m| m.len()).unwrap_or(0);
+
     ItemGroup {
         id: filename.clone(),
         name: filename,
-        size_kb: entry.metadata().map(|m| m.len() / 1024).unwrap_or(0),
+        size_kb: size_bytes / 1024,
         items: vec![],
         leaf: false,
+        size_bytes,
+        file_count: 1,
+        dir_count: 0,
         copy_count: 1,
+        present_on: Default::default(),
+        ignored: false,
+        ignored_on: Default::default(),
         has_conflicts: is_conflict,
         conflict_count: if is_conflict { 1 } else { 0 },
         is_syncing,
@@ -171,21 +313,46 @@ fn file_to_item(entry: fs::DirEntry) -> ItemGroup {
     }
 }
 
-pub fn build_items(item_path: &Path, leaf: bool) -> Vec<ItemGroup> {
+/// Lists every entry of a leaf directory
+fn build_leaf_items(item_path: &Path, rules: &ScanRules) -> Vec<ItemGroup> {
+    read_entries(item_path, rules)
+        .into_iter()
+        .map(|entry| {
+            if is_dir_entry(&entry) {
+                nested_dir_to_item(&entry.path(), rules)
+            } else {
+                file_path_to_item(&entry.path())
+            }
+        })
+        .collect()
+}
+
+/// Builds an item for every subdirectory and loose file of a directory above the leaves,
+/// `depth` being their depth in the category
+pub fn build_children(item_path: &Path, depth: usize, rules: &ScanRules) -> Vec<ItemGroup> {
+    read_entries(item_path, rules)
+        .into_iter()
+        .map(|entry| {
+            if is_dir_entry(&entry) {
+                dir_path_to_item(&entry.path(), depth, rules)
+            } else {
+                file_path_to_item(&entry.path())
+            }
+        })
+        .collect()
+}
+
+fn is_dir_entry(entry: &fs::DirEntry) -> bool {
+    entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false)
+}
+
+/// Lists a directory without Syncthing's own files and anything excluded by config
+fn read_entries(item_path: &Path, rules: &ScanRules) -> Vec<fs::DirEntry> {
     match fs::read_dir(item_path) {
-        Ok(paths) => match leaf {
-            true => paths
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| !is_syncthing_system_item(entry))
-                .map(file_to_item)
-                .collect(),
-            false => paths
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| !is_syncthing_system_item(entry))
-                .filter(|entry| entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
-                .map(dir_to_item)
-                .collect(),
-        },
+        Ok(paths) => paths
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !is_syncthing_system_item(entry) && !rules.is_excluded(&entry.path()))
+            .collect(),
         Err(why) => {
             tracing::warn!("Unable to list path: {:?}", why.kind());
             vec![]
@@ -193,30 +360,6 @@ pub fn build_items(item_path: &Path, leaf: bool) -> Vec<ItemGroup> {
     }
 }
 
-pub fn get_item(start: &Path, path: &[&str]) -> Option<ItemGroup> {
-    if path.is_empty() {
-        return None;
-    }
-
-    let item_name = path[0];
-    let children = build_items(start, false);
-    let found = children
-        .iter()
-        .find(|child| child.name == item_name)
-        .map(|c| c.to_owned());
-
-    match path.len() {
-        1 => found,
-        _ => match found {
-            Some(child) => {
-                let start_here = start.join(&child.name);
-                get_item(start_here.as_path(), &path[1..])
-            }
-            None => None,
-        },
-    }
-}
-
 /// Result of adding a path to .stignore file
 #[derive(Debug, Clone)]
 pub enum StignoreResult {
@@ -227,6 +370,18 @@ pub enum StignoreResult {
     AlreadyIgnored {
         ignored_path: String,
     },
+    /// Dry run, nothing was written
+    Preview {
+        ignored_path: String,
+        preview: IgnorePreview,
+    },
+    /// The path is ignored by a line written by hand, which unignore leaves alone unless asked
+    UserOwned {
+        ignored_path: String,
+    },
+    InvalidPath {
+        message: String,
+    },
     Error {
         message: String,
     },
@@ -238,16 +393,26 @@ pub enum DeleteResult {
     Success {
         deleted_path: String,
         message: String,
+        trash_id: Option<String>,
     },
     NotFound {
         requested_path: String,
     },
+    /// Dry run, nothing was removed
+    Preview {
+        requested_path: String,
+        preview: DeletePreview,
+    },
+    InvalidPath {
+        message: String,
+    },
     Error {
         message: String,
     },
 }
 
-/// Checks if a folder path is ignored in the .stignore file.
+/// Checks if a folder path is ignored in the .stignore file, the way Syncthing would,
+/// including rules that match one of its ancestors.
 /// This function works with folder path components and supports non-existent folders.
 ///
 /// # Parameters
@@ -255,29 +420,84 @@ pub enum DeleteResult {
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
 ///
 /// # Returns
-/// * `bool` - True if the folder path is ignored, false otherwise
-pub fn is_path_ignored(
+/// * `(bool, Option<MatchedIgnoreRule>)` - Whether the path is ignored and the rule that decided it
+pub fn ignore_status(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
-) -> bool {
+) -> (bool, Option<MatchedIgnoreRule>) {
     let folder_path_str = build_unix_path_string(folder_path_components);
-    is_path_ignored_str(category_base_path, &folder_path_str)
+    ignore_status_str(category_base_path, &folder_path_str)
 }
 
 /// Internal helper that works with path strings
-fn is_path_ignored_str(category_base_path: &std::path::Path, folder_path: &str) -> bool {
+fn ignore_status_str(
+    category_base_path: &std::path::Path,
+    folder_path: &str,
+) -> (bool, Option<MatchedIgnoreRule>) {
     let stignore_path = category_base_path.join(".stignore");
 
-    // Read .stignore file if it exists
-    let ignore_content = match std::fs::read_to_string(&stignore_path) {
-        Ok(content) => content,
-        Err(_) => return false, // No .stignore file means nothing is ignored
+    // A missing .stignore ignores nothing
+    let ignore_file = match IgnoreFile::load(&stignore_path) {
+        Ok(ignore_file) => ignore_file,
+        Err(err) => {
+            tracing::warn!("Unable to parse {:?}: {}", stignore_path, err);
+            return (false, None);
+        }
     };
 
-    // Check if the path is in the ignore list
-    ignore_content
-        .lines()
-        .any(|line| line.trim() == folder_path)
+    match ignore_file.evaluate(folder_path) {
+        Some(matched) => (matched.ignored(), Some(to_matched_rule(&matched))),
+        None => (false, None),
+    }
+}
+
+/// Sets `ignored` on a category tree and everything below it from the category's .stignore
+pub fn mark_ignored_items(category_base_path: &Path, category: &mut ItemGroup) {
+    let stignore_path = category_base_path.join(".stignore");
+    let ignore_file = IgnoreFile::load(&stignore_path).unwrap_or_else(|err| {
+        tracing::warn!("Unable to parse {:?}: {}", stignore_path, err);
+        IgnoreFile::default()
+    });
+    // Without negations nothing below an ignored directory can be unignored again
+    let skip_ignored = !ignore_file.patterns.iter().any(|pattern| pattern.negated);
+
+    category.ignored = false;
+    for child in &mut category.items {
+        let child_path = child.name.clone();
+        mark_ignored_recursive(&ignore_file, skip_ignored, &child_path, child);
+    }
+}
+
+fn mark_ignored_recursive(
+    ignore_file: &IgnoreFile,
+    skip_ignored: bool,
+    path: &str,
+    item: &mut ItemGroup,
+) {
+    item.ignored = ignore_file.evaluate(path).is_some_and(|m| m.ignored());
+    if item.ignored && skip_ignored {
+        set_ignored(item);
+        return;
+    }
+    for child in &mut item.items {
+        let child_path = format!("{}/{}", path, child.name);
+        mark_ignored_recursive(ignore_file, skip_ignored, &child_path, child);
+    }
+}
+
+fn set_ignored(item: &mut ItemGroup) {
+    item.ignored = true;
+    for child in &mut item.items {
+        set_ignored(child);
+    }
+}
+
+fn to_matched_rule(matched: &IgnoreMatch<'_>) -> MatchedIgnoreRule {
+    MatchedIgnoreRule {
+        pattern: matched.pattern.text.clone(),
+        line: matched.pattern.line,
+        inherited_from: matched.inherited.then(|| matched.matched_path.clone()),
+    }
 }
 
 /// Adds a folder path to the .stignore file in the specified category directory.
@@ -287,6 +507,7 @@ fn is_path_ignored_str(category_base_path: &std::path::Path, folder_path: &str)
 /// * `category_base_path` - The base directory of the category (e.g., "/home/user/media/movies")
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
 /// * `category_name` - Name of the category for success messages
+/// * `history` - Where the new version of the file is saved, if history is kept
 ///
 /// # Returns
 /// * `StignoreResult` - Success, already ignored, or error result
@@ -294,9 +515,13 @@ pub fn add_to_stignore(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
     category_name: &str,
+    history: Option<&CategoryHistory>,
 ) -> StignoreResult {
+    if let Err(message) = resolve_folder_path(category_base_path, folder_path_components) {
+        return StignoreResult::InvalidPath { message };
+    }
     let folder_path_str = build_unix_path_string(folder_path_components);
-    add_to_stignore_str(category_base_path, &folder_path_str, category_name)
+    add_to_stignore_str(category_base_path, &folder_path_str, category_name, history)
 }
 
 /// Internal helper that works with path strings
@@ -304,11 +529,14 @@ fn add_to_stignore_str(
     category_base_path: &std::path::Path,
     folder_path: &str,
     category_name: &str,
+    history: Option<&CategoryHistory>,
 ) -> StignoreResult {
     let stignore_path = category_base_path.join(".stignore");
+    // Held from the read to the rename so concurrent requests can't drop each other's lines
+    let _lock = lock_stignore(&stignore_path);
 
     // Read existing .stignore or handle new file creation
-    let mut ignore_content = if stignore_path.exists() {
+    let ignore_content = if stignore_path.exists() {
         match std::fs::read_to_string(&stignore_path) {
             Ok(content) => {
                 tracing::debug!(
@@ -337,26 +565,41 @@ fn add_to_stignore_str(
     };
 
     // Check if the path is already ignored
-    if ignore_content
-        .lines()
-        .any(|line| line.trim() == folder_path)
-    {
+    if has_stignore_entry(&ignore_content, folder_path) {
         tracing::debug!("Path '{}' is already in .stignore", folder_path);
         return StignoreResult::AlreadyIgnored {
             ignored_path: folder_path.to_string(),
         };
     }
 
-    // Add the path to ignore content
-    if !ignore_content.is_empty() && !ignore_content.ends_with('\n') {
-        ignore_content.push('\n');
+    // A wider pattern may already cover the path, appending a line would change nothing
+    if ignore_status_str(category_base_path, folder_path).0 {
+        tracing::debug!("Path '{}' is already matched by .stignore", folder_path);
+        return StignoreResult::AlreadyIgnored {
+            ignored_path: folder_path.to_string(),
+        };
     }
-    ignore_content.push_str(folder_path);
-    ignore_content.push('\n');
+
+    // Lines written by hand stay where they are, ours go into the managed block
+    let previous_content = ignore_content;
+    let entry = stignore_entry(folder_path);
+    let mut sections = ManagedStignore::parse(&previous_content);
+    // A verbatim line from an older agent that never matched, replaced by the escaped entry
+    sections.remove_managed(folder_path);
+    sections.remove_user_entry(folder_path);
+    sections.add_managed(&entry);
+    let ignore_content = sections.render();
 
     // Write back to .stignore
-    match std::fs::write(&stignore_path, &ignore_content) {
+    match write_stignore(&stignore_path, &ignore_content) {
         Ok(_) => {
+            record_revision(
+                history,
+                &previous_content,
+                &ignore_content,
+                StignoreChange::Ignore,
+                &entry,
+            );
             tracing::info!(
                 "Successfully added '{}' to .stignore in category '{}'",
                 folder_path,
@@ -383,22 +626,155 @@ fn add_to_stignore_str(
     }
 }
 
+/// One lock per .stignore path, kept for the life of the process
+static STIGNORE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, &'static Mutex<()>>>> =
+    LazyLock::new(Default::default);
+
+/// Serialises read-modify-write cycles on a .stignore file within this agent
+fn lock_stignore(stignore_path: &Path) -> MutexGuard<'static, ()> {
+    let lock: &'static Mutex<()> = STIGNORE_LOCKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(stignore_path.to_path_buf())
+        .or_insert_with(|| Box::leak(Box::default()));
+    // A panic halfway through never touched the file, the rename is the only write
+    lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Replaces a .stignore without ever leaving half a file behind, which Syncthing would
+/// read as "ignore nothing". The content is synced to a temporary file next to it that is
+/// then renamed over it, keeping the permissions and, where allowed, the owner.
+fn write_stignore(stignore_path: &Path, content: &str) -> std::io::Result<()> {
+    // Write through a symlinked .stignore rather than replacing the link
+    let stignore_path =
+        fs::canonicalize(stignore_path).unwrap_or_else(|_| stignore_path.to_path_buf());
+    // Syncthing never syncs files starting with .syncthing. so the temp file can't leak out
+    let tmp_path = stignore_path.with_file_name(".syncthing.stignore.tmp");
+
+    let written = (|| -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(&stignore_path) {
+            file.set_permissions(metadata.permissions())?;
+            #[cfg(unix)]
+            preserve_owner(&file, &metadata);
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, &stignore_path)
+    })();
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    // The rename only survives a crash once the directory is synced too
+    #[cfg(unix)]
+    if let Some(dir) = stignore_path.parent()
+        && let Ok(dir) = fs::File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn preserve_owner(file: &fs::File, original: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(current) = file.metadata() else {
+        return;
+    };
+    if (current.uid(), current.gid()) == (original.uid(), original.gid()) {
+        return;
+    }
+    if let Err(err) = std::os::unix::fs::fchown(file, Some(original.uid()), Some(original.gid())) {
+        tracing::warn!(
+            "Unable to keep the owner {}:{} of .stignore: {}",
+            original.uid(),
+            original.gid(),
+            err
+        );
+    }
+}
+
+/// Reports what `add_to_stignore` would do for a folder path without writing anything:
+/// the line it would add to the managed block and every existing rule already covering
+/// the path.
+pub fn preview_add_to_stignore(
+    category_base_path: &std::path::Path,
+    folder_path_components: &[String],
+) -> StignoreResult {
+    if let Err(message) = resolve_folder_path(category_base_path, folder_path_components) {
+        return StignoreResult::InvalidPath { message };
+    }
+    let folder_path = build_unix_path_string(folder_path_components);
+    let stignore_path = category_base_path.join(".stignore");
+
+    let ignore_content = match std::fs::read_to_string(&stignore_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return StignoreResult::Error {
+                message: format!("Failed to read existing .stignore file: {}", err),
+            };
+        }
+    };
+    // Same fallback as `ignore_status_str`, an unparsable file is treated as empty
+    let ignore_file = IgnoreFile::load(&stignore_path).unwrap_or_else(|err| {
+        tracing::warn!("Unable to parse {:?}: {}", stignore_path, err);
+        IgnoreFile::default()
+    });
+
+    let already_ignored = has_stignore_entry(&ignore_content, &folder_path)
+        || ignore_file
+            .evaluate(&folder_path)
+            .is_some_and(|matched| matched.ignored());
+
+    StignoreResult::Preview {
+        preview: IgnorePreview {
+            line: (!already_ignored).then(|| stignore_entry(&folder_path)),
+            covering_patterns: ignore_file
+                .all_matches(&folder_path)
+                .iter()
+                .map(to_matched_rule)
+                .collect(),
+        },
+        ignored_path: folder_path,
+    }
+}
+
 /// Removes a folder path from the .stignore file in the specified category directory.
+/// Only the managed block is touched unless `include_user_entries` is set, a matching
+/// line written by hand is reported as `UserOwned` instead.
 pub fn remove_from_stignore(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
     category_name: &str,
+    include_user_entries: bool,
+    history: Option<&CategoryHistory>,
 ) -> StignoreResult {
+    if let Err(message) = resolve_folder_path(category_base_path, folder_path_components) {
+        return StignoreResult::InvalidPath { message };
+    }
     let folder_path_str = build_unix_path_string(folder_path_components);
-    remove_from_stignore_str(category_base_path, &folder_path_str, category_name)
+    remove_from_stignore_str(
+        category_base_path,
+        &folder_path_str,
+        category_name,
+        include_user_entries,
+        history,
+    )
 }
 
 fn remove_from_stignore_str(
     category_base_path: &std::path::Path,
     folder_path: &str,
     category_name: &str,
+    include_user_entries: bool,
+    history: Option<&CategoryHistory>,
 ) -> StignoreResult {
     let stignore_path = category_base_path.join(".stignore");
+    let _lock = lock_stignore(&stignore_path);
 
     if !stignore_path.exists() {
         return StignoreResult::Success {
@@ -416,47 +792,314 @@ fn remove_from_stignore_str(
         }
     };
 
-    let remaining_lines: Vec<&str> = ignore_content
-        .lines()
-        .filter(|line| line.trim() != folder_path)
-        .collect();
-
-    let new_content = if remaining_lines.is_empty() {
-        String::new()
-    } else {
-        format!("{}\n", remaining_lines.join("\n"))
-    };
-
-    match std::fs::write(&stignore_path, &new_content) {
-        Ok(_) => StignoreResult::Success {
+    let forms = stignore_entry_forms(folder_path);
+    let mut sections = ManagedStignore::parse(&ignore_content);
+    if forms.iter().any(|form| sections.is_user_entry(form)) && !include_user_entries {
+        return StignoreResult::UserOwned {
             ignored_path: folder_path.to_string(),
-            message: format!(
-                "Successfully removed '{}' from .stignore in category '{}'",
-                folder_path, category_name
-            ),
-        },
+        };
+    }
+    let mut removed = false;
+    for form in &forms {
+        removed |= sections.remove_managed(form);
+        removed |= sections.remove_user_entry(form);
+    }
+    if !removed {
+        return StignoreResult::Success {
+            ignored_path: folder_path.to_string(),
+            message: format!("Path '{}' was not present in .stignore", folder_path),
+        };
+    }
+    let new_content = sections.render();
+
+    match write_stignore(&stignore_path, &new_content) {
+        Ok(_) => {
+            record_revision(
+                history,
+                &ignore_content,
+                &new_content,
+                StignoreChange::Unignore,
+                &forms[0],
+            );
+            StignoreResult::Success {
+                ignored_path: folder_path.to_string(),
+                message: format!(
+                    "Successfully removed '{}' from .stignore in category '{}'",
+                    folder_path, category_name
+                ),
+            }
+        }
         Err(err) => StignoreResult::Error {
             message: format!("Failed to write updated .stignore file: {}", err),
         },
     }
 }
 
+/// Moves entries written by hand into the managed block, so the agent may unignore them
+/// later. Without `entries` every literal path is moved: older versions of the agent
+/// appended their lines anywhere in the file and only ever wrote those.
+pub fn adopt_stignore_entries(
+    category_base_path: &Path,
+    entries: Option<&[String]>,
+    history: Option<&CategoryHistory>,
+) -> Result<Vec<String>, String> {
+    let stignore_path = category_base_path.join(".stignore");
+    let _lock = lock_stignore(&stignore_path);
+
+    let (content, _) = read_stignore(category_base_path)?;
+    let mut sections = ManagedStignore::parse(&content);
+    let adopted = sections.adopt(|pattern| match entries {
+        Some(entries) => entries.iter().any(|entry| entry.trim() == pattern),
+        None => is_literal_path(pattern),
+    });
+    if adopted.is_empty() {
+        return Ok(adopted);
+    }
+
+    let new_content = sections.render();
+    write_stignore(&stignore_path, &new_content)
+        .map_err(|err| format!("Failed to write .stignore file: {}", err))?;
+    record_revision(
+        history,
+        &content,
+        &new_content,
+        StignoreChange::Adopt,
+        &adopted.join(", "),
+    );
+    Ok(adopted)
+}
+
+/// Whether a pattern names one path literally, the way `add_to_stignore` writes them or,
+/// unanchored and unescaped, older versions of the agent did
+fn is_literal_path(pattern: &str) -> bool {
+    let (body, anchored) = match pattern.strip_prefix('/') {
+        Some(body) => (body, true),
+        None => (pattern, false),
+    };
+    if !anchored && body.starts_with(['!', '#', '(']) {
+        return false;
+    }
+
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if anchored => {
+                if chars.next().is_none() {
+                    return false;
+                }
+            }
+            '*' | '?' | '[' | ']' | '{' | '}' | '\\' => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Saves a change to the category's history, a failure there doesn't undo the change
+fn record_revision(
+    history: Option<&CategoryHistory>,
+    previous: &str,
+    content: &str,
+    change: StignoreChange,
+    detail: &str,
+) {
+    if let Some(history) = history
+        && let Err(err) = history.record(previous, content, change, detail)
+    {
+        tracing::warn!("Unable to save .stignore revision: {}", err);
+    }
+}
+
+/// Puts a saved revision back as the category's .stignore. The revert is saved as a
+/// revision of its own, so it can be undone the same way.
+pub fn revert_stignore(
+    category_base_path: &Path,
+    history: &CategoryHistory,
+    revision_id: &str,
+) -> Result<StignoreRevision, HistoryError> {
+    let stignore_path = category_base_path.join(".stignore");
+    let _lock = lock_stignore(&stignore_path);
+
+    let (revision, content) = history.get(revision_id)?;
+    let current = match fs::read_to_string(&stignore_path) {
+        Ok(current) => current,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(HistoryError::Io {
+                message: format!("Failed to read .stignore file: {}", err),
+            });
+        }
+    };
+
+    write_stignore(&stignore_path, &content).map_err(|err| HistoryError::Io {
+        message: format!("Failed to write .stignore file: {}", err),
+    })?;
+    history.record(&current, &content, StignoreChange::Revert, revision_id)?;
+    Ok(revision)
+}
+
+/// Identifies the exact content of a .stignore, quoted as HTTP wants it. FNV-1a keeps it
+/// stable across restarts, unlike the std hasher.
+pub fn stignore_etag(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("\"{:x}-{:016x}\"", content.len(), hash)
+}
+
+/// Reads a category's .stignore along with its ETag, a missing file reads as empty
+pub fn read_stignore(category_base_path: &Path) -> Result<(String, String), String> {
+    let stignore_path = category_base_path.join(".stignore");
+    let content = match fs::read_to_string(&stignore_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("Failed to read .stignore file: {}", err)),
+    };
+    let etag = stignore_etag(&content);
+    Ok((content, etag))
+}
+
+/// Result of replacing a whole .stignore file
+#[derive(Debug, Clone)]
+pub enum StignoreWriteResult {
+    Written {
+        etag: String,
+    },
+    /// The file changed since the caller read it, holds what it is now
+    Conflict {
+        content: String,
+        etag: String,
+    },
+    Invalid {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Replaces a category's .stignore, as long as it still is the version `if_match` was
+/// the ETag of and the new content parses
+pub fn replace_stignore(
+    category_base_path: &Path,
+    content: &str,
+    if_match: &str,
+    history: Option<&CategoryHistory>,
+) -> StignoreWriteResult {
+    let stignore_path = category_base_path.join(".stignore");
+    let _lock = lock_stignore(&stignore_path);
+
+    let (current, current_etag) = match read_stignore(category_base_path) {
+        Ok(current) => current,
+        Err(message) => return StignoreWriteResult::Error { message },
+    };
+    if current_etag != if_match {
+        return StignoreWriteResult::Conflict {
+            content: current,
+            etag: current_etag,
+        };
+    }
+
+    // Syncthing ignores nothing at all when it can't parse the file
+    if let Err(err) = IgnoreFile::parse_as(content, &stignore_path) {
+        return StignoreWriteResult::Invalid {
+            message: err.to_string(),
+        };
+    }
+
+    if let Err(err) = write_stignore(&stignore_path, content) {
+        return StignoreWriteResult::Error {
+            message: format!("Failed to write .stignore file: {}", err),
+        };
+    }
+    if current != content {
+        record_revision(history, &current, content, StignoreChange::Edit, "");
+    }
+    StignoreWriteResult::Written {
+        etag: stignore_etag(content),
+    }
+}
+
+/// Reports what `delete_from_filesystem` would remove without touching anything.
+/// Unlike a real delete a missing path isn't an error, the preview just says it doesn't exist.
+pub fn preview_delete(
+    category_base_path: &std::path::Path,
+    folder_path_components: &[String],
+    moves_to_trash: bool,
+) -> DeleteResult {
+    let full_path = match resolve_folder_path(category_base_path, folder_path_components) {
+        Ok(full_path) => full_path,
+        Err(message) => return DeleteResult::InvalidPath { message },
+    };
+    let normalized_folder_path = build_unix_path_string(folder_path_components);
+
+    let mut preview = DeletePreview {
+        moves_to_trash,
+        ..Default::default()
+    };
+    if full_path.exists()
+        && let Ok(metadata) = full_path.symlink_metadata()
+    {
+        preview.exists = true;
+        preview.is_dir = metadata.is_dir();
+        collect_files(&full_path, &normalized_folder_path, &metadata, &mut preview);
+        preview.files.sort();
+    }
+
+    DeleteResult::Preview {
+        requested_path: normalized_folder_path,
+        preview,
+    }
+}
+
+/// Adds every file below a path to the preview, symlinked directories aren't followed
+/// since `remove_dir_all` only removes the link
+fn collect_files(
+    path: &Path,
+    relative: &str,
+    metadata: &fs::Metadata,
+    preview: &mut DeletePreview,
+) {
+    if !metadata.is_dir() {
+        preview.total_bytes += metadata.len();
+        preview.files.push(relative.to_string());
+        return;
+    }
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let child = entry.path();
+            if let Ok(child_metadata) = child.symlink_metadata() {
+                let child_relative =
+                    format!("{}/{}", relative, entry.file_name().to_string_lossy());
+                collect_files(&child, &child_relative, &child_metadata, preview);
+            }
+        }
+    }
+}
+
 /// Deletes a folder path from the filesystem in the specified category directory.
+/// When a trash is configured the item is moved into it instead of being removed.
 /// This function works with folder path components.
 ///
 /// # Parameters
 /// * `category_base_path` - The base directory of the category (e.g., "/home/user/media/movies")
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
-/// * `category_name` - Name of the category for success messages
+/// * `category` - The category, its name is used for success messages
+/// * `trash` - Where to move the item to, None deletes it permanently
 ///
 /// # Returns
 /// * `DeleteResult` - Success, not found, or error result
 pub fn delete_from_filesystem(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
-    category_name: &str,
+    category: &Category,
+    trash: Option<&Trash>,
 ) -> DeleteResult {
-    let full_path = build_full_path(category_base_path, folder_path_components);
+    let full_path = match resolve_folder_path(category_base_path, folder_path_components) {
+        Ok(full_path) => full_path,
+        Err(message) => return DeleteResult::InvalidPath { message },
+    };
     let normalized_folder_path = build_unix_path_string(folder_path_components);
 
     // Check if the path exists
@@ -466,6 +1109,22 @@ pub fn delete_from_filesystem(
         };
     }
 
+    if let Some(trash) = trash {
+        return match trash.move_in(&full_path, &category.id, folder_path_components) {
+            Ok(entry) => DeleteResult::Success {
+                deleted_path: normalized_folder_path.clone(),
+                message: format!(
+                    "Moved '{}' from category '{}' to trash",
+                    normalized_folder_path, category.name
+                ),
+                trash_id: Some(entry.id),
+            },
+            Err(err) => DeleteResult::Error {
+                message: err.to_string(),
+            },
+        };
+    }
+
     // Attempt to delete the path
     let result = if full_path.is_dir() {
         std::fs::remove_dir_all(&full_path)
@@ -478,8 +1137,9 @@ pub fn delete_from_filesystem(
             deleted_path: normalized_folder_path.clone(),
             message: format!(
                 "Successfully deleted '{}' from category '{}'",
-                normalized_folder_path, category_name
+                normalized_folder_path, category.name
             ),
+            trash_id: None,
         },
         Err(err) => DeleteResult::Error {
             message: format!("Failed to delete '{}': {}", normalized_folder_path, err),
@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use stignore_lib::{AgentConfig, DiffLine, DiffOp, StignoreChange, StignoreRevision};
+
+/// Disambiguates revisions saved within the same millisecond
+static REVISION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+pub enum HistoryError {
+    NotFound { id: String },
+    Io { message: String },
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::NotFound { id } => write!(f, "Revision '{}' not found", id),
+            HistoryError::Io { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// Per-agent .stignore history. Each category gets its own directory where every
+/// revision is stored as `<id>.stignore` next to a `<id>.json` describing the change.
+#[derive(Debug, Clone)]
+pub struct StignoreHistory {
+    root: PathBuf,
+    retention: usize,
+}
+
+impl StignoreHistory {
+    /// Returns the configured history, or None when no history should be kept
+    pub fn from_config(agent: &AgentConfig) -> Option<StignoreHistory> {
+        agent.history_path.as_ref().map(|path| StignoreHistory {
+            root: PathBuf::from(path),
+            retention: agent.history_retention.max(1),
+        })
+    }
+
+    pub fn retention(&self) -> usize {
+        self.retention
+    }
+
+    pub fn category(&self, category_id: &str) -> CategoryHistory {
+        CategoryHistory {
+            category_id: category_id.to_string(),
+            dir: self.root.join(category_id),
+            retention: self.retention,
+        }
+    }
+}
+
+/// The revisions of a single category's .stignore
+#[derive(Debug, Clone)]
+pub struct CategoryHistory {
+    category_id: String,
+    dir: PathBuf,
+    retention: usize,
+}
+
+impl CategoryHistory {
+    /// Saves the content a change produced. When `previous` isn't the newest revision the
+    /// file was edited by something else in the meantime, that version is saved first so
+    /// it can be reverted to as well.
+    pub fn record(
+        &self,
+        previous: &str,
+        content: &str,
+        change: StignoreChange,
+        detail: &str,
+    ) -> Result<StignoreRevision, HistoryError> {
+        let newest = self
+            .list()
+            .into_iter()
+            .next()
+            .and_then(|revision| self.get(&revision.id).ok());
+        if newest.is_none_or(|(_, newest_content)| newest_content != previous) {
+            self.save(previous, StignoreChange::Outside, "")?;
+        }
+
+        let revision = self.save(content, change, detail)?;
+        self.prune();
+        Ok(revision)
+    }
+
+    /// Lists saved revisions, newest first
+    pub fn list(&self) -> Vec<StignoreRevision> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+
+        let mut revisions: Vec<StignoreRevision> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .filter_map(|content| serde_json::from_str::<StignoreRevision>(&content).ok())
+            .filter(|revision| self.content_path(&revision.id).exists())
+            .collect();
+
+        revisions.sort_by_key(|revision| std::cmp::Reverse(order_key(&revision.id)));
+        revisions
+    }
+
+    /// Returns a revision along with the .stignore content it saved
+    pub fn get(&self, id: &str) -> Result<(StignoreRevision, String), HistoryError> {
+        let not_found = || HistoryError::NotFound { id: id.to_string() };
+
+        // Ids are generated by us, anything else could point outside the history
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Err(not_found());
+        }
+
+        let metadata = std::fs::read_to_string(self.metadata_path(id)).map_err(|_| not_found())?;
+        let revision: StignoreRevision =
+            serde_json::from_str(&metadata).map_err(|_| not_found())?;
+        let content = std::fs::read_to_string(self.content_path(id)).map_err(|_| not_found())?;
+        Ok((revision, content))
+    }
+
+    fn save(
+        &self,
+        content: &str,
+        change: StignoreChange,
+        detail: &str,
+    ) -> Result<StignoreRevision, HistoryError> {
+        std::fs::create_dir_all(&self.dir).map_err(|err| HistoryError::Io {
+            message: format!("Failed to create history directory: {}", err),
+        })?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let id = format!(
+            "{}-{}",
+            now.as_millis(),
+            REVISION_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let revision = StignoreRevision {
+            id: id.clone(),
+            category_id: self.category_id.clone(),
+            created_at: now.as_secs(),
+            change,
+            detail: detail.to_string(),
+            line_count: content.lines().count(),
+        };
+
+        // Content goes first, a revision is only listed once its metadata exists
+        std::fs::write(self.content_path(&id), content).map_err(|err| HistoryError::Io {
+            message: format!("Failed to save .stignore revision: {}", err),
+        })?;
+        let json = serde_json::to_string_pretty(&revision).map_err(|err| HistoryError::Io {
+            message: format!("Failed to serialise revision metadata: {}", err),
+        })?;
+        std::fs::write(self.metadata_path(&id), json).map_err(|err| HistoryError::Io {
+            message: format!("Failed to write revision metadata: {}", err),
+        })?;
+
+        Ok(revision)
+    }
+
+    /// Drops the oldest revisions beyond the retention
+    fn prune(&self) {
+        for revision in self.list().into_iter().skip(self.retention) {
+            let _ = std::fs::remove_file(self.metadata_path(&revision.id));
+            let _ = std::fs::remove_file(self.content_path(&revision.id));
+        }
+    }
+
+    fn content_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.stignore", id))
+    }
+
+    fn metadata_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+/// Ids are `<millis>-<counter>`, which don't sort correctly as strings
+fn order_key(id: &str) -> (u128, u64) {
+    let (millis, counter) = id.split_once('-').unwrap_or((id, "0"));
+    (millis.parse().unwrap_or(0), counter.parse().unwrap_or(0))
+}
+
+/// Line by line difference between two versions of a file. Uses Myers' algorithm in
+/// linear space, the agent writes one line per ignored item so files can get long.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    similar::capture_diff_slices(similar::Algorithm::Myers, &old, &new)
+        .iter()
+        .flat_map(|op| op.iter_changes(&old, &new))
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                similar::ChangeTag::Equal => DiffOp::Same,
+                similar::ChangeTag::Insert => DiffOp::Added,
+                similar::ChangeTag::Delete => DiffOp::Removed,
+            },
+            text: change.value().to_string(),
+        })
+        .collect()
+}
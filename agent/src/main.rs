@@ -1,10 +1,14 @@
+mod cache;
+mod enrolment;
 mod filesystem;
+mod history;
 mod tasks;
+mod trash;
 
 use axum::{
     Router,
     body::Body,
-    extract::State,
+    extract::{FromRef, State},
     http::{Request, StatusCode},
     middleware,
     response::Response,
@@ -17,6 +21,40 @@ use std::env;
 use stignore_lib::{AgentData, load_agent_config};
 use tokio::signal;
 
+/// Shared state for the agent's handlers
+#[derive(Clone)]
+pub struct AgentState {
+    pub data: AgentData,
+    pub cache: cache::TreeCache,
+    pub trash: Option<trash::Trash>,
+    pub history: Option<history::StignoreHistory>,
+    pub started_at: std::time::Instant,
+}
+
+impl FromRef<AgentState> for AgentData {
+    fn from_ref(state: &AgentState) -> Self {
+        state.data.clone()
+    }
+}
+
+impl FromRef<AgentState> for cache::TreeCache {
+    fn from_ref(state: &AgentState) -> Self {
+        state.cache.clone()
+    }
+}
+
+impl FromRef<AgentState> for Option<trash::Trash> {
+    fn from_ref(state: &AgentState) -> Self {
+        state.trash.clone()
+    }
+}
+
+impl FromRef<AgentState> for Option<history::StignoreHistory> {
+    fn from_ref(state: &AgentState) -> Self {
+        state.history.clone()
+    }
+}
+
 async fn auth_middleware(
     State(data): State<AgentData>,
     request: Request<Body>,
@@ -91,11 +129,85 @@ async fn main() {
         }
     };
 
+    /* build the tree cache and keep it current from filesystem events */
+    let cache = cache::TreeCache::new(&data);
+    tracing::info!(
+        "Building tree cache for {} categories",
+        data.categories.len()
+    );
+    // Watch first so changes made during a long initial scan are applied once it finishes
+    if let Err(err) = cache.start_watching() {
+        tracing::warn!("Unable to watch filesystem, tree cache will not update: {err}");
+    }
+    let initial = cache.clone();
+    tokio::task::spawn_blocking(move || initial.rebuild_all())
+        .await
+        .expect("failed to build tree cache");
+
+    /* purge expired trash items in the background */
+    let trash = trash::Trash::from_config(&data.agent);
+    if let Some(trash) = trash.clone() {
+        tracing::info!(
+            "Deleted items are kept in trash for {} days",
+            trash.retention_days()
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(trash::PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let trash = trash.clone();
+                if let Ok(purged) = tokio::task::spawn_blocking(move || trash.purge_expired()).await
+                {
+                    for entry in purged {
+                        tracing::info!(
+                            "Purged expired trash item '{}' ({}/{})",
+                            entry.id,
+                            entry.category_id,
+                            entry.folder_path.join("/")
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    let history = history::StignoreHistory::from_config(&data.agent);
+    if let Some(history) = &history {
+        tracing::info!(
+            "Keeping the last {} .stignore revisions of each category",
+            history.retention()
+        );
+    }
+
+    /* announce ourselves to the manager and keep sending heartbeats */
+    if let Some(enrolment) = data.agent.enrolment.clone() {
+        tracing::info!("Sending heartbeats to manager at {}", enrolment.manager_url);
+        enrolment::spawn_heartbeat_task(enrolment, data.clone());
+    }
+
+    let state = AgentState {
+        data: data.clone(),
+        cache,
+        trash,
+        history,
+        started_at: std::time::Instant::now(),
+    };
+
     /* configure application routes */
     let app = Router::new()
         .route("/", get(tasks::help))
+        .route("/api/v1/health", get(tasks::health))
         .route("/api/v1/categories", get(tasks::category_list))
+        .route("/api/v1/summary", get(tasks::category_summary))
         .route("/api/v1/categories/{id}", get(tasks::category_info))
+        .route(
+            "/api/v1/categories/{id}/stignore",
+            get(tasks::get_stignore).put(tasks::put_stignore),
+        )
+        .route(
+            "/api/v1/categories/{id}/stignore/adopt",
+            post(tasks::post_stignore_adopt),
+        )
         .route("/api/v1/items", post(tasks::post_item_info))
         .route("/api/v1/ignore", post(tasks::post_ignore))
         .route("/api/v1/unignore", post(tasks::post_unignore))
@@ -105,11 +217,17 @@ async fn main() {
             post(tasks::post_ignore_status_bulk),
         )
         .route("/api/v1/delete", post(tasks::post_delete))
+        .route("/api/v1/trash", get(tasks::trash_list))
+        .route("/api/v1/trash/restore", post(tasks::post_trash_restore))
+        .route("/api/v1/trash/purge", post(tasks::post_trash_purge))
+        .route("/api/v1/history", get(tasks::history_list))
+        .route("/api/v1/history/diff", post(tasks::post_history_diff))
+        .route("/api/v1/history/revert", post(tasks::post_history_revert))
         .layer(middleware::from_fn_with_state(
             data.clone(),
             auth_middleware,
         ))
-        .with_state(data.clone());
+        .with_state(state);
 
     /* bind to the port and listen */
     let addr = format!("0.0.0.0:{}", data.agent.port);
@@ -1,8 +1,11 @@
+use crate::cache::TreeCache;
 use crate::filesystem;
+use crate::history::{self, HistoryError, StignoreHistory};
+use crate::trash::{Trash, TrashError};
 use axum::{
     Json,
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode, header},
     response::{Html, IntoResponse, Response},
 };
 use std::path::PathBuf;
@@ -19,47 +22,72 @@ pub async fn help() -> Html<&'static str> {
     )
 }
 
+// GET health
+// Cheap liveness and capability check, answers from config without touching the filesystem
+pub async fn health(State(state): State<crate::AgentState>) -> impl IntoResponse {
+    let mut features = Vec::new();
+    if state.trash.is_some() {
+        features.push("trash".to_string());
+    }
+    if state.history.is_some() {
+        features.push("history".to_string());
+    }
+    if state.data.agent.enrolment.is_some() {
+        features.push("enrolment".to_string());
+    }
+
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: AGENT_API_VERSION,
+        uptime_seconds: state.started_at.elapsed().as_secs(),
+        categories: state
+            .data
+            .categories
+            .iter()
+            .map(|category| HealthCategory {
+                id: category.id.clone(),
+                name: category.name.clone(),
+            })
+            .collect(),
+        features,
+    })
+}
+
 // GET categories
 // Returns all configured categories that the agent is configured for!
-pub async fn category_list(State(data): State<AgentData>) -> impl IntoResponse {
-    let items = data
-        .categories
-        .iter()
-        .map(|c| {
-            let category_path = build_category_base_path(&data.agent, c);
-            let children = filesystem::build_items(&category_path, false);
-            filesystem::create_dir_item_group(
-                c.id.clone(),
-                c.name.clone(),
-                &category_path,
-                children,
-                false,
-            )
-        })
-        .collect();
+pub async fn category_list(State(cache): State<TreeCache>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(CategoryListingResponse {
+            items: cache.categories(),
+        }),
+    )
+}
 
-    (StatusCode::OK, Json(CategoryListingResponse { items }))
+// GET category summary
+// Returns only the totals of each category, for overviews that don't need the trees
+pub async fn category_summary(State(cache): State<TreeCache>) -> impl IntoResponse {
+    Json(CategorySummaryResponse {
+        categories: cache.summaries(),
+    })
 }
 
 // GET category info
 // Returns specific info for a given category
 pub async fn category_info(
-    State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
     Path(category_id): Path<String>,
 ) -> Response {
-    match data.categories.iter().find(|x| x.id == category_id) {
-        Some(category) => {
-            let category_path = build_category_base_path(&data.agent, category);
-
-            (
-                StatusCode::OK,
-                Json(CategoryInfoResponse {
-                    name: category.name.clone(),
-                    items: filesystem::build_items(&category_path, false),
-                }),
-            )
-                .into_response()
-        }
+    match cache.category(&category_id) {
+        Some(category) => (
+            StatusCode::OK,
+            Json(CategoryInfoResponse {
+                name: category.name,
+                items: category.items,
+            }),
+        )
+            .into_response(),
         None => (
             StatusCode::NOT_FOUND,
             Json(NotFoundResponse {
@@ -74,7 +102,7 @@ pub async fn category_info(
 // Returns specific info for a given itemgroup
 // We must be given a series of correct itemgroup names to traverse
 pub async fn post_item_info(
-    State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
     Json(payload): Json<ItemInfoRequest>,
 ) -> Response {
     let item_path: Vec<&str> = payload.item_path.iter().map(AsRef::as_ref).collect();
@@ -91,7 +119,7 @@ pub async fn post_item_info(
     }
 
     let category_id = &item_path[0];
-    let category = match data.categories.iter().find(|c| c.id == *category_id) {
+    let category_item = match cache.category(category_id) {
         Some(cat) => cat,
         None => {
             return (
@@ -104,18 +132,8 @@ pub async fn post_item_info(
         }
     };
 
-    let category_path = build_category_base_path(&data.agent, category);
-
     if item_path.len() == 1 {
         // Return the category itself
-        let items = filesystem::build_items(&category_path, false);
-        let category_item = filesystem::create_dir_item_group(
-            category.id.clone(),
-            category.name.clone(),
-            &category_path,
-            items,
-            false,
-        );
         return (
             StatusCode::OK,
             Json(ItemInfoResponse {
@@ -127,7 +145,7 @@ pub async fn post_item_info(
 
     // Navigate to the specific item within the category
     let item_path_within_category = &item_path[1..];
-    match filesystem::get_item(&category_path, item_path_within_category) {
+    match cache.get_item(category_id, item_path_within_category) {
         Some(item) => (StatusCode::OK, Json(ItemInfoResponse { item })).into_response(),
         None => (
             StatusCode::NOT_FOUND,
@@ -143,6 +161,8 @@ pub async fn post_item_info(
 // Adds a folder path to .stignore in the appropriate category
 pub async fn post_ignore(
     State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
+    State(history): State<Option<StignoreHistory>>,
     Json(payload): Json<IgnoreRequest>,
 ) -> Response {
     tracing::info!(
@@ -159,6 +179,7 @@ pub async fn post_ignore(
                 success: false,
                 message: "Folder path cannot be empty".to_string(),
                 ignored_path: None,
+                preview: None,
             }),
         )
             .into_response();
@@ -174,6 +195,7 @@ pub async fn post_ignore(
                     success: false,
                     message: format!("Category ID '{}' not found", payload.category_id),
                     ignored_path: None,
+                    preview: None,
                 }),
             )
                 .into_response();
@@ -183,7 +205,32 @@ pub async fn post_ignore(
     let category_base_path = build_category_base_path(&data.agent, category);
 
     // Add to .stignore using the folder path components directly
-    match filesystem::add_to_stignore(&category_base_path, &payload.folder_path, &category.name) {
+    let result = {
+        let category_base_path = category_base_path.clone();
+        let folder_path = payload.folder_path.clone();
+        let category_name = category.name.clone();
+        let history = history.map(|h| h.category(&category.id));
+        let dry_run = payload.dry_run;
+        run_blocking(move || {
+            if dry_run {
+                filesystem::preview_add_to_stignore(&category_base_path, &folder_path)
+            } else {
+                filesystem::add_to_stignore(
+                    &category_base_path,
+                    &folder_path,
+                    &category_name,
+                    history.as_ref(),
+                )
+            }
+        })
+        .await
+        .unwrap_or_else(|message| filesystem::StignoreResult::Error { message })
+    };
+    if matches!(result, filesystem::StignoreResult::Success { .. }) {
+        refresh_ignored(cache, &category.id).await;
+    }
+
+    match result {
         filesystem::StignoreResult::Success {
             ignored_path,
             message,
@@ -193,15 +240,47 @@ pub async fn post_ignore(
                 success: true,
                 message,
                 ignored_path: Some(ignored_path),
+                preview: None,
             }),
         )
             .into_response(),
-        filesystem::StignoreResult::AlreadyIgnored { ignored_path } => (
+        filesystem::StignoreResult::AlreadyIgnored { ignored_path }
+        | filesystem::StignoreResult::UserOwned { ignored_path } => (
             StatusCode::OK,
             Json(IgnoreResponse {
                 success: true,
                 message: "Path is already ignored".to_string(),
                 ignored_path: Some(ignored_path),
+                preview: None,
+            }),
+        )
+            .into_response(),
+        filesystem::StignoreResult::Preview {
+            ignored_path,
+            preview,
+        } => (
+            StatusCode::OK,
+            Json(IgnoreResponse {
+                success: true,
+                message: match &preview.line {
+                    Some(line) => format!(
+                        "Dry run: would add '{}' to .stignore in category '{}'",
+                        line, category.name
+                    ),
+                    None => "Dry run: path is already ignored".to_string(),
+                },
+                ignored_path: Some(ignored_path),
+                preview: Some(preview),
+            }),
+        )
+            .into_response(),
+        filesystem::StignoreResult::InvalidPath { message } => (
+            StatusCode::BAD_REQUEST,
+            Json(IgnoreResponse {
+                success: false,
+                message: format!("Invalid path: {}", message),
+                ignored_path: None,
+                preview: None,
             }),
         )
             .into_response(),
@@ -211,17 +290,41 @@ pub async fn post_ignore(
                 success: false,
                 message,
                 ignored_path: None,
+                preview: None,
             }),
         )
             .into_response(),
     }
 }
 
+/// Runs filesystem work on the blocking pool, .stignore writes wait on the file lock and
+/// fsync and deletes can walk or copy whole directory trees
+async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|err| format!("Filesystem task failed: {}", err))
+}
+
+/// Updates the cached ignore flags after a write to .stignore.
+/// Don't wait for the watcher, the caller will list the category straight away.
+async fn refresh_ignored(cache: TreeCache, category_id: &str) {
+    let category_id = category_id.to_string();
+    tokio::task::spawn_blocking(move || cache.refresh_ignored(&category_id))
+        .await
+        .ok();
+}
+
 // POST unignore
-// Removes an item from .stignore file
+// Removes an item from the managed block of .stignore, lines written by hand only on request
 pub async fn post_unignore(
     State(data): State<AgentData>,
-    Json(payload): Json<IgnoreRequest>,
+    State(cache): State<TreeCache>,
+    State(history): State<Option<StignoreHistory>>,
+    Json(payload): Json<UnignoreRequest>,
 ) -> Response {
     tracing::info!(
         "Processing unignore request for category: '{}', folder_path: {:?}",
@@ -236,6 +339,7 @@ pub async fn post_unignore(
                 success: false,
                 message: "Folder path cannot be empty".to_string(),
                 ignored_path: None,
+                preview: None,
             }),
         )
             .into_response();
@@ -250,15 +354,47 @@ pub async fn post_unignore(
                     success: false,
                     message: format!("Category ID '{}' not found", payload.category_id),
                     ignored_path: None,
+                    preview: None,
                 }),
             )
                 .into_response();
         }
     };
 
+    if payload.dry_run {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(IgnoreResponse {
+                success: false,
+                message: "Dry run is not supported for unignore".to_string(),
+                ignored_path: None,
+                preview: None,
+            }),
+        )
+            .into_response();
+    }
+
     let category_base_path = build_category_base_path(&data.agent, category);
-    let result =
-        filesystem::remove_from_stignore(&category_base_path, &payload.folder_path, &category.name);
+    let result = {
+        let folder_path = payload.folder_path.clone();
+        let category_name = category.name.clone();
+        let history = history.map(|h| h.category(&category.id));
+        let include_user_entries = payload.include_user_entries;
+        run_blocking(move || {
+            filesystem::remove_from_stignore(
+                &category_base_path,
+                &folder_path,
+                &category_name,
+                include_user_entries,
+                history.as_ref(),
+            )
+        })
+        .await
+        .unwrap_or_else(|message| filesystem::StignoreResult::Error { message })
+    };
+    if matches!(result, filesystem::StignoreResult::Success { .. }) {
+        refresh_ignored(cache, &category.id).await;
+    }
 
     match result {
         filesystem::StignoreResult::Success {
@@ -270,15 +406,41 @@ pub async fn post_unignore(
                 success: true,
                 message,
                 ignored_path: Some(ignored_path),
+                preview: None,
             }),
         )
             .into_response(),
-        filesystem::StignoreResult::AlreadyIgnored { ignored_path } => (
+        filesystem::StignoreResult::AlreadyIgnored { ignored_path }
+        | filesystem::StignoreResult::Preview { ignored_path, .. } => (
             StatusCode::OK,
             Json(IgnoreResponse {
                 success: true,
                 message: "Path is not ignored".to_string(),
                 ignored_path: Some(ignored_path),
+                preview: None,
+            }),
+        )
+            .into_response(),
+        filesystem::StignoreResult::UserOwned { ignored_path } => (
+            StatusCode::CONFLICT,
+            Json(IgnoreResponse {
+                success: false,
+                message: format!(
+                    "'{}' was added to .stignore by hand, set include_user_entries to remove it anyway",
+                    ignored_path
+                ),
+                ignored_path: Some(ignored_path),
+                preview: None,
+            }),
+        )
+            .into_response(),
+        filesystem::StignoreResult::InvalidPath { message } => (
+            StatusCode::BAD_REQUEST,
+            Json(IgnoreResponse {
+                success: false,
+                message: format!("Invalid path: {}", message),
+                ignored_path: None,
+                preview: None,
             }),
         )
             .into_response(),
@@ -288,6 +450,7 @@ pub async fn post_unignore(
                 success: false,
                 message,
                 ignored_path: None,
+                preview: None,
             }),
         )
             .into_response(),
@@ -304,7 +467,10 @@ pub async fn post_ignore_status(
     if payload.folder_path.is_empty() {
         return (
             StatusCode::BAD_REQUEST,
-            Json(IgnoreStatusResponse { ignored: false }),
+            Json(IgnoreStatusResponse {
+                ignored: false,
+                matched_rule: None,
+            }),
         )
             .into_response();
     }
@@ -315,7 +481,10 @@ pub async fn post_ignore_status(
         None => {
             return (
                 StatusCode::BAD_REQUEST,
-                Json(IgnoreStatusResponse { ignored: false }),
+                Json(IgnoreStatusResponse {
+                    ignored: false,
+                    matched_rule: None,
+                }),
             )
                 .into_response();
         }
@@ -323,10 +492,18 @@ pub async fn post_ignore_status(
 
     let category_base_path = build_category_base_path(&data.agent, category);
 
-    // Check if the folder path is ignored
-    let ignored = filesystem::is_path_ignored(&category_base_path, &payload.folder_path);
+    // Check if the folder path, or one of its parents, is ignored
+    let (ignored, matched_rule) =
+        filesystem::ignore_status(&category_base_path, &payload.folder_path);
 
-    (StatusCode::OK, Json(IgnoreStatusResponse { ignored })).into_response()
+    (
+        StatusCode::OK,
+        Json(IgnoreStatusResponse {
+            ignored,
+            matched_rule,
+        }),
+    )
+        .into_response()
 }
 
 // POST ignore-status-bulk
@@ -339,18 +516,18 @@ pub async fn post_ignore_status_bulk(
 
     for item in payload.items {
         // Use the same logic as the single ignore status check
-        let ignored = if item.folder_path.is_empty() {
-            false
+        let (ignored, matched_rule) = if item.folder_path.is_empty() {
+            (false, None)
         } else {
             // Find the category by matching the category ID
             match data.categories.iter().find(|c| c.id == item.category_id) {
                 Some(category) => {
                     let category_base_path = build_category_base_path(&data.agent, category);
 
-                    // Check if the folder path is ignored
-                    filesystem::is_path_ignored(&category_base_path, &item.folder_path)
+                    // Check if the folder path, or one of its parents, is ignored
+                    filesystem::ignore_status(&category_base_path, &item.folder_path)
                 }
-                None => false, // Invalid category
+                None => (false, None), // Invalid category
             }
         };
 
@@ -358,6 +535,7 @@ pub async fn post_ignore_status_bulk(
             category_id: item.category_id,
             folder_path: item.folder_path,
             ignored,
+            matched_rule,
         });
     }
 
@@ -372,6 +550,8 @@ pub async fn post_ignore_status_bulk(
 // Deletes a folder path from the filesystem
 pub async fn post_delete(
     State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
+    State(trash): State<Option<Trash>>,
     Json(payload): Json<DeleteRequest>,
 ) -> Response {
     tracing::info!(
@@ -388,6 +568,8 @@ pub async fn post_delete(
                 success: false,
                 message: "Folder path cannot be empty".to_string(),
                 deleted_path: None,
+                trash_id: None,
+                preview: None,
             }),
         )
             .into_response();
@@ -403,6 +585,8 @@ pub async fn post_delete(
                     success: false,
                     message: format!("Category ID '{}' not found", payload.category_id),
                     deleted_path: None,
+                    trash_id: None,
+                    preview: None,
                 }),
             )
                 .into_response();
@@ -411,21 +595,75 @@ pub async fn post_delete(
 
     let category_base_path = build_category_base_path(&data.agent, category);
 
-    // Delete from filesystem
-    match filesystem::delete_from_filesystem(
-        &category_base_path,
-        &payload.folder_path,
-        &category.name,
-    ) {
+    // Delete from filesystem, or only report what would go
+    let result = {
+        let category_base_path = category_base_path.clone();
+        let folder_path = payload.folder_path.clone();
+        let category = category.clone();
+        let dry_run = payload.dry_run;
+        run_blocking(move || {
+            if dry_run {
+                filesystem::preview_delete(&category_base_path, &folder_path, trash.is_some())
+            } else {
+                filesystem::delete_from_filesystem(
+                    &category_base_path,
+                    &folder_path,
+                    &category,
+                    trash.as_ref(),
+                )
+            }
+        })
+        .await
+        .unwrap_or_else(|message| filesystem::DeleteResult::Error { message })
+    };
+
+    match result {
         filesystem::DeleteResult::Success {
             deleted_path,
             message,
+            trash_id,
+        } => {
+            // Don't wait for the watcher, the caller will list the category straight away
+            let removed = payload
+                .folder_path
+                .iter()
+                .fold(category_base_path.clone(), |path, c| path.join(c));
+            tokio::task::spawn_blocking(move || cache.refresh_paths([removed]))
+                .await
+                .ok();
+
+            (
+                StatusCode::OK,
+                Json(DeleteResponse {
+                    success: true,
+                    message,
+                    deleted_path: Some(deleted_path),
+                    trash_id,
+                    preview: None,
+                }),
+            )
+                .into_response()
+        }
+        filesystem::DeleteResult::Preview {
+            requested_path,
+            preview,
         } => (
             StatusCode::OK,
             Json(DeleteResponse {
                 success: true,
-                message,
-                deleted_path: Some(deleted_path),
+                message: if preview.exists {
+                    format!(
+                        "Dry run: would delete '{}' ({} files) from category '{}'",
+                        requested_path,
+                        preview.files.len(),
+                        category.name
+                    )
+                } else {
+                    format!("Dry run: path '{}' does not exist", requested_path)
+                },
+                deleted_path: None,
+                trash_id: None,
+                preview: Some(preview),
             }),
         )
             .into_response(),
@@ -435,6 +673,19 @@ pub async fn post_delete(
                 success: false,
                 message: format!("Path '{}' not found", requested_path),
                 deleted_path: None,
+                trash_id: None,
+                preview: None,
+            }),
+        )
+            .into_response(),
+        filesystem::DeleteResult::InvalidPath { message } => (
+            StatusCode::BAD_REQUEST,
+            Json(DeleteResponse {
+                success: false,
+                message: format!("Invalid path: {}", message),
+                deleted_path: None,
+                trash_id: None,
+                preview: None,
             }),
         )
             .into_response(),
@@ -444,123 +695,662 @@ pub async fn post_delete(
                 success: false,
                 message,
                 deleted_path: None,
+                trash_id: None,
+                preview: None,
             }),
         )
             .into_response(),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::Router;
-    use axum::http::StatusCode;
-    use axum_test::TestServer;
-    use std::fs;
-    use stignore_lib::{AgentConfig, AgentData, Category};
-    use tempfile::TempDir;
+/// Builds the response for a failed trash operation
+fn trash_error_response(err: TrashError) -> Response {
+    let status = match err {
+        TrashError::NotFound { .. } => StatusCode::NOT_FOUND,
+        TrashError::Conflict { .. } => StatusCode::CONFLICT,
+        TrashError::Io { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(TrashResponse {
+            success: false,
+            message: err.to_string(),
+        }),
+    )
+        .into_response()
+}
 
-    // Test constants
-    const MOVIES_ID: &str = "movies";
-    const NONEXISTENT_ID: &str = "nonexistent_id";
+fn trash_disabled_response() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(TrashResponse {
+            success: false,
+            message: "Trash is not enabled on this agent".to_string(),
+        }),
+    )
+        .into_response()
+}
 
-    struct TestDirectoryPaths {
-        movie1: std::path::PathBuf,
-        movie2: std::path::PathBuf,
-        show1_season1: std::path::PathBuf,
-        show1_season2: std::path::PathBuf,
-        show2_season1: std::path::PathBuf,
-        show3_season1: std::path::PathBuf,
-        show3_season2: std::path::PathBuf,
-        show3_season3: std::path::PathBuf,
-    }
+// GET trash
+// Lists the items deleted into the agent's trash
+pub async fn trash_list(State(trash): State<Option<Trash>>) -> Response {
+    let response = match trash {
+        Some(trash) => TrashListResponse {
+            enabled: true,
+            retention_days: trash.retention_days(),
+            items: trash.list(),
+        },
+        None => TrashListResponse {
+            enabled: false,
+            retention_days: 0,
+            items: vec![],
+        },
+    };
 
-    fn create_directory_structure(temp_dir: &TempDir) -> TestDirectoryPaths {
-        let movies_dir = temp_dir.path().join("movies");
-        let tv_dir = temp_dir.path().join("tv");
-        fs::create_dir_all(&movies_dir).unwrap();
-        fs::create_dir_all(&tv_dir).unwrap();
+    (StatusCode::OK, Json(response)).into_response()
+}
 
-        // Create Syncthing system files/folders that should be filtered out
-        fs::write(movies_dir.join(".stfolder"), "").unwrap();
-        fs::write(movies_dir.join(".stignore"), "").unwrap(); // Empty .stignore for other tests
-        fs::create_dir_all(movies_dir.join(".stversions")).unwrap();
-        fs::write(tv_dir.join(".stfolder"), "").unwrap();
+// POST trash restore
+// Moves a trashed item back to where it was deleted from
+pub async fn post_trash_restore(
+    State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
+    State(trash): State<Option<Trash>>,
+    Json(payload): Json<TrashRestoreRequest>,
+) -> Response {
+    tracing::info!("Processing trash restore request for '{}'", payload.id);
 
-        // Create Movies structure
-        let movie1_dir = movies_dir.join("Movie 1 (2023)");
-        let movie2_dir = movies_dir.join("Movie 2 (2024)");
-        fs::create_dir_all(&movie1_dir).unwrap();
-        fs::create_dir_all(&movie2_dir).unwrap();
+    let Some(trash) = trash else {
+        return trash_disabled_response();
+    };
 
-        // Create TV show structure
-        let shows = [
-            ("Show 1 (2021)", vec!["Season 1", "Season 2"]),
-            ("Show 2 (2022)", vec!["Season 1"]),
-            ("Show 3 (2023)", vec!["Season 1", "Season 2", "Season 3"]),
-        ];
+    let entry = match trash.get(&payload.id) {
+        Ok(entry) => entry,
+        Err(err) => return trash_error_response(err),
+    };
 
-        for (show_name, seasons) in shows {
-            let show_dir = tv_dir.join(show_name);
-            fs::create_dir_all(&show_dir).unwrap();
+    let category = match data.categories.iter().find(|c| c.id == entry.category_id) {
+        Some(cat) => cat,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(TrashResponse {
+                    success: false,
+                    message: format!("Category ID '{}' not found", entry.category_id),
+                }),
+            )
+                .into_response();
+        }
+    };
 
-            for season in seasons {
-                fs::create_dir_all(show_dir.join(season)).unwrap();
-            }
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let destination = match filesystem::resolve_folder_path(&category_base_path, &entry.folder_path)
+    {
+        Ok(destination) => destination,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(TrashResponse {
+                    success: false,
+                    message: format!("Invalid path: {}", message),
+                }),
+            )
+                .into_response();
         }
+    };
 
-        TestDirectoryPaths {
-            movie1: movie1_dir,
-            movie2: movie2_dir,
-            show1_season1: tv_dir.join("Show 1 (2021)").join("Season 1"),
-            show1_season2: tv_dir.join("Show 1 (2021)").join("Season 2"),
-            show2_season1: tv_dir.join("Show 2 (2022)").join("Season 1"),
-            show3_season1: tv_dir.join("Show 3 (2023)").join("Season 1"),
-            show3_season2: tv_dir.join("Show 3 (2023)").join("Season 2"),
-            show3_season3: tv_dir.join("Show 3 (2023)").join("Season 3"),
+    let result = {
+        let id = payload.id.clone();
+        let destination = destination.clone();
+        run_blocking(move || trash.restore(&id, &destination))
+            .await
+            .unwrap_or_else(|message| Err(TrashError::Io { message }))
+    };
+    match result {
+        Ok(entry) => {
+            tokio::task::spawn_blocking(move || cache.refresh_paths([destination]))
+                .await
+                .ok();
+
+            (
+                StatusCode::OK,
+                Json(TrashResponse {
+                    success: true,
+                    message: format!(
+                        "Restored '{}' to category '{}'",
+                        entry.folder_path.join("/"),
+                        category.name
+                    ),
+                }),
+            )
+                .into_response()
         }
+        Err(err) => trash_error_response(err),
     }
+}
 
-    fn create_test_files(paths: &TestDirectoryPaths) {
-        // Create movie files
-        fs::write(
-            paths.movie1.join("Movie 1 (2023).mkv"),
-            "test movie 1 content",
-        )
-        .unwrap();
-        fs::write(
-            paths.movie2.join("Movie 2 (2024).mp4"),
-            "test movie 2 content",
-        )
-        .unwrap();
+// POST trash purge
+// Permanently removes one item from the trash, or everything when `all` is set
+pub async fn post_trash_purge(
+    State(trash): State<Option<Trash>>,
+    Json(payload): Json<TrashPurgeRequest>,
+) -> Response {
+    tracing::info!("Processing trash purge request for {:?}", payload.id);
 
-        // Create TV show files
-        let tv_episodes = [
-            (
-                &paths.show1_season1,
-                vec!["S01E01 - Ep 1.mkv", "S01E02 - Ep 2.mkv"],
-            ),
-            (
-                &paths.show1_season2,
-                vec![
-                    "S02E01 - Ep 1.mkv",
-                    "S02E02 - Ep 2.mkv",
-                    "S02E03 - Ep 3.mkv",
-                ],
-            ),
-            (&paths.show2_season1, vec!["S01E01 - Ep 1.mkv"]),
-            (&paths.show3_season1, vec!["S01E01 - Ep 1.mkv"]),
-            (
-                &paths.show3_season2,
-                vec!["S02E01 - Ep 1.mkv", "S02E02 - Ep 2.mkv"],
-            ),
-            (
-                &paths.show3_season3,
-                vec![
-                    "S03E01 - Ep 1.mkv",
-                    "S03E02 - Ep 2.mkv",
-                    "S03E03 - Ep 3.mkv",
-                ],
+    let Some(trash) = trash else {
+        return trash_disabled_response();
+    };
+
+    let result = match (payload.id, payload.all) {
+        (Some(id), false) => {
+            run_blocking(move || {
+                trash
+                    .purge(&id)
+                    .map(|entry| format!("Purged '{}' from trash", entry.folder_path.join("/")))
+            })
+            .await
+        }
+        (None, true) => {
+            run_blocking(move || {
+                let mut purged = 0;
+                for entry in trash.list() {
+                    trash.purge(&entry.id)?;
+                    purged += 1;
+                }
+                Ok(format!("Purged {} items from trash", purged))
+            })
+            .await
+        }
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(TrashResponse {
+                    success: false,
+                    message: "Either an item ID or all: true is required".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    };
+    let message = match result.unwrap_or_else(|message| Err(TrashError::Io { message })) {
+        Ok(message) => message,
+        Err(err) => return trash_error_response(err),
+    };
+
+    (
+        StatusCode::OK,
+        Json(TrashResponse {
+            success: true,
+            message,
+        }),
+    )
+        .into_response()
+}
+
+fn history_error_response(err: HistoryError) -> Response {
+    let status = match err {
+        HistoryError::NotFound { .. } => StatusCode::NOT_FOUND,
+        HistoryError::Io { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(StignoreRevertResponse {
+            success: false,
+            message: err.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+fn history_disabled_response() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(StignoreRevertResponse {
+            success: false,
+            message: ".stignore history is not enabled on this agent".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+fn unknown_category_response(category_id: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(StignoreRevertResponse {
+            success: false,
+            message: format!("Category ID '{}' not found", category_id),
+        }),
+    )
+        .into_response()
+}
+
+// GET history
+// Lists the saved .stignore revisions of every category
+pub async fn history_list(
+    State(data): State<AgentData>,
+    State(history): State<Option<StignoreHistory>>,
+) -> Response {
+    let response = match history {
+        Some(history) => StignoreHistoryResponse {
+            enabled: true,
+            retention: history.retention(),
+            categories: data
+                .categories
+                .iter()
+                .map(|category| StignoreHistoryCategory {
+                    id: category.id.clone(),
+                    name: category.name.clone(),
+                    revisions: history.category(&category.id).list(),
+                })
+                .collect(),
+        },
+        None => StignoreHistoryResponse {
+            enabled: false,
+            retention: 0,
+            categories: vec![],
+        },
+    };
+
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+// POST history diff
+// Compares two saved revisions of a category's .stignore, or one with the current file
+pub async fn post_history_diff(
+    State(data): State<AgentData>,
+    State(history): State<Option<StignoreHistory>>,
+    Json(payload): Json<StignoreDiffRequest>,
+) -> Response {
+    let Some(history) = history else {
+        return history_disabled_response();
+    };
+    let Some(category) = data.categories.iter().find(|c| c.id == payload.category_id) else {
+        return unknown_category_response(&payload.category_id);
+    };
+    let category_history = history.category(&category.id);
+
+    let revision_content = |id: &str| category_history.get(id).map(|(_, content)| content);
+    let from = match &payload.from {
+        Some(id) => revision_content(id),
+        None => Ok(String::new()),
+    };
+    let to = match &payload.to {
+        Some(id) => revision_content(id),
+        None => {
+            let category_base_path = build_category_base_path(&data.agent, category);
+            Ok(std::fs::read_to_string(category_base_path.join(".stignore")).unwrap_or_default())
+        }
+    };
+
+    match (from, to) {
+        (Ok(from), Ok(to)) => (
+            StatusCode::OK,
+            Json(StignoreDiffResponse {
+                success: true,
+                message: String::new(),
+                lines: history::diff_lines(&from, &to),
+            }),
+        )
+            .into_response(),
+        (Err(err), _) | (_, Err(err)) => history_error_response(err),
+    }
+}
+
+// POST history revert
+// Replaces a category's .stignore with a saved revision
+pub async fn post_history_revert(
+    State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
+    State(history): State<Option<StignoreHistory>>,
+    Json(payload): Json<StignoreRevertRequest>,
+) -> Response {
+    tracing::info!(
+        "Processing .stignore revert request for category: '{}', revision: '{}'",
+        payload.category_id,
+        payload.id
+    );
+
+    let Some(history) = history else {
+        return history_disabled_response();
+    };
+    let Some(category) = data.categories.iter().find(|c| c.id == payload.category_id) else {
+        return unknown_category_response(&payload.category_id);
+    };
+
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let category_history = history.category(&category.id);
+    let revision_id = payload.id.clone();
+    let result = run_blocking(move || {
+        filesystem::revert_stignore(&category_base_path, &category_history, &revision_id)
+    })
+    .await
+    .unwrap_or_else(|message| Err(HistoryError::Io { message }));
+    match result {
+        Ok(revision) => {
+            refresh_ignored(cache, &category.id).await;
+            (
+                StatusCode::OK,
+                Json(StignoreRevertResponse {
+                    success: true,
+                    message: format!(
+                        "Reverted .stignore in category '{}' to revision {}",
+                        category.name, revision.id
+                    ),
+                }),
+            )
+                .into_response()
+        }
+        Err(err) => history_error_response(err),
+    }
+}
+
+fn stignore_content_response(
+    status: StatusCode,
+    message: String,
+    content: String,
+    etag: String,
+) -> Response {
+    let sections = ManagedStignore::parse(&content);
+    let managed_entries = sections
+        .managed_entries()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let user_entries = sections
+        .user_entries()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    (
+        status,
+        [(header::ETAG, etag.clone())],
+        Json(StignoreContentResponse {
+            success: status.is_success(),
+            message,
+            content,
+            etag,
+            managed_entries,
+            user_entries,
+        }),
+    )
+        .into_response()
+}
+
+fn stignore_failure_response(status: StatusCode, message: String) -> Response {
+    (
+        status,
+        Json(StignoreContentResponse {
+            success: false,
+            message,
+            content: String::new(),
+            etag: String::new(),
+            managed_entries: vec![],
+            user_entries: vec![],
+        }),
+    )
+        .into_response()
+}
+
+// GET category stignore
+// Returns a category's whole .stignore, its ETag is needed to replace it
+pub async fn get_stignore(
+    State(data): State<AgentData>,
+    Path(category_id): Path<String>,
+) -> Response {
+    let Some(category) = data.categories.iter().find(|c| c.id == category_id) else {
+        return stignore_failure_response(
+            StatusCode::NOT_FOUND,
+            format!("Category ID '{}' not found", category_id),
+        );
+    };
+
+    let category_base_path = build_category_base_path(&data.agent, category);
+    match filesystem::read_stignore(&category_base_path) {
+        Ok((content, etag)) => {
+            stignore_content_response(StatusCode::OK, String::new(), content, etag)
+        }
+        Err(message) => stignore_failure_response(StatusCode::INTERNAL_SERVER_ERROR, message),
+    }
+}
+
+// PUT category stignore
+// Replaces a category's whole .stignore, only if `If-Match` holds the ETag of its current content
+pub async fn put_stignore(
+    State(data): State<AgentData>,
+    State(cache): State<TreeCache>,
+    State(history): State<Option<StignoreHistory>>,
+    Path(category_id): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<StignoreWriteRequest>,
+) -> Response {
+    tracing::info!(
+        "Processing .stignore replace request for category: '{}'",
+        category_id
+    );
+
+    let Some(category) = data.categories.iter().find(|c| c.id == category_id) else {
+        return stignore_failure_response(
+            StatusCode::NOT_FOUND,
+            format!("Category ID '{}' not found", category_id),
+        );
+    };
+    let Some(if_match) = headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return stignore_failure_response(
+            StatusCode::PRECONDITION_REQUIRED,
+            "If-Match header with the ETag of the current .stignore is required".to_string(),
+        );
+    };
+
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let result = {
+        let content = payload.content.clone();
+        let if_match = if_match.trim().to_string();
+        let history = history.map(|h| h.category(&category.id));
+        run_blocking(move || {
+            filesystem::replace_stignore(&category_base_path, &content, &if_match, history.as_ref())
+        })
+        .await
+        .unwrap_or_else(|message| filesystem::StignoreWriteResult::Error { message })
+    };
+    match result {
+        filesystem::StignoreWriteResult::Written { etag } => {
+            refresh_ignored(cache, &category.id).await;
+            stignore_content_response(
+                StatusCode::OK,
+                format!("Saved .stignore in category '{}'", category.name),
+                payload.content,
+                etag,
+            )
+        }
+        filesystem::StignoreWriteResult::Conflict { content, etag } => stignore_content_response(
+            StatusCode::PRECONDITION_FAILED,
+            format!(
+                ".stignore in category '{}' was changed since it was loaded",
+                category.name
+            ),
+            content,
+            etag,
+        ),
+        filesystem::StignoreWriteResult::Invalid { message } => {
+            stignore_failure_response(StatusCode::BAD_REQUEST, message)
+        }
+        filesystem::StignoreWriteResult::Error { message } => {
+            stignore_failure_response(StatusCode::INTERNAL_SERVER_ERROR, message)
+        }
+    }
+}
+
+// POST adopt stignore entries
+// Moves lines written by hand into the managed block, for files from before it existed
+pub async fn post_stignore_adopt(
+    State(data): State<AgentData>,
+    State(history): State<Option<StignoreHistory>>,
+    Path(category_id): Path<String>,
+    Json(payload): Json<StignoreAdoptRequest>,
+) -> Response {
+    let Some(category) = data.categories.iter().find(|c| c.id == category_id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(StignoreAdoptResponse {
+                success: false,
+                message: format!("Category ID '{}' not found", category_id),
+                adopted: vec![],
+            }),
+        )
+            .into_response();
+    };
+
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let history = history.map(|h| h.category(&category.id));
+    let result = run_blocking(move || {
+        filesystem::adopt_stignore_entries(
+            &category_base_path,
+            payload.entries.as_deref(),
+            history.as_ref(),
+        )
+    })
+    .await
+    .and_then(|result| result);
+    match result {
+        Ok(adopted) => (
+            StatusCode::OK,
+            Json(StignoreAdoptResponse {
+                success: true,
+                message: format!(
+                    "Moved {} entries into the managed block of .stignore in category '{}'",
+                    adopted.len(),
+                    category.name
+                ),
+                adopted,
+            }),
+        )
+            .into_response(),
+        Err(message) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(StignoreAdoptResponse {
+                success: false,
+                message,
+                adopted: vec![],
+            }),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum_test::TestServer;
+    use std::fs;
+    use stignore_lib::{AgentConfig, AgentData, Category};
+    use tempfile::TempDir;
+
+    // Test constants
+    const MOVIES_ID: &str = "movies";
+    const NONEXISTENT_ID: &str = "nonexistent_id";
+
+    struct TestDirectoryPaths {
+        movie1: std::path::PathBuf,
+        movie2: std::path::PathBuf,
+        show1_season1: std::path::PathBuf,
+        show1_season2: std::path::PathBuf,
+        show2_season1: std::path::PathBuf,
+        show3_season1: std::path::PathBuf,
+        show3_season2: std::path::PathBuf,
+        show3_season3: std::path::PathBuf,
+    }
+
+    fn create_directory_structure(temp_dir: &TempDir) -> TestDirectoryPaths {
+        let movies_dir = temp_dir.path().join("movies");
+        let tv_dir = temp_dir.path().join("tv");
+        fs::create_dir_all(&movies_dir).unwrap();
+        fs::create_dir_all(&tv_dir).unwrap();
+
+        // Create Syncthing system files/folders that should be filtered out
+        fs::write(movies_dir.join(".stfolder"), "").unwrap();
+        fs::write(movies_dir.join(".stignore"), "").unwrap(); // Empty .stignore for other tests
+        fs::create_dir_all(movies_dir.join(".stversions")).unwrap();
+        fs::write(tv_dir.join(".stfolder"), "").unwrap();
+
+        // Create Movies structure
+        let movie1_dir = movies_dir.join("Movie 1 (2023)");
+        let movie2_dir = movies_dir.join("Movie 2 (2024)");
+        fs::create_dir_all(&movie1_dir).unwrap();
+        fs::create_dir_all(&movie2_dir).unwrap();
+
+        // Create TV show structure
+        let shows = [
+            ("Show 1 (2021)", vec!["Season 1", "Season 2"]),
+            ("Show 2 (2022)", vec!["Season 1"]),
+            ("Show 3 (2023)", vec!["Season 1", "Season 2", "Season 3"]),
+        ];
+
+        for (show_name, seasons) in shows {
+            let show_dir = tv_dir.join(show_name);
+            fs::create_dir_all(&show_dir).unwrap();
+
+            for season in seasons {
+                fs::create_dir_all(show_dir.join(season)).unwrap();
+            }
+        }
+
+        TestDirectoryPaths {
+            movie1: movie1_dir,
+            movie2: movie2_dir,
+            show1_season1: tv_dir.join("Show 1 (2021)").join("Season 1"),
+            show1_season2: tv_dir.join("Show 1 (2021)").join("Season 2"),
+            show2_season1: tv_dir.join("Show 2 (2022)").join("Season 1"),
+            show3_season1: tv_dir.join("Show 3 (2023)").join("Season 1"),
+            show3_season2: tv_dir.join("Show 3 (2023)").join("Season 2"),
+            show3_season3: tv_dir.join("Show 3 (2023)").join("Season 3"),
+        }
+    }
+
+    fn create_test_files(paths: &TestDirectoryPaths) {
+        // Create movie files
+        fs::write(
+            paths.movie1.join("Movie 1 (2023).mkv"),
+            "test movie 1 content",
+        )
+        .unwrap();
+        fs::write(
+            paths.movie2.join("Movie 2 (2024).mp4"),
+            "test movie 2 content",
+        )
+        .unwrap();
+
+        // Create TV show files
+        let tv_episodes = [
+            (
+                &paths.show1_season1,
+                vec!["S01E01 - Ep 1.mkv", "S01E02 - Ep 2.mkv"],
+            ),
+            (
+                &paths.show1_season2,
+                vec![
+                    "S02E01 - Ep 1.mkv",
+                    "S02E02 - Ep 2.mkv",
+                    "S02E03 - Ep 3.mkv",
+                ],
+            ),
+            (&paths.show2_season1, vec!["S01E01 - Ep 1.mkv"]),
+            (&paths.show3_season1, vec!["S01E01 - Ep 1.mkv"]),
+            (
+                &paths.show3_season2,
+                vec!["S02E01 - Ep 1.mkv", "S02E02 - Ep 2.mkv"],
+            ),
+            (
+                &paths.show3_season3,
+                vec![
+                    "S03E01 - Ep 1.mkv",
+                    "S03E02 - Ep 2.mkv",
+                    "S03E03 - Ep 3.mkv",
+                ],
             ),
         ];
 
@@ -584,17 +1374,27 @@ mod tests {
                 port: 3000,
                 base_path,
                 api_key: "550e8400-e29b-41d4-a716-446655440000".to_string(),
+                trash_path: None,
+                trash_retention_days: 30,
+                history_path: None,
+                history_retention: 100,
+                enrolment: None,
+                exclude: vec![],
             },
             categories: vec![
                 Category {
                     id: "movies".to_string(),
                     name: "Movies".to_string(),
                     relative_path: "movies".to_string(),
+                    leaf_depth: None,
+                    exclude: vec![],
                 },
                 Category {
                     id: "tv".to_string(),
                     name: "TV Shows".to_string(),
                     relative_path: "tv".to_string(),
+                    leaf_depth: None,
+                    exclude: vec![],
                 },
             ],
         };
@@ -603,10 +1403,31 @@ mod tests {
     }
 
     fn create_test_router(data: AgentData) -> Router {
+        let cache = TreeCache::new(&data);
+        cache.rebuild_all();
+        cache.start_watching().unwrap();
+        let state = crate::AgentState {
+            trash: Trash::from_config(&data.agent),
+            history: StignoreHistory::from_config(&data.agent),
+            data: data.clone(),
+            cache,
+            started_at: std::time::Instant::now(),
+        };
+
         Router::new()
             .route("/", axum::routing::get(help))
+            .route("/api/v1/health", axum::routing::get(health))
             .route("/api/v1/categories", axum::routing::get(category_list))
+            .route("/api/v1/summary", axum::routing::get(category_summary))
             .route("/api/v1/categories/{id}", axum::routing::get(category_info))
+            .route(
+                "/api/v1/categories/{id}/stignore",
+                axum::routing::get(get_stignore).put(put_stignore),
+            )
+            .route(
+                "/api/v1/categories/{id}/stignore/adopt",
+                axum::routing::post(post_stignore_adopt),
+            )
             .route("/api/v1/items", axum::routing::post(post_item_info))
             .route("/api/v1/ignore", axum::routing::post(post_ignore))
             .route("/api/v1/unignore", axum::routing::post(post_unignore))
@@ -619,11 +1440,26 @@ mod tests {
                 axum::routing::post(post_ignore_status_bulk),
             )
             .route("/api/v1/delete", axum::routing::post(post_delete))
+            .route("/api/v1/trash", axum::routing::get(trash_list))
+            .route(
+                "/api/v1/trash/restore",
+                axum::routing::post(post_trash_restore),
+            )
+            .route("/api/v1/trash/purge", axum::routing::post(post_trash_purge))
+            .route("/api/v1/history", axum::routing::get(history_list))
+            .route(
+                "/api/v1/history/diff",
+                axum::routing::post(post_history_diff),
+            )
+            .route(
+                "/api/v1/history/revert",
+                axum::routing::post(post_history_revert),
+            )
             .layer(axum::middleware::from_fn_with_state(
                 data.clone(),
                 crate::auth_middleware,
             ))
-            .with_state(data)
+            .with_state(state)
     }
 
     async fn setup_test_server() -> (TestServer, TempDir) {
@@ -633,6 +1469,75 @@ mod tests {
         (server, temp_dir)
     }
 
+    /// Like `setup_test_server`, keeping .stignore history next to the categories
+    async fn setup_test_server_with_history(retention: usize) -> (TestServer, TempDir) {
+        let (mut data, temp_dir) = create_test_data();
+        data.agent.history_path = Some(
+            temp_dir
+                .path()
+                .join(".history")
+                .to_string_lossy()
+                .to_string(),
+        );
+        data.agent.history_retention = retention;
+        let app = create_test_router(data);
+        let server = TestServer::new(app).unwrap();
+        (server, temp_dir)
+    }
+
+    async fn change_ignore(server: &TestServer, endpoint: &str, name: &str) {
+        let request_body = IgnoreRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec![name.to_string()],
+            dry_run: false,
+        };
+        server
+            .post(endpoint)
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::OK);
+    }
+
+    async fn movie_revisions(server: &TestServer) -> Vec<StignoreRevision> {
+        let json: StignoreHistoryResponse = server
+            .get("/api/v1/history")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        assert!(json.enabled);
+        json.categories
+            .into_iter()
+            .find(|c| c.id == MOVIES_ID)
+            .unwrap()
+            .revisions
+    }
+
+    /// Like `setup_test_server`, with deletes going to a trash next to the categories
+    async fn setup_test_server_with_trash() -> (TestServer, TempDir) {
+        let (mut data, temp_dir) = create_test_data();
+        data.agent.trash_path = Some(temp_dir.path().join(".trash").to_string_lossy().to_string());
+        let app = create_test_router(data);
+        let server = TestServer::new(app).unwrap();
+        (server, temp_dir)
+    }
+
+    /// Polls the category listing until the tree cache has picked up changes made on disk
+    async fn wait_for_movies(server: &TestServer, ready: impl Fn(&ItemGroup) -> bool) {
+        for _ in 0..50 {
+            let response = server
+                .get("/api/v1/categories")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .await;
+            let json: CategoryListingResponse = response.json();
+            if json.items.iter().any(|c| c.id == MOVIES_ID && ready(c)) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("tree cache did not pick up filesystem changes");
+    }
+
     // Helper endpoint tests
     #[tokio::test]
     async fn test_help_endpoint() {
@@ -644,6 +1549,26 @@ mod tests {
         assert!(text.contains("documentation"));
     }
 
+    #[tokio::test]
+    async fn test_health_endpoint() {
+        let (server, _temp_dir) = setup_test_server_with_trash().await;
+
+        let response = server.get("/api/v1/health").await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        let response = server
+            .get("/api/v1/health")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: HealthResponse = response.json();
+        assert_eq!(json.status, "ok");
+        assert_eq!(json.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(json.api_version, AGENT_API_VERSION);
+        assert!(json.categories.iter().any(|c| c.id == MOVIES_ID));
+        assert_eq!(json.features, vec!["trash".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_unauthorized_access() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -683,6 +1608,37 @@ mod tests {
         assert_eq!(tv_category.name, "TV Shows");
     }
 
+    #[tokio::test]
+    async fn test_category_summary_matches_listing_totals() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let listing: CategoryListingResponse = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+
+        let response = server
+            .get("/api/v1/summary")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: CategorySummaryResponse = response.json();
+        assert_eq!(json.categories.len(), listing.items.len());
+
+        for (summary, tree) in json.categories.iter().zip(&listing.items) {
+            assert_eq!(summary.id, tree.id);
+            assert_eq!(summary.name, tree.name);
+            assert_eq!(summary.size_kb, tree.size_kb);
+            assert_eq!(summary.item_count, tree.items.len());
+            assert_eq!(summary.conflict_count, tree.conflict_count);
+            assert_eq!(summary.stfolder_present, tree.stfolder_present);
+            assert_eq!(summary.stversions_size_kb, tree.stversions_size_kb);
+        }
+        let movies = json.categories.iter().find(|c| c.id == MOVIES_ID).unwrap();
+        assert!(movies.item_count > 0);
+    }
+
     #[tokio::test]
     async fn test_category_list_excludes_syncthing_files() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -725,6 +1681,75 @@ mod tests {
         assert!(!tv_names.contains(&&".stfolder".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_category_list_flags_ignored_items() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        // No waiting for the watcher, the ignore refreshes the cached flags itself
+        change_ignore(&server, "/api/v1/ignore", "Movie 1 (2023)").await;
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CategoryListingResponse = response.json();
+        let movies_category = json.items.iter().find(|item| item.id == "movies").unwrap();
+        assert!(!movies_category.ignored);
+
+        let movie_1 = movies_category
+            .items
+            .iter()
+            .find(|item| item.name == "Movie 1 (2023)")
+            .unwrap();
+        assert!(movie_1.ignored);
+        // Children inherit the ignore from their parent
+        assert!(movie_1.items.iter().all(|item| item.ignored));
+
+        let movie_2 = movies_category
+            .items
+            .iter()
+            .find(|item| item.name == "Movie 2 (2024)")
+            .unwrap();
+        assert!(!movie_2.ignored);
+    }
+
+    #[tokio::test]
+    async fn test_negations_below_ignored_items_are_listed() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let response = server
+            .get("/api/v1/categories/tv/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        let etag = response.header("ETag").to_str().unwrap().to_string();
+        server
+            .put("/api/v1/categories/tv/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .add_header("If-Match", etag.as_str())
+            .json(&StignoreWriteRequest {
+                content: "!/Show 1 (2021)/Season 2\n/Show 1 (2021)\n".to_string(),
+            })
+            .await
+            .assert_status(StatusCode::OK);
+
+        let json: CategoryInfoResponse = server
+            .get("/api/v1/categories/tv")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        let show_1 = json
+            .items
+            .iter()
+            .find(|item| item.name == "Show 1 (2021)")
+            .unwrap();
+        assert!(show_1.ignored);
+        let season = |name: &str| show_1.items.iter().find(|item| item.name == name).unwrap();
+        assert!(season("Season 1").ignored);
+        assert!(!season("Season 2").ignored);
+    }
+
     #[tokio::test]
     async fn test_category_info_found() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -827,6 +1852,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -859,6 +1885,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -881,6 +1908,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec![],
+            dry_run: false,
         };
 
         let response = server
@@ -902,6 +1930,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: NONEXISTENT_ID.to_string(),
             folder_path: vec!["Some Movie".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -927,6 +1956,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -986,12 +2016,186 @@ mod tests {
         let response = server
             .post("/api/v1/ignore-status")
             .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
-            .json(&request_body)
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_status_uses_syncthing_patterns() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let stignore_path = temp_dir.path().join("tv").join(".stignore");
+        std::fs::write(
+            &stignore_path,
+            "// keep the first season\n!/Show C (2000)/Season 1\n(?i)/show c*\n**/sample\n",
+        )
+        .unwrap();
+
+        let cases = [
+            (vec!["Show C (2000)", "Season 1"], false),
+            (vec!["Show C (2000)", "Season 2"], true),
+            (vec!["Show A (1990)"], false),
+            (vec!["Show A (1990)", "Season 1", "sample"], true),
+        ];
+
+        for (folder_path, expected) in cases {
+            let request_body = IgnoreStatusRequest {
+                category_id: "tv".to_string(),
+                folder_path: folder_path.iter().map(|s| s.to_string()).collect(),
+            };
+
+            let response = server
+                .post("/api/v1/ignore-status")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .json(&request_body)
+                .await;
+            response.assert_status(StatusCode::OK);
+
+            let json: IgnoreStatusResponse = response.json();
+            assert_eq!(json.ignored, expected, "{:?}", folder_path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_status_inherited_from_parent() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let stignore_path = temp_dir.path().join("tv").join(".stignore");
+        std::fs::write(&stignore_path, "// shows\nShow 3 (2023)\n").unwrap();
+
+        let request_body = IgnoreStatusRequest {
+            category_id: "tv".to_string(),
+            folder_path: vec!["Show 3 (2023)".to_string(), "Season 2".to_string()],
+        };
+
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+        assert_eq!(
+            json.matched_rule,
+            Some(MatchedIgnoreRule {
+                pattern: "Show 3 (2023)".to_string(),
+                line: 2,
+                inherited_from: Some("Show 3 (2023)".to_string()),
+            })
+        );
+
+        // The parent itself matches directly
+        let request_body = BulkIgnoreStatusRequest {
+            items: vec![IgnoreStatusRequest {
+                category_id: "tv".to_string(),
+                folder_path: vec!["Show 3 (2023)".to_string()],
+            }],
+        };
+        let response = server
+            .post("/api/v1/ignore-status-bulk")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: BulkIgnoreStatusResponse = response.json();
+        let rule = json.items[0].matched_rule.as_ref().unwrap();
+        assert!(json.items[0].ignored);
+        assert_eq!(rule.inherited_from, None);
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_already_matched_by_pattern() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        std::fs::write(&stignore_path, "Movie *\n").unwrap();
+
+        let request_body = IgnoreRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
+        };
+
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: IgnoreResponse = response.json();
+        assert!(json.message.contains("already ignored"));
+        assert_eq!(
+            std::fs::read_to_string(&stignore_path).unwrap(),
+            "Movie *\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_dry_run() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let stignore_path = temp_dir.path().join("tv").join(".stignore");
+        std::fs::write(&stignore_path, "// keep\n!Show 2*\nShow 1*\n").unwrap();
+
+        let dry_run = |folder_path: Vec<&str>| IgnoreRequest {
+            category_id: "tv".to_string(),
+            folder_path: folder_path.into_iter().map(String::from).collect(),
+            dry_run: true,
+        };
+
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&dry_run(vec!["Show 3 (2023)", "Season 1"]))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: IgnoreResponse = response.json();
+        assert!(json.success);
+        let preview = json.preview.unwrap();
+        assert_eq!(preview.line.as_deref(), Some("/Show 3 (2023)/Season 1"));
+        assert!(preview.covering_patterns.is_empty());
+
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&dry_run(vec!["Show 1 (2021)", "Season 2"]))
+            .await;
+        let json: IgnoreResponse = response.json();
+        assert!(json.message.contains("already ignored"));
+        let preview = json.preview.unwrap();
+        assert_eq!(preview.line, None);
+        assert_eq!(
+            preview.covering_patterns,
+            vec![MatchedIgnoreRule {
+                pattern: "Show 1*".to_string(),
+                line: 3,
+                inherited_from: Some("Show 1 (2021)".to_string()),
+            }]
+        );
+
+        // Negated rules are reported too, they would win over the appended line
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&dry_run(vec!["Show 2 (2022)"]))
             .await;
-        response.assert_status(StatusCode::OK);
+        let json: IgnoreResponse = response.json();
+        let preview = json.preview.unwrap();
+        assert_eq!(preview.line.as_deref(), Some("/Show 2 (2022)"));
+        assert_eq!(preview.covering_patterns[0].pattern, "!Show 2*");
 
-        let json: IgnoreStatusResponse = response.json();
-        assert!(json.ignored);
+        assert_eq!(
+            std::fs::read_to_string(&stignore_path).unwrap(),
+            "// keep\n!Show 2*\nShow 1*\n"
+        );
     }
 
     #[tokio::test]
@@ -1141,6 +2345,7 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -1164,6 +2369,7 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -1186,6 +2392,7 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec![],
+            dry_run: false,
         };
 
         let response = server
@@ -1208,6 +2415,7 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: NONEXISTENT_ID.to_string(),
             folder_path: vec!["Some Movie".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -1237,6 +2445,7 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["test-file.txt".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -1256,6 +2465,70 @@ mod tests {
         assert!(!test_file_path.exists());
     }
 
+    #[tokio::test]
+    async fn test_post_delete_dry_run() {
+        let (server, temp_dir) = setup_test_server().await;
+        let show = temp_dir.path().join("tv").join("Show 1 (2021)");
+
+        let request_body = DeleteRequest {
+            category_id: "tv".to_string(),
+            folder_path: vec!["Show 1 (2021)".to_string()],
+            dry_run: true,
+        };
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: DeleteResponse = response.json();
+        assert!(json.success);
+        assert!(json.deleted_path.is_none());
+        let preview = json.preview.unwrap();
+        assert!(preview.exists);
+        assert!(preview.is_dir);
+        assert!(!preview.moves_to_trash);
+        assert_eq!(preview.total_bytes, 5 * "test episode content".len() as u64);
+        assert_eq!(
+            preview.files,
+            vec![
+                "Show 1 (2021)/Season 1/S01E01 - Ep 1.mkv",
+                "Show 1 (2021)/Season 1/S01E02 - Ep 2.mkv",
+                "Show 1 (2021)/Season 2/S02E01 - Ep 1.mkv",
+                "Show 1 (2021)/Season 2/S02E02 - Ep 2.mkv",
+                "Show 1 (2021)/Season 2/S02E03 - Ep 3.mkv",
+            ]
+        );
+
+        // Nothing was touched
+        assert!(show.join("Season 2").join("S02E03 - Ep 3.mkv").exists());
+    }
+
+    #[tokio::test]
+    async fn test_post_delete_dry_run_missing_path() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Nonexistent Movie".to_string()],
+            dry_run: true,
+        };
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: DeleteResponse = response.json();
+        assert!(json.success);
+        assert!(json.message.contains("does not exist"));
+        assert_eq!(json.preview.unwrap(), DeletePreview::default());
+    }
+
     #[tokio::test]
     async fn test_syncthing_system_files_filtered() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -1272,28 +2545,575 @@ mod tests {
             .await;
         response.assert_status(StatusCode::OK);
 
-        let json: ItemInfoResponse = response.json();
+        let json: ItemInfoResponse = response.json();
+
+        let items = json.item.items;
+
+        // Verify that no Syncthing system files are returned
+        for item in &items {
+            assert!(
+                !item.name.starts_with(".st"),
+                "Syncthing system file '{}' should be filtered out",
+                item.name
+            );
+        }
+
+        // Verify we still get regular movies (Movie 1 and Movie 2)
+        let movie_names: Vec<&String> = items.iter().map(|item| &item.name).collect();
+        assert!(movie_names.contains(&&"Movie 1 (2023)".to_string()));
+        assert!(movie_names.contains(&&"Movie 2 (2024)".to_string()));
+
+        // Verify specific system files are not present
+        assert!(!movie_names.contains(&&".stfolder".to_string()));
+        assert!(!movie_names.contains(&&".stignore".to_string()));
+        assert!(!movie_names.contains(&&".stversions".to_string()));
+    }
+
+    #[test]
+    fn test_diff_of_long_stignore_files() {
+        let old: String = (0..10_000).map(|i| format!("/Movie {}\n", i)).collect();
+        let new = old.replace("/Movie 5000\n", "/Movie 5000 (Director's Cut)\n");
+
+        let lines = history::diff_lines(&old, &new);
+        assert_eq!(lines.len(), 10_001);
+        let changed: Vec<&DiffLine> = lines.iter().filter(|l| l.op != DiffOp::Same).collect();
+        assert_eq!(
+            changed,
+            vec![
+                &DiffLine {
+                    op: DiffOp::Removed,
+                    text: "/Movie 5000".to_string()
+                },
+                &DiffLine {
+                    op: DiffOp::Added,
+                    text: "/Movie 5000 (Director's Cut)".to_string()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stignore_history_diff_and_revert() {
+        let (server, temp_dir) = setup_test_server_with_history(100).await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+
+        change_ignore(&server, "/api/v1/ignore", "Movie 1 (2023)").await;
+        change_ignore(&server, "/api/v1/ignore", "Movie 2 (2024)").await;
+        change_ignore(&server, "/api/v1/unignore", "Movie 1 (2023)").await;
+
+        let revisions = movie_revisions(&server).await;
+        let changes: Vec<(StignoreChange, &str)> = revisions
+            .iter()
+            .map(|r| (r.change, r.detail.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (StignoreChange::Unignore, "/Movie 1 (2023)"),
+                (StignoreChange::Ignore, "/Movie 2 (2024)"),
+                (StignoreChange::Ignore, "/Movie 1 (2023)"),
+                // The empty file the fixture started with
+                (StignoreChange::Outside, ""),
+            ]
+        );
+        assert_eq!(revisions[1].line_count, 4);
+
+        let request_body = StignoreDiffRequest {
+            category_id: MOVIES_ID.to_string(),
+            from: Some(revisions[1].id.clone()),
+            to: None,
+        };
+        let json: StignoreDiffResponse = server
+            .post("/api/v1/history/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .json();
+        assert!(json.success);
+        let line = |op, text: &str| DiffLine {
+            op,
+            text: text.to_string(),
+        };
+        assert_eq!(
+            json.lines,
+            vec![
+                line(DiffOp::Same, MANAGED_BEGIN),
+                line(DiffOp::Removed, "/Movie 1 (2023)"),
+                line(DiffOp::Same, "/Movie 2 (2024)"),
+                line(DiffOp::Same, MANAGED_END),
+            ]
+        );
+
+        // Undo the unignore
+        let request_body = StignoreRevertRequest {
+            category_id: MOVIES_ID.to_string(),
+            id: revisions[1].id.clone(),
+        };
+        let response = server
+            .post("/api/v1/history/revert")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// BEGIN stignore-manager\n/Movie 1 (2023)\n/Movie 2 (2024)\n// END stignore-manager\n"
+        );
+
+        let revisions_after = movie_revisions(&server).await;
+        assert_eq!(revisions_after.len(), 5);
+        assert_eq!(revisions_after[0].change, StignoreChange::Revert);
+        assert_eq!(revisions_after[0].detail, revisions[1].id);
+
+        let request_body = StignoreRevertRequest {
+            category_id: MOVIES_ID.to_string(),
+            id: "../../etc/passwd".to_string(),
+        };
+        server
+            .post("/api/v1/history/revert")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_stignore_history_keeps_outside_edits_and_retention() {
+        let (server, temp_dir) = setup_test_server_with_history(3).await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+
+        change_ignore(&server, "/api/v1/ignore", "Movie 1 (2023)").await;
+        // Someone edits the file by hand before the next change
+        fs::write(&stignore_path, "// by hand\nMovie 1 (2023)\n").unwrap();
+        change_ignore(&server, "/api/v1/ignore", "Movie 2 (2024)").await;
+
+        let revisions = movie_revisions(&server).await;
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(revisions[0].change, StignoreChange::Ignore);
+        assert_eq!(revisions[1].change, StignoreChange::Outside);
+        assert_eq!(revisions[1].line_count, 2);
+        assert_eq!(revisions[2].detail, "/Movie 1 (2023)");
+    }
+
+    #[tokio::test]
+    async fn test_stignore_history_disabled() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let json: StignoreHistoryResponse = server
+            .get("/api/v1/history")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        assert!(!json.enabled);
+
+        let request_body = StignoreRevertRequest {
+            category_id: MOVIES_ID.to_string(),
+            id: "1-0".to_string(),
+        };
+        server
+            .post("/api/v1/history/revert")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_raw_stignore_read_and_conditional_write() {
+        let (server, temp_dir) = setup_test_server_with_history(100).await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        fs::write(&stignore_path, "Movie 1 (2023)\n").unwrap();
+
+        let response = server
+            .get("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let etag = response.header("ETag").to_str().unwrap().to_string();
+        let json: StignoreContentResponse = response.json();
+        assert_eq!(json.content, "Movie 1 (2023)\n");
+        assert_eq!(json.etag, etag);
+
+        let new_content = StignoreWriteRequest {
+            content: "// movies\nMovie 1 (2023)\n(?i)*.nfo\n".to_string(),
+        };
+        server
+            .put("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&new_content)
+            .await
+            .assert_status(StatusCode::PRECONDITION_REQUIRED);
+
+        // Patterns Syncthing can't parse never reach the file
+        let response = server
+            .put("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .add_header("If-Match", etag.as_str())
+            .json(&StignoreWriteRequest {
+                content: "Movie 1 (2023)\n[abc\n".to_string(),
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(
+            response
+                .json::<StignoreContentResponse>()
+                .message
+                .contains("line 2")
+        );
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "Movie 1 (2023)\n"
+        );
+
+        // Includes can't be used to read files outside the folder
+        fs::write(temp_dir.path().join("secret"), "[password\n").unwrap();
+        let response = server
+            .put("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .add_header("If-Match", etag.as_str())
+            .json(&StignoreWriteRequest {
+                content: "#include ../secret\n".to_string(),
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let message = response.json::<StignoreContentResponse>().message;
+        assert!(message.contains("inside the folder"));
+        assert!(!message.contains("password"));
+
+        let response = server
+            .put("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .add_header("If-Match", etag.as_str())
+            .json(&new_content)
+            .await;
+        response.assert_status(StatusCode::OK);
+        let new_etag = response.json::<StignoreContentResponse>().etag;
+        assert_ne!(new_etag, etag);
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            new_content.content
+        );
+        assert_eq!(
+            movie_revisions(&server).await[0].change,
+            StignoreChange::Edit
+        );
+
+        // A second editor still holding the old ETag gets the current content back
+        let response = server
+            .put("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .add_header("If-Match", etag.as_str())
+            .json(&StignoreWriteRequest {
+                content: String::new(),
+            })
+            .await;
+        response.assert_status(StatusCode::PRECONDITION_FAILED);
+        let json: StignoreContentResponse = response.json();
+        assert!(!json.success);
+        assert_eq!(json.content, new_content.content);
+        assert_eq!(json.etag, new_etag);
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            new_content.content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unignore_leaves_hand_written_entries_alone() {
+        let (server, temp_dir) = setup_test_server_with_history(100).await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        fs::write(&stignore_path, "// mine\nMovie 1 (2023)\n*.nfo\n").unwrap();
+
+        change_ignore(&server, "/api/v1/ignore", "Movie 2 (2024)").await;
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// mine\nMovie 1 (2023)\n*.nfo\n// BEGIN stignore-manager\n/Movie 2 (2024)\n// END stignore-manager\n"
+        );
+
+        let json: StignoreContentResponse = server
+            .get("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        assert_eq!(json.managed_entries, vec!["/Movie 2 (2024)"]);
+        assert_eq!(json.user_entries, vec!["Movie 1 (2023)", "*.nfo"]);
+
+        let mut request_body = UnignoreRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
+            include_user_entries: false,
+        };
+        server
+            .post("/api/v1/unignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        assert!(
+            fs::read_to_string(&stignore_path)
+                .unwrap()
+                .contains("\nMovie 1 (2023)\n")
+        );
+
+        request_body.include_user_entries = true;
+        server
+            .post("/api/v1/unignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::OK);
+        change_ignore(&server, "/api/v1/unignore", "Movie 2 (2024)").await;
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// mine\n*.nfo\n// BEGIN stignore-manager\n// END stignore-manager\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ignored_names_are_escaped_and_anchored() {
+        let (server, temp_dir) = setup_test_server().await;
+        let movies = temp_dir.path().join("movies");
+        let stignore_path = movies.join(".stignore");
+        let name = "Movie [Director's Cut] (1999)";
+        fs::create_dir(movies.join(name)).unwrap();
+        fs::create_dir(movies.join("Movie D (1999)")).unwrap();
+
+        change_ignore(&server, "/api/v1/ignore", name).await;
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// BEGIN stignore-manager\n/Movie \\[Director's Cut\\] (1999)\n// END stignore-manager\n"
+        );
+
+        for (folder, ignored) in [(name, true), ("Movie D (1999)", false)] {
+            let json: IgnoreStatusResponse = server
+                .post("/api/v1/ignore-status")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .json(&IgnoreStatusRequest {
+                    category_id: MOVIES_ID.to_string(),
+                    folder_path: vec![folder.to_string()],
+                })
+                .await
+                .json();
+            assert_eq!(json.ignored, ignored, "{}", folder);
+        }
+
+        // Written verbatim by an older agent, Syncthing reads it as a character class
+        let legacy = format!(
+            "// BEGIN stignore-manager\n{}\n// END stignore-manager\n",
+            name
+        );
+        fs::write(&stignore_path, &legacy).unwrap();
+        let json: IgnoreResponse = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec![name.to_string()],
+                dry_run: false,
+            })
+            .await
+            .json();
+        assert!(json.success);
+        assert!(!json.message.contains("already ignored"));
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// BEGIN stignore-manager\n/Movie \\[Director's Cut\\] (1999)\n// END stignore-manager\n"
+        );
+
+        // Un-ignoring still recognises the verbatim line
+        fs::write(&stignore_path, &legacy).unwrap();
+        change_ignore(&server, "/api/v1/unignore", name).await;
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// BEGIN stignore-manager\n// END stignore-manager\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entries_from_before_the_managed_block_stay_user_entries() {
+        let (server, temp_dir) = setup_test_server().await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        // Nothing marks which lines an older agent wrote, they may just as well be the user's
+        let content = "// mine\nMovie 1 (2023)\nThumbs.db\n";
+        fs::write(&stignore_path, content).unwrap();
+
+        server
+            .post("/api/v1/unignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&UnignoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["Movie 1 (2023)".to_string()],
+                dry_run: false,
+                include_user_entries: false,
+            })
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        assert_eq!(fs::read_to_string(&stignore_path).unwrap(), content);
+
+        change_ignore(&server, "/api/v1/ignore", "Movie 2 (2024)").await;
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// mine\nMovie 1 (2023)\nThumbs.db\n// BEGIN stignore-manager\n/Movie 2 (2024)\n// END stignore-manager\n"
+        );
+        let json: StignoreContentResponse = server
+            .get("/api/v1/categories/movies/stignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        assert_eq!(json.user_entries, vec!["Movie 1 (2023)", "Thumbs.db"]);
+    }
+
+    #[tokio::test]
+    async fn test_adopt_moves_plain_entries_into_the_managed_block() {
+        let (server, temp_dir) = setup_test_server_with_history(100).await;
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        // Written by an older agent, which appended its lines after the user's
+        fs::write(
+            &stignore_path,
+            "(?i)*.nfo\n!Movie 2 (2024)\nMovie 1 (2023)\n",
+        )
+        .unwrap();
+
+        let response = server
+            .post("/api/v1/categories/movies/stignore/adopt")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&StignoreAdoptRequest::default())
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert_eq!(
+            response.json::<StignoreAdoptResponse>().adopted,
+            vec!["Movie 1 (2023)"]
+        );
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "(?i)*.nfo\n!Movie 2 (2024)\n// BEGIN stignore-manager\nMovie 1 (2023)\n// END stignore-manager\n"
+        );
+        assert_eq!(
+            movie_revisions(&server).await[0].change,
+            StignoreChange::Adopt
+        );
+
+        // Now the agent's own entry, it can be unignored without asking
+        change_ignore(&server, "/api/v1/unignore", "Movie 1 (2023)").await;
+
+        // Moving the nfo rule past the negation could change what it ignores
+        let response = server
+            .post("/api/v1/categories/movies/stignore/adopt")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&StignoreAdoptRequest {
+                entries: Some(vec!["(?i)*.nfo".to_string()]),
+            })
+            .await;
+        assert!(response.json::<StignoreAdoptResponse>().adopted.is_empty());
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "(?i)*.nfo\n!Movie 2 (2024)\n// BEGIN stignore-manager\n// END stignore-manager\n"
+        );
+    }
+
+    #[test]
+    fn test_concurrent_stignore_updates_keep_every_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().to_path_buf();
+        let existing: Vec<String> = (0..8).map(|i| format!("Old {i}")).collect();
+        for name in &existing {
+            fs::create_dir(base.join(name)).unwrap();
+        }
+        fs::write(base.join(".stignore"), format!("{}\n", existing.join("\n"))).unwrap();
+
+        // Half the threads add new entries while the other half remove existing ones
+        let barrier = std::sync::Barrier::new(16);
+        std::thread::scope(|scope| {
+            for (i, old) in existing.iter().enumerate() {
+                let (base, barrier) = (&base, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    let result = crate::filesystem::add_to_stignore(
+                        base,
+                        &[format!("New {i}")],
+                        "Movies",
+                        None,
+                    );
+                    assert!(matches!(
+                        result,
+                        crate::filesystem::StignoreResult::Success { .. }
+                    ));
+                });
+                scope.spawn(move || {
+                    barrier.wait();
+                    let result = crate::filesystem::remove_from_stignore(
+                        base,
+                        std::slice::from_ref(old),
+                        "Movies",
+                        true,
+                        None,
+                    );
+                    assert!(matches!(
+                        result,
+                        crate::filesystem::StignoreResult::Success { .. }
+                    ));
+                });
+            }
+        });
 
-        let items = json.item.items;
+        let content = fs::read_to_string(base.join(".stignore")).unwrap();
+        let sections = ManagedStignore::parse(&content);
+        let mut lines = sections.managed_entries();
+        lines.sort();
+        let expected: Vec<String> = (0..8).map(|i| format!("/New {i}")).collect();
+        assert_eq!(lines, expected);
+        assert!(!base.join(".syncthing.stignore.tmp").exists());
+    }
 
-        // Verify that no Syncthing system files are returned
-        for item in &items {
-            assert!(
-                !item.name.starts_with(".st"),
-                "Syncthing system file '{}' should be filtered out",
-                item.name
-            );
-        }
+    #[cfg(unix)]
+    #[test]
+    fn test_stignore_update_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
 
-        // Verify we still get regular movies (Movie 1 and Movie 2)
-        let movie_names: Vec<&String> = items.iter().map(|item| &item.name).collect();
-        assert!(movie_names.contains(&&"Movie 1 (2023)".to_string()));
-        assert!(movie_names.contains(&&"Movie 2 (2024)".to_string()));
+        let temp_dir = TempDir::new().unwrap();
+        let stignore_path = temp_dir.path().join(".stignore");
+        fs::write(&stignore_path, "// shared with Syncthing\n").unwrap();
+        fs::set_permissions(&stignore_path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let result = crate::filesystem::add_to_stignore(
+            temp_dir.path(),
+            &["Movie".to_string()],
+            "Movies",
+            None,
+        );
+        assert!(matches!(
+            result,
+            crate::filesystem::StignoreResult::Success { .. }
+        ));
 
-        // Verify specific system files are not present
-        assert!(!movie_names.contains(&&".stfolder".to_string()));
-        assert!(!movie_names.contains(&&".stignore".to_string()));
-        assert!(!movie_names.contains(&&".stversions".to_string()));
+        let metadata = fs::metadata(&stignore_path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(
+            fs::read_to_string(&stignore_path).unwrap(),
+            "// shared with Syncthing\n// BEGIN stignore-manager\n/Movie\n// END stignore-manager\n"
+        );
+    }
+
+    #[test]
+    fn test_failed_stignore_write_leaves_the_file_intact() {
+        let temp_dir = TempDir::new().unwrap();
+        let stignore_path = temp_dir.path().join(".stignore");
+        fs::write(&stignore_path, "Keep me\n").unwrap();
+        // Something in the way of the temporary file makes the write fail before the rename
+        fs::create_dir(temp_dir.path().join(".syncthing.stignore.tmp")).unwrap();
+
+        let result = crate::filesystem::add_to_stignore(
+            temp_dir.path(),
+            &["Movie".to_string()],
+            "Movies",
+            None,
+        );
+        assert!(matches!(
+            result,
+            crate::filesystem::StignoreResult::Error { .. }
+        ));
+        assert_eq!(fs::read_to_string(&stignore_path).unwrap(), "Keep me\n");
     }
 
     #[tokio::test]
@@ -1311,6 +3131,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 2 (2024)".to_string()],
+            dry_run: false,
         };
 
         let response = server
@@ -1353,6 +3174,7 @@ mod tests {
             vec![0u8; 10240], // 10 KB
         )
         .unwrap();
+        wait_for_movies(&server, |movies| movies.stversions_size_kb == 10).await;
 
         let response = server
             .get("/api/v1/categories")
@@ -1380,6 +3202,7 @@ mod tests {
             .join("Movie 1 (2023)")
             .join("Movie 1 (2023).sync-conflict-20230815-120000-AGENT1.mkv");
         fs::write(&conflict_file, "conflict data").unwrap();
+        wait_for_movies(&server, |movies| movies.has_conflicts).await;
 
         let request_body = ItemInfoRequest {
             item_path: vec![MOVIES_ID.to_string(), "Movie 1 (2023)".to_string()],
@@ -1423,6 +3246,7 @@ mod tests {
             .join("Movie 1 (2023)")
             .join(".syncthing.Movie 1 (2023).mkv.tmp");
         fs::write(&sync_file, "downloading...").unwrap();
+        wait_for_movies(&server, |movies| movies.is_syncing).await;
 
         let request_body = ItemInfoRequest {
             item_path: vec![MOVIES_ID.to_string(), "Movie 1 (2023)".to_string()],
@@ -1447,4 +3271,525 @@ mod tests {
                 .any(|name| name.starts_with(".syncthing."))
         );
     }
+
+    #[tokio::test]
+    async fn test_cache_picks_up_new_directory() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let new_movie = temp_dir.path().join("movies").join("Movie 3 (2025)");
+        fs::create_dir_all(&new_movie).unwrap();
+        fs::write(new_movie.join("Movie 3 (2025).mkv"), vec![0u8; 4096]).unwrap();
+
+        wait_for_movies(&server, |movies| {
+            movies
+                .items
+                .iter()
+                .any(|m| m.name == "Movie 3 (2025)" && m.size_kb == 4)
+        })
+        .await;
+
+        let request_body = ItemInfoRequest {
+            item_path: vec![MOVIES_ID.to_string(), "Movie 3 (2025)".to_string()],
+        };
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: ItemInfoResponse = response.json();
+        assert!(json.item.leaf);
+        assert_eq!(json.item.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_leaf_depth_decides_where_items_end() {
+        let (mut data, temp_dir) = create_test_data();
+        data.categories[0].leaf_depth = Some(1);
+        data.categories[1].leaf_depth = Some(2);
+
+        // A movie with extras and a season with a specials folder stay items
+        let extras = temp_dir.path().join("movies/Movie 1 (2023)/Extras");
+        fs::create_dir_all(&extras).unwrap();
+        fs::write(extras.join("Trailer.mkv"), "trailer").unwrap();
+        let specials = temp_dir.path().join("tv/Show 1 (2021)/Season 1/Specials");
+        fs::create_dir_all(&specials).unwrap();
+        fs::write(specials.join("Special.mkv"), "special").unwrap();
+        // A show without seasons yet isn't mistaken for a season
+        let new_show = temp_dir.path().join("tv/Show 4 (2026)");
+        fs::create_dir_all(&new_show).unwrap();
+        fs::write(new_show.join("poster.jpg"), "poster").unwrap();
+
+        let server = TestServer::new(create_test_router(data)).unwrap();
+        let json: CategoryListingResponse = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+
+        let movies = json.items.iter().find(|c| c.id == MOVIES_ID).unwrap();
+        let movie = movies
+            .items
+            .iter()
+            .find(|m| m.name == "Movie 1 (2023)")
+            .unwrap();
+        assert!(movie.leaf);
+        assert_eq!(movie.file_count, 2);
+        assert_eq!(movie.dir_count, 1);
+        assert!(movie.items.iter().any(|i| i.name == "Extras" && i.is_dir()));
+
+        let tv = json.items.iter().find(|c| c.id == "tv").unwrap();
+        let show = tv.items.iter().find(|s| s.name == "Show 1 (2021)").unwrap();
+        assert!(!show.leaf);
+        let season = show.items.iter().find(|s| s.name == "Season 1").unwrap();
+        assert!(season.leaf);
+        assert!(season.items.iter().any(|i| i.name == "Specials"));
+
+        let empty_show = tv.items.iter().find(|s| s.name == "Show 4 (2026)").unwrap();
+        assert!(!empty_show.leaf);
+    }
+
+    #[tokio::test]
+    async fn test_loose_files_are_listed_at_every_level() {
+        let (data, temp_dir) = create_test_data();
+        fs::write(temp_dir.path().join("tv/README.txt"), vec![0u8; 100]).unwrap();
+        fs::write(
+            temp_dir.path().join("tv/Show 1 (2021)/poster.jpg"),
+            vec![0u8; 200],
+        )
+        .unwrap();
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let json: CategoryListingResponse = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        let tv = json.items.iter().find(|c| c.id == "tv").unwrap();
+
+        let readme = tv.items.iter().find(|i| i.name == "README.txt").unwrap();
+        assert!(!readme.is_dir());
+        assert_eq!(readme.size_bytes, 100);
+
+        let show = tv.items.iter().find(|i| i.name == "Show 1 (2021)").unwrap();
+        assert!(!show.leaf);
+        assert!(show.items.iter().any(|i| i.name == "poster.jpg"));
+
+        let listed: u64 = tv.items.iter().map(|i| i.size_bytes).sum();
+        assert_eq!(tv.size_bytes, listed);
+        assert_eq!(tv.dir_count, 3 + 6);
+
+        // Loose files can be looked up like any other item
+        let request_body = ItemInfoRequest {
+            item_path: vec![
+                "tv".to_string(),
+                "Show 1 (2021)".to_string(),
+                "poster.jpg".to_string(),
+            ],
+        };
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: ItemInfoResponse = response.json();
+        assert_eq!(json.item.size_bytes, 200);
+    }
+
+    #[tokio::test]
+    async fn test_excluded_entries_are_not_listed() {
+        let (mut data, temp_dir) = create_test_data();
+        data.agent.exclude = vec!["@eaDir".to_string(), ".DS_Store".to_string()];
+        data.categories[1].exclude = vec!["/#recycle".to_string()];
+        for dir in [
+            "tv/@eaDir",
+            "tv/Show 1 (2021)/@eaDir",
+            "tv/#recycle",
+            "movies/#recycle",
+        ] {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("thumb.jpg"), vec![0u8; 100]).unwrap();
+        }
+        fs::write(
+            temp_dir.path().join("tv/Show 1 (2021)/Season 1/.DS_Store"),
+            vec![0u8; 100],
+        )
+        .unwrap();
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let json: CategoryListingResponse = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .json();
+        let tv = json.items.iter().find(|c| c.id == "tv").unwrap();
+        let names: Vec<&str> = tv.items.iter().map(|i| i.name.as_str()).collect();
+        assert!(!names.contains(&"@eaDir"));
+        assert!(!names.contains(&"#recycle"));
+
+        let show = tv.items.iter().find(|i| i.name == "Show 1 (2021)").unwrap();
+        assert!(show.items.iter().all(|i| i.name != "@eaDir"));
+        // Still a show of seasons, the thumbnail folder doesn't turn it into anything else
+        assert!(show.items.iter().all(|i| i.leaf));
+        let season = show.items.iter().find(|i| i.name == "Season 1").unwrap();
+        assert!(season.items.iter().all(|i| i.name != ".DS_Store"));
+        assert_eq!(tv.dir_count, 3 + 6);
+
+        // Category patterns only apply to their own category
+        let movies = json.items.iter().find(|c| c.id == "movies").unwrap();
+        assert!(movies.items.iter().any(|i| i.name == "#recycle"));
+    }
+
+    #[tokio::test]
+    async fn test_small_files_are_counted_in_bytes() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let new_movie = temp_dir.path().join("movies").join("Movie 4 (2026)");
+        fs::create_dir_all(&new_movie).unwrap();
+        for language in ["en", "fr", "de", "es"] {
+            fs::write(new_movie.join(format!("{}.srt", language)), vec![0u8; 700]).unwrap();
+        }
+
+        wait_for_movies(&server, |movies| {
+            movies
+                .items
+                .iter()
+                .any(|m| m.name == "Movie 4 (2026)" && m.file_count == 4)
+        })
+        .await;
+
+        let request_body = ItemInfoRequest {
+            item_path: vec![MOVIES_ID.to_string(), "Movie 4 (2026)".to_string()],
+        };
+        let json: ItemInfoResponse = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .json();
+        assert_eq!(json.item.size_bytes, 2800);
+        assert_eq!(json.item.size_kb, 2);
+        assert_eq!(json.item.file_count, 4);
+        assert_eq!(json.item.dir_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_updated_immediately_after_delete() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            dry_run: false,
+        };
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let response = server
+            .get("/api/v1/categories/movies")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CategoryInfoResponse = response.json();
+        assert!(!json.items.iter().any(|m| m.name == "Movie 1 (2023)"));
+    }
+
+    #[tokio::test]
+    async fn test_mutating_endpoints_reject_path_traversal() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        // A file next to the category roots that must never be reachable
+        let outside = temp_dir.path().join("outside.txt");
+        fs::write(&outside, "keep me").unwrap();
+
+        let bad_paths = [
+            vec!["..".to_string(), "outside.txt".to_string()],
+            vec!["../outside.txt".to_string()],
+            vec![outside.to_string_lossy().to_string()],
+            vec!["Movie 1 (2023)".to_string(), ".".to_string()],
+            vec!["Movie 1 (2023)\\..\\..".to_string()],
+            vec!["".to_string()],
+        ];
+
+        for folder_path in bad_paths {
+            for endpoint in ["/api/v1/delete", "/api/v1/ignore", "/api/v1/unignore"] {
+                let response = server
+                    .post(endpoint)
+                    .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                    .json(&IgnoreRequest {
+                        category_id: MOVIES_ID.to_string(),
+                        folder_path: folder_path.clone(),
+                        dry_run: false,
+                    })
+                    .await;
+                response.assert_status(StatusCode::BAD_REQUEST);
+
+                let json: IgnoreResponse = response.json();
+                assert!(!json.success);
+                assert!(
+                    json.message.starts_with("Invalid path"),
+                    "{} {:?}: {}",
+                    endpoint,
+                    folder_path,
+                    json.message
+                );
+            }
+        }
+
+        assert!(outside.exists());
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        assert_eq!(fs::read_to_string(stignore_path).unwrap(), "");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mutating_endpoints_reject_escaping_symlinks() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let outside_dir = TempDir::new().unwrap();
+        fs::write(outside_dir.path().join("precious.txt"), "keep me").unwrap();
+
+        let movies = temp_dir.path().join("movies");
+        std::os::unix::fs::symlink(outside_dir.path(), movies.join("escape")).unwrap();
+
+        // Both the link itself and anything reached through it are rejected
+        for folder_path in [
+            vec!["escape".to_string()],
+            vec!["escape".to_string(), "precious.txt".to_string()],
+            vec!["escape".to_string(), "not-there-yet".to_string()],
+        ] {
+            let request_body = DeleteRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path,
+                dry_run: false,
+            };
+            let response = server
+                .post("/api/v1/delete")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .json(&request_body)
+                .await;
+            response.assert_status(StatusCode::BAD_REQUEST);
+
+            let json: DeleteResponse = response.json();
+            assert!(json.message.contains("outside of the category"));
+        }
+
+        assert!(outside_dir.path().join("precious.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_within_category_is_allowed() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let movies = temp_dir.path().join("movies");
+        std::os::unix::fs::symlink(movies.join("Movie 1 (2023)"), movies.join("alias")).unwrap();
+
+        let request_body = IgnoreRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["alias".to_string()],
+            dry_run: false,
+        };
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    async fn delete_movie(server: &TestServer, name: &str) -> DeleteResponse {
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec![name.to_string()],
+            dry_run: false,
+        };
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+        response.json()
+    }
+
+    #[tokio::test]
+    async fn test_trash_disabled_by_default() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let response = server
+            .get("/api/v1/trash")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: TrashListResponse = response.json();
+        assert!(!json.enabled);
+
+        let response = server
+            .post("/api/v1/trash/purge")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashPurgeRequest {
+                id: None,
+                all: true,
+            })
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_delete_moves_to_trash_and_restores() {
+        let (server, temp_dir) = setup_test_server_with_trash().await;
+        let movie = temp_dir.path().join("movies").join("Movie 1 (2023)");
+
+        let json = delete_movie(&server, "Movie 1 (2023)").await;
+        assert!(json.success);
+        assert!(json.message.contains("to trash"));
+        let trash_id = json.trash_id.unwrap();
+        assert!(!movie.exists());
+
+        let response = server
+            .get("/api/v1/trash")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: TrashListResponse = response.json();
+        assert!(json.enabled);
+        assert_eq!(json.retention_days, 30);
+        assert_eq!(json.items.len(), 1);
+        assert_eq!(json.items[0].id, trash_id);
+        assert_eq!(json.items[0].category_id, MOVIES_ID);
+        assert_eq!(json.items[0].folder_path, vec!["Movie 1 (2023)"]);
+        assert!(json.items[0].is_dir);
+
+        let response = server
+            .post("/api/v1/trash/restore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashRestoreRequest { id: trash_id })
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert!(movie.join("Movie 1 (2023).mkv").exists());
+
+        // The restored item is immediately visible again
+        let response = server
+            .get("/api/v1/categories/movies")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        let json: CategoryInfoResponse = response.json();
+        assert!(json.items.iter().any(|m| m.name == "Movie 1 (2023)"));
+
+        let response = server
+            .get("/api/v1/trash")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        let json: TrashListResponse = response.json();
+        assert!(json.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trash_restore_refuses_to_overwrite() {
+        let (server, temp_dir) = setup_test_server_with_trash().await;
+        let movie = temp_dir.path().join("movies").join("Movie 1 (2023)");
+
+        let trash_id = delete_movie(&server, "Movie 1 (2023)")
+            .await
+            .trash_id
+            .unwrap();
+        fs::create_dir_all(&movie).unwrap();
+
+        let response = server
+            .post("/api/v1/trash/restore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashRestoreRequest { id: trash_id })
+            .await;
+        response.assert_status(StatusCode::CONFLICT);
+
+        let response = server
+            .post("/api/v1/trash/restore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashRestoreRequest {
+                id: "../movies".to_string(),
+            })
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_trash_purge() {
+        let (server, temp_dir) = setup_test_server_with_trash().await;
+
+        let first = delete_movie(&server, "Movie 1 (2023)")
+            .await
+            .trash_id
+            .unwrap();
+        delete_movie(&server, "Movie 2 (2024)").await;
+
+        let response = server
+            .post("/api/v1/trash/purge")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashPurgeRequest {
+                id: Some(first.clone()),
+                all: false,
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let response = server
+            .post("/api/v1/trash/restore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashRestoreRequest { id: first })
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // An empty request must never wipe the trash
+        let response = server
+            .post("/api/v1/trash/purge")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({}))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let trash_dir = temp_dir.path().join(".trash");
+        assert_eq!(fs::read_dir(&trash_dir).unwrap().count(), 2);
+
+        let response = server
+            .post("/api/v1/trash/purge")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&TrashPurgeRequest {
+                id: None,
+                all: true,
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: TrashResponse = response.json();
+        assert!(json.message.contains("Purged 1 items"));
+
+        assert_eq!(fs::read_dir(trash_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_trash_purge_expired() {
+        let temp_dir = TempDir::new().unwrap();
+        let item = temp_dir.path().join("old.mkv");
+        fs::write(&item, "data").unwrap();
+
+        let mut config = create_test_data().0.agent;
+        config.trash_path = Some(temp_dir.path().join("trash").to_string_lossy().to_string());
+        config.trash_retention_days = 0;
+        let trash = Trash::from_config(&config).unwrap();
+
+        trash
+            .move_in(&item, MOVIES_ID, &["old.mkv".to_string()])
+            .unwrap();
+        assert_eq!(trash.list().len(), 1);
+
+        let purged = trash.purge_expired();
+        assert_eq!(purged.len(), 1);
+        assert!(trash.list().is_empty());
+    }
 }
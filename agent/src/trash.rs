@@ -0,0 +1,346 @@
+use crate::filesystem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stignore_lib::{AgentConfig, TrashEntry};
+
+/// How often expired items are purged from the trash
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Disambiguates items trashed within the same millisecond
+static TRASH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+pub enum TrashError {
+    NotFound {
+        id: String,
+    },
+    /// Something already exists where a restored item would go
+    Conflict {
+        path: String,
+    },
+    Io {
+        message: String,
+    },
+}
+
+impl std::fmt::Display for TrashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrashError::NotFound { id } => write!(f, "Trash item '{}' not found", id),
+            TrashError::Conflict { path } => {
+                write!(f, "'{}' already exists, not overwriting it", path)
+            }
+            TrashError::Io { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TrashError {}
+
+/// Per-agent trash directory. Each deleted item is stored as `<id>.item` next to
+/// a `<id>.json` file describing where it came from.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    root: PathBuf,
+    retention_days: u64,
+}
+
+impl Trash {
+    /// Returns the configured trash, or None when deletes should be permanent
+    pub fn from_config(agent: &AgentConfig) -> Option<Trash> {
+        agent.trash_path.as_ref().map(|path| Trash {
+            root: PathBuf::from(path),
+            retention_days: agent.trash_retention_days,
+        })
+    }
+
+    pub fn retention_days(&self) -> u64 {
+        self.retention_days
+    }
+
+    /// Moves an item into the trash, recording its category and path for a later restore
+    pub fn move_in(
+        &self,
+        full_path: &Path,
+        category_id: &str,
+        folder_path: &[String],
+    ) -> Result<TrashEntry, TrashError> {
+        std::fs::create_dir_all(&self.root).map_err(|err| TrashError::Io {
+            message: format!("Failed to create trash directory: {}", err),
+        })?;
+
+        let metadata = full_path.symlink_metadata().map_err(|err| TrashError::Io {
+            message: format!("Failed to read '{}': {}", full_path.display(), err),
+        })?;
+        let is_dir = metadata.is_dir();
+        let size_kb = if is_dir {
+            filesystem::calculate_dir_size_bytes(full_path) / 1024
+        } else {
+            metadata.len() / 1024
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let id = format!(
+            "{}-{}",
+            now.as_millis(),
+            TRASH_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let entry = TrashEntry {
+            id: id.clone(),
+            category_id: category_id.to_string(),
+            folder_path: folder_path.to_vec(),
+            is_dir,
+            size_kb,
+            deleted_at: now.as_secs(),
+            expires_at: now.as_secs() + self.retention_days * SECONDS_PER_DAY,
+        };
+
+        // Metadata goes first so an interrupted move never leaves an anonymous item behind
+        self.write_metadata(&entry)?;
+        match move_path(full_path, &self.item_path(&id)) {
+            Ok(()) => {}
+            Err(MoveError::NotMoved(err)) => {
+                let _ = std::fs::remove_file(self.metadata_path(&id));
+                return Err(TrashError::Io {
+                    message: format!("Failed to move '{}' to trash: {}", full_path.display(), err),
+                });
+            }
+            // The trash holds a full copy, keep it listed so it can still be restored or purged
+            Err(MoveError::SourceLeft(err)) => {
+                return Err(TrashError::Io {
+                    message: format!(
+                        "Copied '{}' to trash but failed to remove the original: {}",
+                        full_path.display(),
+                        err
+                    ),
+                });
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Lists trashed items, newest first
+    pub fn list(&self) -> Vec<TrashEntry> {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return vec![];
+        };
+
+        let mut items: Vec<TrashEntry> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .filter_map(|content| serde_json::from_str::<TrashEntry>(&content).ok())
+            .filter(|entry| self.item_path(&entry.id).symlink_metadata().is_ok())
+            .collect();
+
+        items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(b.id.cmp(&a.id)));
+        items
+    }
+
+    pub fn get(&self, id: &str) -> Result<TrashEntry, TrashError> {
+        let not_found = || TrashError::NotFound { id: id.to_string() };
+
+        // Ids are generated by us, anything else could point outside the trash
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Err(not_found());
+        }
+
+        let content = std::fs::read_to_string(self.metadata_path(id)).map_err(|_| not_found())?;
+        let entry: TrashEntry = serde_json::from_str(&content).map_err(|_| not_found())?;
+        if self.item_path(id).symlink_metadata().is_err() {
+            return Err(not_found());
+        }
+        Ok(entry)
+    }
+
+    /// Moves a trashed item back to the given destination, which must not exist
+    pub fn restore(&self, id: &str, destination: &Path) -> Result<TrashEntry, TrashError> {
+        let entry = self.get(id)?;
+
+        if destination.symlink_metadata().is_ok() {
+            return Err(TrashError::Conflict {
+                path: entry.folder_path.join("/"),
+            });
+        }
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| TrashError::Io {
+                message: format!("Failed to recreate '{}': {}", parent.display(), err),
+            })?;
+        }
+
+        move_path(&self.item_path(id), destination).map_err(|err| TrashError::Io {
+            message: format!(
+                "Failed to restore '{}': {}",
+                entry.folder_path.join("/"),
+                err
+            ),
+        })?;
+        let _ = std::fs::remove_file(self.metadata_path(id));
+
+        Ok(entry)
+    }
+
+    /// Permanently removes a trashed item
+    pub fn purge(&self, id: &str) -> Result<TrashEntry, TrashError> {
+        let entry = self.get(id)?;
+
+        let item_path = self.item_path(id);
+        let result = if entry.is_dir {
+            std::fs::remove_dir_all(&item_path)
+        } else {
+            std::fs::remove_file(&item_path)
+        };
+        result.map_err(|err| TrashError::Io {
+            message: format!("Failed to purge '{}': {}", entry.folder_path.join("/"), err),
+        })?;
+        let _ = std::fs::remove_file(self.metadata_path(id));
+
+        Ok(entry)
+    }
+
+    /// Purges every item whose retention period has passed, returning what was removed
+    pub fn purge_expired(&self) -> Vec<TrashEntry> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        self.list()
+            .into_iter()
+            .filter(|entry| entry.expires_at <= now)
+            .filter_map(|entry| match self.purge(&entry.id) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    tracing::warn!("Unable to purge expired trash item: {}", err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn item_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.item", id))
+    }
+
+    fn metadata_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+
+    fn write_metadata(&self, entry: &TrashEntry) -> Result<(), TrashError> {
+        let json = serde_json::to_string_pretty(entry).map_err(|err| TrashError::Io {
+            message: format!("Failed to serialise trash metadata: {}", err),
+        })?;
+        std::fs::write(self.metadata_path(&entry.id), json).map_err(|err| TrashError::Io {
+            message: format!("Failed to write trash metadata: {}", err),
+        })
+    }
+}
+
+/// How far a failed move got
+enum MoveError {
+    /// Nothing was moved, a partial copy has been removed again
+    NotMoved(std::io::Error),
+    /// The destination holds a full copy but the source was only partly removed
+    SourceLeft(std::io::Error),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NotMoved(err) => write!(f, "{}", err),
+            MoveError::SourceLeft(err) => write!(f, "copied but not removed: {}", err),
+        }
+    }
+}
+
+/// Renames a path, falling back to copy and delete when the trash is on another filesystem
+fn move_path(from: &Path, to: &Path) -> Result<(), MoveError> {
+    match std::fs::rename(from, to) {
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            if let Err(err) = copy_recursive(from, to) {
+                let _ = remove_path(to);
+                return Err(MoveError::NotMoved(err));
+            }
+            remove_path(from).map_err(MoveError::SourceLeft)
+        }
+        result => result.map_err(MoveError::NotMoved),
+    }
+}
+
+/// Removes a file, symlink or whole directory tree
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Copies a tree without following symlinks, links are recreated pointing at the same target
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_symlink() {
+        copy_symlink(from, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(from)?;
+    if std::fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_copy_keeps_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("big.mkv"), "not part of the item").unwrap();
+
+        let item = temp_dir.path().join("item");
+        std::fs::create_dir_all(&item).unwrap();
+        std::fs::write(item.join("movie.mkv"), "movie").unwrap();
+        std::os::unix::fs::symlink(&outside, item.join("linked")).unwrap();
+
+        let copy = temp_dir.path().join("copy");
+        copy_recursive(&item, &copy).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(copy.join("movie.mkv")).unwrap(),
+            "movie"
+        );
+        let linked = copy.join("linked");
+        assert!(linked.symlink_metadata().unwrap().is_symlink());
+        assert_eq!(std::fs::read_link(&linked).unwrap(), outside);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::stignore::{IgnoreFile, StignoreError};
 use serde::{Deserialize, Serialize};
 use std::fs;
 
@@ -11,6 +12,15 @@ pub enum ConfigError {
         filename: String,
         source: toml::de::Error,
     },
+    Exclude {
+        filename: String,
+        category: String,
+        source: StignoreError,
+    },
+    LeafDepth {
+        filename: String,
+        category: String,
+    },
 }
 
 impl std::fmt::Display for ConfigError {
@@ -22,6 +32,20 @@ impl std::fmt::Display for ConfigError {
             ConfigError::Parse { filename, source } => {
                 write!(f, "Unable to parse config file '{}': {}", filename, source)
             }
+            ConfigError::Exclude {
+                filename,
+                category,
+                source,
+            } => write!(
+                f,
+                "Bad exclude patterns for category '{}' in config file '{}': {}",
+                category, filename, source
+            ),
+            ConfigError::LeafDepth { filename, category } => write!(
+                f,
+                "leaf_depth of category '{}' in config file '{}' must be at least 1",
+                category, filename
+            ),
         }
     }
 }
@@ -31,6 +55,8 @@ impl std::error::Error for ConfigError {
         match self {
             ConfigError::FileRead { source, .. } => Some(source),
             ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Exclude { source, .. } => Some(source),
+            ConfigError::LeafDepth { .. } => None,
         }
     }
 }
@@ -40,6 +66,15 @@ pub struct Category {
     pub id: String,
     pub name: String,
     pub relative_path: String,
+    /// Depth of the directories that are items, 1 for the category's own subdirectories.
+    /// Anything deeper is listed as their files. When unset a directory is an item as
+    /// soon as it has no subdirectories.
+    #[serde(default)]
+    pub leaf_depth: Option<usize>,
+    /// Entries left out of the listing on top of the agent-wide `exclude`, in .stignore
+    /// pattern syntax relative to the category
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,6 +83,52 @@ pub struct AgentConfig {
     pub port: u16,
     pub base_path: String,
     pub api_key: String,
+    /// Directory deleted items are moved into, deletes are permanent when unset.
+    /// Should live on the same filesystem as `base_path` so moves are cheap.
+    #[serde(default)]
+    pub trash_path: Option<String>,
+    /// How long trashed items are kept before being purged
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u64,
+    /// Directory earlier versions of each category's .stignore are kept in, no history
+    /// is kept when unset. Must be outside every category so Syncthing doesn't sync it.
+    #[serde(default)]
+    pub history_path: Option<String>,
+    /// How many .stignore revisions are kept per category
+    #[serde(default = "default_history_retention")]
+    pub history_retention: usize,
+    /// Announces the agent to a manager and keeps sending heartbeats when set
+    #[serde(default)]
+    pub enrolment: Option<EnrolmentConfig>,
+    /// Entries no category lists as items, such as NAS thumbnails and desktop metadata
+    /// files. Same syntax as .stignore, patterns without a leading `/` match at any depth.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnrolmentConfig {
+    pub manager_url: String,
+    /// Shared secret, must match the manager's `enrolment_token`
+    pub token: String,
+    /// Address the manager should use to reach this agent. Defaults to the local
+    /// address the manager is reached from plus `port`, so it follows IP changes.
+    #[serde(default)]
+    pub advertised_hostname: Option<String>,
+    #[serde(default = "default_heartbeat_interval_seconds")]
+    pub heartbeat_interval_seconds: u64,
+}
+
+fn default_heartbeat_interval_seconds() -> u64 {
+    30
+}
+
+fn default_trash_retention_days() -> u64 {
+    30
+}
+
+fn default_history_retention() -> usize {
+    100
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -56,6 +137,20 @@ pub struct AgentData {
     pub categories: Vec<Category>,
 }
 
+impl AgentData {
+    /// The agent-wide exclude patterns followed by the category's own
+    pub fn exclude_rules(&self, category: &Category) -> Result<IgnoreFile, StignoreError> {
+        let patterns: Vec<&str> = self
+            .agent
+            .exclude
+            .iter()
+            .chain(&category.exclude)
+            .map(String::as_str)
+            .collect();
+        IgnoreFile::parse(&patterns.join("\n"))
+    }
+}
+
 /// Expands environment variable placeholders in text.
 /// Supports both `${VAR_NAME}` and `$VAR_NAME` syntax.
 /// If an environment variable is not set, the placeholder remains unchanged.
@@ -180,6 +275,34 @@ pub fn apply_manager_env_overrides(mut data: ManagerData) -> ManagerData {
         data.manager.agent_timeout_seconds = timeout;
     }
 
+    if let Ok(deadline) = std::env::var("STIGNORE_AGGREGATION_DEADLINE_SECONDS")
+        .and_then(|s| s.parse::<u64>().map_err(|_| std::env::VarError::NotPresent))
+    {
+        data.manager.aggregation_deadline_seconds = deadline;
+    }
+
+    if let Ok(interval) = std::env::var("STIGNORE_TREE_REFRESH_INTERVAL_SECONDS")
+        .and_then(|s| s.parse::<u64>().map_err(|_| std::env::VarError::NotPresent))
+    {
+        data.manager.tree_refresh_interval_seconds = interval;
+    }
+
+    if let Some(path) = std::env::var("STIGNORE_DISABLED_AGENTS_PATH")
+        .ok()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+    {
+        data.manager.disabled_agents_path = Some(path);
+    }
+
+    if let Some(token) = std::env::var("STIGNORE_ENROLMENT_TOKEN")
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+    {
+        data.manager.enrolment_token = Some(token);
+    }
+
     if let Ok(auth_enabled_str) = std::env::var("STIGNORE_AUTH_ENABLED") {
         let is_enabled = match auth_enabled_str.to_lowercase().as_str() {
             "1" | "true" | "yes" | "on" => Some(true),
@@ -223,6 +346,23 @@ pub fn load_agent_config(filename: &str) -> Result<AgentData, ConfigError> {
         source,
     })?;
 
+    // A typo in a pattern would silently list everything, refuse to start instead
+    for category in &data.categories {
+        data.exclude_rules(category)
+            .map_err(|source| ConfigError::Exclude {
+                filename: filename.to_string(),
+                category: category.name.clone(),
+                source,
+            })?;
+        // Depth 0 would be the category itself, which is never an item
+        if category.leaf_depth == Some(0) {
+            return Err(ConfigError::LeafDepth {
+                filename: filename.to_string(),
+                category: category.name.clone(),
+            });
+        }
+    }
+
     Ok(data)
 }
 
@@ -238,14 +378,47 @@ pub struct ManagerConfig {
     pub minimum_copies: u8,
     #[serde(default = "default_agent_timeout_seconds")]
     pub agent_timeout_seconds: u64,
+    /// Upper bound on how long a page waits for all agents combined, agents that
+    /// haven't answered by then are shown as empty
+    #[serde(default = "default_aggregation_deadline_seconds")]
+    pub aggregation_deadline_seconds: u64,
+    /// How often the cached agent trees are refetched in the background, 0 disables it
+    #[serde(default = "default_tree_refresh_interval_seconds")]
+    pub tree_refresh_interval_seconds: u64,
+    /// File the agents disabled from the UI are kept in, without it they come back
+    /// enabled after a restart
+    #[serde(default)]
+    pub disabled_agents_path: Option<String>,
+    /// Shared secret agents use to register themselves, registration is off without it
+    #[serde(default)]
+    pub enrolment_token: Option<String>,
     #[serde(default)]
     pub auth: AuthConfig,
 }
 
+impl ManagerConfig {
+    pub fn aggregation_deadline(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.aggregation_deadline_seconds)
+    }
+
+    pub fn tree_refresh_interval(&self) -> Option<std::time::Duration> {
+        (self.tree_refresh_interval_seconds > 0)
+            .then(|| std::time::Duration::from_secs(self.tree_refresh_interval_seconds))
+    }
+}
+
 fn default_agent_timeout_seconds() -> u64 {
     5
 }
 
+fn default_aggregation_deadline_seconds() -> u64 {
+    10
+}
+
+fn default_tree_refresh_interval_seconds() -> u64 {
+    60
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct AuthConfig {
     #[serde(default)]
@@ -293,6 +466,9 @@ pub struct Agent {
     pub name: String,
     pub hostname: String,
     pub api_key: String,
+    /// Overrides the manager wide `agent_timeout_seconds` for this agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
 }
 
 pub fn load_manager_config(filename: &str) -> Result<ManagerData, ConfigError> {
@@ -336,6 +512,192 @@ mod tests {
         assert!(data.is_ok());
     }
 
+    #[test]
+    fn test_agent_config_trash_settings() {
+        let data: AgentData = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+           trash_path = "/path/to/trash"
+           trash_retention_days = 7
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(data.agent.trash_path.as_deref(), Some("/path/to/trash"));
+        assert_eq!(data.agent.trash_retention_days, 7);
+
+        let data: AgentData = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+        "#,
+        )
+        .unwrap();
+        assert!(data.agent.trash_path.is_none());
+        assert_eq!(data.agent.trash_retention_days, 30);
+    }
+
+    #[test]
+    fn test_agent_config_enrolment_settings() {
+        let data: AgentData = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [agent.enrolment]
+           manager_url = "http://manager:8000"
+           token = "enrol-me"
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+        "#,
+        )
+        .unwrap();
+        let enrolment = data.agent.enrolment.unwrap();
+        assert_eq!(enrolment.manager_url, "http://manager:8000");
+        assert_eq!(enrolment.token, "enrol-me");
+        assert!(enrolment.advertised_hostname.is_none());
+        assert_eq!(enrolment.heartbeat_interval_seconds, 30);
+    }
+
+    #[test]
+    fn test_category_leaf_depth() {
+        let data: AgentData = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [[categories]]
+           id = "movies"
+           name = "Movies"
+           relative_path = "movies/"
+           leaf_depth = 1
+
+           [[categories]]
+           id = "music"
+           name = "Music"
+           relative_path = "music/"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(data.categories[0].leaf_depth, Some(1));
+        assert_eq!(data.categories[1].leaf_depth, None);
+    }
+
+    #[test]
+    fn test_category_exclude_rules() {
+        let data: AgentData = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+           exclude = ["@eaDir", ".DS_Store", "(?i)thumbs.db"]
+
+           [[categories]]
+           id = "movies"
+           name = "Movies"
+           relative_path = "movies/"
+           exclude = ["/#recycle"]
+
+           [[categories]]
+           id = "music"
+           name = "Music"
+           relative_path = "music/"
+        "#,
+        )
+        .unwrap();
+
+        let movies = data.exclude_rules(&data.categories[0]).unwrap();
+        assert!(movies.is_ignored("Movie 1/@eaDir"));
+        assert!(movies.is_ignored("Movie 1/Thumbs.db"));
+        assert!(movies.is_ignored("#recycle"));
+        assert!(!movies.is_ignored("Movie 1/#recycle"));
+
+        let music = data.exclude_rules(&data.categories[1]).unwrap();
+        assert!(music.is_ignored(".DS_Store"));
+        assert!(!music.is_ignored("#recycle"));
+    }
+
+    #[test]
+    fn test_invalid_exclude_pattern_is_rejected() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("agent.toml");
+        fs::write(
+            &path,
+            r#"
+[agent]
+port = 3000
+name = "Agent Smith"
+base_path = "/path/to/stuff"
+api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+[[categories]]
+id = "movies"
+name = "Movies"
+relative_path = "movies/"
+exclude = ["[abc"]
+"#,
+        )
+        .unwrap();
+
+        let err = load_agent_config(path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, ConfigError::Exclude { .. }));
+        assert!(err.to_string().contains("Movies"));
+    }
+
+    #[test]
+    fn test_zero_leaf_depth_is_rejected() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("agent.toml");
+        fs::write(
+            &path,
+            r#"
+[agent]
+port = 3000
+name = "Agent Smith"
+base_path = "/path/to/stuff"
+api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+[[categories]]
+id = "movies"
+name = "Movies"
+relative_path = "movies/"
+leaf_depth = 0
+"#,
+        )
+        .unwrap();
+
+        let err = load_agent_config(path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, ConfigError::LeafDepth { .. }));
+        assert!(err.to_string().contains("Movies"));
+    }
+
     #[test]
     fn test_manager_config_serde() {
         let data: Result<ManagerData, toml::de::Error> = toml::from_str(
@@ -353,6 +715,10 @@ mod tests {
         assert!(data.is_ok());
         let config = data.unwrap();
         assert_eq!(config.manager.agent_timeout_seconds, 5);
+        assert_eq!(config.manager.aggregation_deadline_seconds, 10);
+        assert_eq!(config.manager.tree_refresh_interval_seconds, 60);
+        assert!(config.manager.disabled_agents_path.is_none());
+        assert!(config.manager.enrolment_token.is_none());
         assert!(!config.manager.auth.enabled);
         assert_eq!(config.manager.auth.user_header, "X-Proxy-User");
         assert_eq!(config.manager.auth.role_header, "X-Proxy-Role");
@@ -458,6 +824,10 @@ relative_path = "test/"
                 port: 8000,
                 minimum_copies: 2,
                 agent_timeout_seconds: 5,
+                aggregation_deadline_seconds: 10,
+                tree_refresh_interval_seconds: 60,
+                disabled_agents_path: None,
+                enrolment_token: None,
                 auth: AuthConfig::default(),
             },
             agents: vec![],
@@ -467,6 +837,13 @@ relative_path = "test/"
             std::env::set_var("STIGNORE_PORT", "8080");
             std::env::set_var("STIGNORE_MINIMUM_COPIES", "3");
             std::env::set_var("STIGNORE_AGENT_TIMEOUT_SECONDS", "10");
+            std::env::set_var("STIGNORE_AGGREGATION_DEADLINE_SECONDS", "20");
+            std::env::set_var("STIGNORE_TREE_REFRESH_INTERVAL_SECONDS", "0");
+            std::env::set_var(
+                "STIGNORE_DISABLED_AGENTS_PATH",
+                "/data/disabled_agents.json",
+            );
+            std::env::set_var("STIGNORE_ENROLMENT_TOKEN", "enrol-me");
             std::env::set_var("STIGNORE_AUTH_ENABLED", "true");
             std::env::set_var("STIGNORE_AUTH_USER_HEADER", "X-Custom-User");
             std::env::set_var("STIGNORE_AUTH_ROLE_HEADER", "X-Custom-Roles");
@@ -479,6 +856,16 @@ relative_path = "test/"
         assert_eq!(overridden.manager.port, 8080);
         assert_eq!(overridden.manager.minimum_copies, 3);
         assert_eq!(overridden.manager.agent_timeout_seconds, 10);
+        assert_eq!(overridden.manager.aggregation_deadline_seconds, 20);
+        assert_eq!(overridden.manager.tree_refresh_interval(), None);
+        assert_eq!(
+            overridden.manager.disabled_agents_path.as_deref(),
+            Some("/data/disabled_agents.json")
+        );
+        assert_eq!(
+            overridden.manager.enrolment_token.as_deref(),
+            Some("enrol-me")
+        );
         assert!(overridden.manager.auth.enabled);
         assert_eq!(overridden.manager.auth.user_header, "X-Custom-User");
         assert_eq!(overridden.manager.auth.role_header, "X-Custom-Roles");
@@ -489,6 +876,10 @@ relative_path = "test/"
             std::env::remove_var("STIGNORE_PORT");
             std::env::remove_var("STIGNORE_MINIMUM_COPIES");
             std::env::remove_var("STIGNORE_AGENT_TIMEOUT_SECONDS");
+            std::env::remove_var("STIGNORE_AGGREGATION_DEADLINE_SECONDS");
+            std::env::remove_var("STIGNORE_TREE_REFRESH_INTERVAL_SECONDS");
+            std::env::remove_var("STIGNORE_DISABLED_AGENTS_PATH");
+            std::env::remove_var("STIGNORE_ENROLMENT_TOKEN");
             std::env::remove_var("STIGNORE_AUTH_ENABLED");
             std::env::remove_var("STIGNORE_AUTH_USER_HEADER");
             std::env::remove_var("STIGNORE_AUTH_ROLE_HEADER");
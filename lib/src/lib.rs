@@ -1,5 +1,7 @@
 pub mod config;
+pub mod stignore;
 pub mod types;
 
 pub use config::*;
+pub use stignore::*;
 pub use types::*;
@@ -0,0 +1,1144 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Errors raised while reading or parsing a .stignore file
+#[derive(Debug)]
+pub enum StignoreError {
+    FileRead {
+        filename: String,
+        source: std::io::Error,
+    },
+    IncludeCycle {
+        filename: String,
+    },
+    InvalidPattern {
+        line: usize,
+        pattern: String,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for StignoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StignoreError::FileRead { filename, source } => {
+                write!(f, "Could not read ignore file '{}': {}", filename, source)
+            }
+            StignoreError::IncludeCycle { filename } => {
+                write!(f, "Ignore file '{}' includes itself", filename)
+            }
+            StignoreError::InvalidPattern {
+                line,
+                pattern,
+                message,
+            } => {
+                write!(
+                    f,
+                    "Invalid pattern '{}' on line {}: {}",
+                    pattern, line, message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StignoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StignoreError::FileRead { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    /// `*`, anything except a path separator
+    Star,
+    /// `**`, anything including path separators
+    DoubleStar,
+    /// `?`, a single character except a path separator
+    Question,
+    /// `[...]`, a single character from (or not from) a set of ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob, matched against `/` separated paths relative to the folder root
+#[derive(Debug, Clone)]
+struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    fn compile(pattern: &str) -> Result<Glob, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => {
+                    let escaped = chars.get(i + 1).ok_or("dangling escape character")?;
+                    tokens.push(Token::Literal(*escaped));
+                    i += 2;
+                }
+                '*' => {
+                    let mut run = 0;
+                    while chars.get(i + run) == Some(&'*') {
+                        run += 1;
+                    }
+                    tokens.push(if run > 1 {
+                        Token::DoubleStar
+                    } else {
+                        Token::Star
+                    });
+                    i += run;
+                }
+                '?' => {
+                    tokens.push(Token::Question);
+                    i += 1;
+                }
+                '[' => {
+                    let (token, next) = compile_class(&chars, i)?;
+                    tokens.push(token);
+                    i = next;
+                }
+                c => {
+                    tokens.push(Token::Literal(c));
+                    i += 1;
+                }
+            }
+        }
+
+        Ok(Glob { tokens })
+    }
+
+    fn matches(&self, path: &[char]) -> bool {
+        match_tokens(&self.tokens, path)
+    }
+}
+
+/// Parses a character class starting at `chars[start] == '['`, returning the token and the index after `]`
+fn compile_class(chars: &[char], start: usize) -> Result<(Token, usize), String> {
+    let mut i = start + 1;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = match chars.get(i) {
+            None => return Err("unterminated character class".to_string()),
+            Some(']') if !first => break,
+            Some('\\') => {
+                i += 1;
+                *chars.get(i).ok_or("dangling escape character")?
+            }
+            Some(c) => *c,
+        };
+        first = false;
+        i += 1;
+
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|c| *c != ']') {
+            let end = match chars[i + 1] {
+                '\\' => *chars.get(i + 2).ok_or("dangling escape character")?,
+                end => end,
+            };
+            i += if chars[i + 1] == '\\' { 3 } else { 2 };
+            ranges.push((c, end));
+        } else {
+            ranges.push((c, c));
+        }
+    }
+
+    Ok((Token::Class { negated, ranges }, i + 1))
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return path.is_empty();
+    };
+
+    match token {
+        Token::Literal(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::Question => {
+            path.first().is_some_and(|c| *c != '/') && match_tokens(rest, &path[1..])
+        }
+        Token::Class { negated, ranges } => match path.first() {
+            Some(c) if *c != '/' => {
+                let in_class = ranges.iter().any(|(lo, hi)| lo <= c && c <= hi);
+                in_class != *negated && match_tokens(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            for i in 0..=path.len() {
+                if match_tokens(rest, &path[i..]) {
+                    return true;
+                }
+                if i < path.len() && path[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Token::DoubleStar => (0..=path.len()).any(|i| match_tokens(rest, &path[i..])),
+    }
+}
+
+/// Expands `{a,b}` alternations into every concrete pattern they describe
+fn expand_braces(pattern: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = pattern.chars().collect();
+
+    // Find the first unescaped top level `{` outside a character class
+    let mut i = 0;
+    let mut in_class = false;
+    let mut open = None;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '{' if !in_class => {
+                open = Some(i);
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(open) = open else {
+        return Ok(vec![pattern.to_string()]);
+    };
+
+    // Split the alternatives on top level commas up to the matching `}`
+    let mut depth = 0;
+    let mut alternatives = Vec::new();
+    let mut current = String::new();
+    let mut close = None;
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                current.push('\\');
+                if let Some(c) = chars.get(i + 1) {
+                    current.push(*c);
+                }
+                i += 1;
+            }
+            '{' => {
+                depth += 1;
+                current.push('{');
+            }
+            '}' if depth == 0 => {
+                close = Some(i);
+                break;
+            }
+            '}' => {
+                depth -= 1;
+                current.push('}');
+            }
+            ',' if depth == 0 => alternatives.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+        i += 1;
+    }
+    let close = close.ok_or("unterminated alternation")?;
+    alternatives.push(current);
+
+    let prefix: String = chars[..open].iter().collect();
+    let suffix: String = chars[close + 1..].iter().collect();
+
+    let mut expanded = Vec::new();
+    for alternative in alternatives {
+        expanded.extend(expand_braces(&format!("{prefix}{alternative}{suffix}"))?);
+    }
+    Ok(expanded)
+}
+
+/// Escapes a path so every character in it is matched literally, `/` is left alone as
+/// the separator. The result still needs a leading `/` to only match at the folder root,
+/// which also keeps a leading `!` or `#` from being read as syntax.
+pub fn escape_pattern(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if matches!(c, '\\' | '*' | '?' | '[' | ']' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A single rule from a .stignore file
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    /// The line as written in the file, including any prefixes
+    pub text: String,
+    /// 1-based line number within `source`
+    pub line: usize,
+    /// File the rule was read from, differs from the top level file for `#include`d rules
+    pub source: Option<PathBuf>,
+    /// `!` prefix, matching paths are explicitly not ignored
+    pub negated: bool,
+    /// `(?i)` prefix
+    pub case_insensitive: bool,
+    /// `(?d)` prefix, Syncthing may delete matching files blocking a directory removal
+    pub deletable: bool,
+    globs: Vec<Glob>,
+}
+
+impl IgnorePattern {
+    /// Parses a single pattern line, returning None for blank lines and comments
+    pub fn parse(text: &str, line: usize) -> Result<Option<IgnorePattern>, StignoreError> {
+        let trimmed = text.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            return Ok(None);
+        }
+
+        let invalid = |message: String| StignoreError::InvalidPattern {
+            line,
+            pattern: trimmed.to_string(),
+            message,
+        };
+
+        let mut rest = trimmed;
+        let mut negated = false;
+        let mut case_insensitive = false;
+        let mut deletable = false;
+        loop {
+            if let Some(r) = rest.strip_prefix('!').filter(|_| !negated) {
+                negated = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("(?i)").filter(|_| !case_insensitive) {
+                case_insensitive = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("(?d)").filter(|_| !deletable) {
+                deletable = true;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let mut body = rest.trim_end_matches('/').to_string();
+        if body.is_empty() {
+            return Err(invalid("pattern is empty".to_string()));
+        }
+        if case_insensitive {
+            body = body.to_lowercase();
+        }
+
+        // Unanchored patterns match at any depth
+        let bases = if let Some(anchored) = body.strip_prefix('/') {
+            vec![anchored.to_string()]
+        } else if let Some(unprefixed) = body.strip_prefix("**/") {
+            vec![body.clone(), unprefixed.to_string()]
+        } else {
+            vec![body.clone(), format!("**/{body}")]
+        };
+
+        let mut globs = Vec::new();
+        for base in bases {
+            for expanded in expand_braces(&base).map_err(|e| invalid(e.to_string()))? {
+                globs.push(Glob::compile(&expanded).map_err(invalid)?);
+            }
+        }
+
+        Ok(Some(IgnorePattern {
+            text: trimmed.to_string(),
+            line,
+            source: None,
+            negated,
+            case_insensitive,
+            deletable,
+            globs,
+        }))
+    }
+
+    /// Checks a `/` separated path relative to the folder root against this rule
+    pub fn matches(&self, path: &str) -> bool {
+        self.matched_prefix(path).is_some()
+    }
+
+    /// Returns the shortest leading part of the path this rule matches. A rule also
+    /// covers everything inside a matching directory, so this may be an ancestor.
+    pub fn matched_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let path = path.trim_matches('/');
+        let chars: Vec<char> = if self.case_insensitive {
+            path.to_lowercase().chars().collect()
+        } else {
+            path.chars().collect()
+        };
+
+        let mut boundaries: Vec<usize> = chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c == '/')
+            .map(|(i, _)| i)
+            .collect();
+        boundaries.push(chars.len());
+
+        boundaries
+            .into_iter()
+            .find(|end| self.globs.iter().any(|glob| glob.matches(&chars[..*end])))
+            .map(|end| {
+                // Lowercasing can change byte lengths, so count back through the original
+                let byte_end = path.char_indices().nth(end).map_or(path.len(), |(b, _)| b);
+                &path[..byte_end]
+            })
+    }
+}
+
+/// The rule that decided a path's ignore status
+#[derive(Debug, Clone)]
+pub struct IgnoreMatch<'a> {
+    pub pattern: &'a IgnorePattern,
+    /// The path itself, or the ancestor directory the rule matched
+    pub matched_path: String,
+    /// True when the rule matched an ancestor rather than the path itself
+    pub inherited: bool,
+}
+
+impl IgnoreMatch<'_> {
+    pub fn ignored(&self) -> bool {
+        !self.pattern.negated
+    }
+}
+
+/// The ordered rules of a .stignore file with any `#include`d files expanded in place
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFile {
+    pub patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreFile {
+    /// Parses .stignore content that isn't backed by a file, `#include` lines are rejected
+    pub fn parse(content: &str) -> Result<IgnoreFile, StignoreError> {
+        let mut patterns = Vec::new();
+        parse_into(content, None, &mut Vec::new(), &mut patterns)?;
+        Ok(IgnoreFile { patterns })
+    }
+
+    /// Parses content that is about to replace the file at `path`, `#include`s are
+    /// resolved next to it like they would be once it's written
+    pub fn parse_as(content: &str, path: &Path) -> Result<IgnoreFile, StignoreError> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| {
+            // Not written yet, its folder decides where includes may point
+            match (path.parent().map(fs::canonicalize), path.file_name()) {
+                (Some(Ok(dir)), Some(name)) => dir.join(name),
+                _ => path.to_path_buf(),
+            }
+        });
+        let mut patterns = Vec::new();
+        parse_into(content, Some(path), &mut vec![canonical], &mut patterns)?;
+        Ok(IgnoreFile { patterns })
+    }
+
+    /// Loads a .stignore file and everything it includes, a missing file ignores nothing
+    pub fn load(path: &Path) -> Result<IgnoreFile, StignoreError> {
+        if !path.exists() {
+            return Ok(IgnoreFile::default());
+        }
+
+        let mut patterns = Vec::new();
+        load_into(path, &mut Vec::new(), &mut patterns)?;
+        Ok(IgnoreFile { patterns })
+    }
+
+    /// Returns the rule deciding the path's fate, Syncthing applies the first match
+    pub fn first_match(&self, path: &str) -> Option<&IgnorePattern> {
+        self.patterns.iter().find(|p| p.matches(path))
+    }
+
+    /// Like `first_match`, also reporting whether the rule applies through an ancestor
+    pub fn evaluate(&self, path: &str) -> Option<IgnoreMatch<'_>> {
+        let path = path.trim_matches('/');
+        self.patterns.iter().find_map(|pattern| {
+            pattern.matched_prefix(path).map(|prefix| IgnoreMatch {
+                pattern,
+                matched_path: prefix.to_string(),
+                inherited: prefix.len() < path.len(),
+            })
+        })
+    }
+
+    /// Every rule matching the path or one of its ancestors in file order, not just the
+    /// one Syncthing would apply. Each .stignore line is reported once.
+    pub fn all_matches(&self, path: &str) -> Vec<IgnoreMatch<'_>> {
+        let path = path.trim_matches('/');
+        let mut matches: Vec<IgnoreMatch<'_>> = Vec::new();
+        for pattern in &self.patterns {
+            let already_reported = matches
+                .iter()
+                .any(|m| m.pattern.line == pattern.line && m.pattern.source == pattern.source);
+            if already_reported {
+                continue;
+            }
+            if let Some(prefix) = pattern.matched_prefix(path) {
+                matches.push(IgnoreMatch {
+                    pattern,
+                    matched_path: prefix.to_string(),
+                    inherited: prefix.len() < path.len(),
+                });
+            }
+        }
+        matches
+    }
+
+    /// Whether Syncthing would ignore the given path
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.first_match(path).is_some_and(|p| !p.negated)
+    }
+}
+
+fn load_into(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    patterns: &mut Vec<IgnorePattern>,
+) -> Result<(), StignoreError> {
+    let filename = path.to_string_lossy().to_string();
+    let canonical = fs::canonicalize(path).map_err(|source| StignoreError::FileRead {
+        filename: filename.clone(),
+        source,
+    })?;
+    if stack.contains(&canonical) {
+        return Err(StignoreError::IncludeCycle { filename });
+    }
+
+    let content = fs::read_to_string(path).map_err(|source| StignoreError::FileRead {
+        filename: filename.clone(),
+        source,
+    })?;
+
+    stack.push(canonical);
+    let result = parse_into(&content, Some(path), stack, patterns);
+    stack.pop();
+    result
+}
+
+fn parse_into(
+    content: &str,
+    source: Option<&Path>,
+    stack: &mut Vec<PathBuf>,
+    patterns: &mut Vec<IgnorePattern>,
+) -> Result<(), StignoreError> {
+    for (index, text) in content.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = text.trim();
+
+        if let Some(include) = trimmed.strip_prefix("#include") {
+            let include = include.trim();
+            let Some(source) = source.filter(|_| !include.is_empty()) else {
+                return Err(StignoreError::InvalidPattern {
+                    line,
+                    pattern: trimmed.to_string(),
+                    message: "#include needs a file path and a parent file".to_string(),
+                });
+            };
+            if !include_in_folder(stack, include) {
+                return Err(StignoreError::InvalidPattern {
+                    line,
+                    pattern: trimmed.to_string(),
+                    message: "#include must point at a file inside the folder".to_string(),
+                });
+            }
+            let include_path = source
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(include);
+            load_into(&include_path, stack, patterns)?;
+            continue;
+        }
+
+        // `#escape` only changes the escape character on Windows
+        if trimmed.starts_with("#escape") {
+            continue;
+        }
+
+        if let Some(mut pattern) = IgnorePattern::parse(text, line)? {
+            pattern.source = source.map(Path::to_path_buf);
+            patterns.push(pattern);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether an `#include` resolves inside the folder, the directory of the top level file.
+/// Syncthing reads includes through the folder's filesystem, which can't be left.
+fn include_in_folder(stack: &[PathBuf], include: &str) -> bool {
+    let (Some(root), Some(dir)) = (
+        stack.first().and_then(|top| top.parent()),
+        stack.last().and_then(|current| current.parent()),
+    ) else {
+        return false;
+    };
+
+    let mut resolved = dir.to_path_buf();
+    for component in Path::new(include).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    // A symlink inside the folder can still lead out of it
+    let resolved = fs::canonicalize(&resolved).unwrap_or(resolved);
+    resolved.starts_with(root)
+}
+
+/// First line of the block of a .stignore the agent maintains
+pub const MANAGED_BEGIN: &str = "// BEGIN stignore-manager";
+/// Last line of the block of a .stignore the agent maintains
+pub const MANAGED_END: &str = "// END stignore-manager";
+
+/// A .stignore split around the block between `MANAGED_BEGIN` and `MANAGED_END`. Entries
+/// inside belong to the agent, everything around them was written by hand and is kept
+/// exactly as it is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManagedStignore {
+    before: Vec<String>,
+    /// None until the agent first writes an entry
+    managed: Option<Vec<String>>,
+    after: Vec<String>,
+}
+
+impl ManagedStignore {
+    /// Splits a .stignore, a block missing its end marker runs to the end of the file
+    pub fn parse(content: &str) -> ManagedStignore {
+        let mut stignore = ManagedStignore::default();
+        let mut in_block = false;
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if stignore.managed.is_none() && trimmed == MANAGED_BEGIN {
+                stignore.managed = Some(Vec::new());
+                in_block = true;
+            } else if in_block && trimmed == MANAGED_END {
+                in_block = false;
+            } else if let Some(managed) = stignore.managed.as_mut().filter(|_| in_block) {
+                managed.push(line.to_string());
+            } else if stignore.managed.is_some() {
+                stignore.after.push(line.to_string());
+            } else {
+                stignore.before.push(line.to_string());
+            }
+        }
+        stignore
+    }
+
+    /// The patterns inside the managed block, in file order
+    pub fn managed_entries(&self) -> Vec<&str> {
+        self.managed
+            .iter()
+            .flatten()
+            .filter_map(|line| entry(line))
+            .collect()
+    }
+
+    /// The patterns outside the managed block, in file order
+    pub fn user_entries(&self) -> Vec<&str> {
+        self.before
+            .iter()
+            .chain(&self.after)
+            .filter_map(|line| entry(line))
+            .collect()
+    }
+
+    pub fn is_managed(&self, pattern: &str) -> bool {
+        self.managed_entries().contains(&pattern)
+    }
+
+    pub fn is_user_entry(&self, pattern: &str) -> bool {
+        self.user_entries().contains(&pattern)
+    }
+
+    /// Adds a pattern to the end of the managed block, creating the block at the end of
+    /// the file when there is none yet
+    pub fn add_managed(&mut self, pattern: &str) {
+        self.managed
+            .get_or_insert_with(Vec::new)
+            .push(pattern.to_string());
+    }
+
+    /// Removes a pattern from the managed block, returns whether it was there
+    pub fn remove_managed(&mut self, pattern: &str) -> bool {
+        self.managed
+            .as_mut()
+            .is_some_and(|managed| remove_entry(managed, pattern))
+    }
+
+    /// Removes a pattern written by hand, returns whether it was there
+    pub fn remove_user_entry(&mut self, pattern: &str) -> bool {
+        let before = remove_entry(&mut self.before, pattern);
+        let after = remove_entry(&mut self.after, pattern);
+        before || after
+    }
+
+    /// Moves the entries written by hand that `adopt` picks into the managed block, which
+    /// is how files from before the block existed are taken over. A new block takes the
+    /// place of the first moved line. Syncthing applies the first matching rule, so an
+    /// entry that would have to move past a `!` pattern or an `#include` to reach the
+    /// block stays where it is. Returns the moved patterns.
+    pub fn adopt(&mut self, adopt: impl Fn(&str) -> bool) -> Vec<String> {
+        if self.managed.is_none() {
+            let Some(first) = self
+                .before
+                .iter()
+                .position(|line| entry(line).is_some_and(&adopt))
+            else {
+                return Vec::new();
+            };
+            self.after = self.before.split_off(first);
+            self.managed = Some(Vec::new());
+        }
+
+        // Lines before the block move down to it, lines after it move up
+        let mut adopted_before = Vec::new();
+        let mut crossed_barrier = false;
+        let mut kept: Vec<String> = Vec::new();
+        for line in std::mem::take(&mut self.before).into_iter().rev() {
+            match entry(&line) {
+                Some(pattern) if !crossed_barrier && adopt(pattern) => {
+                    adopted_before.push(pattern.to_string())
+                }
+                Some(pattern) => {
+                    crossed_barrier |= is_order_barrier(pattern);
+                    kept.push(line);
+                }
+                None => kept.push(line),
+            }
+        }
+        kept.reverse();
+        adopted_before.reverse();
+        self.before = kept;
+
+        let mut adopted_after = Vec::new();
+        let mut crossed_barrier = false;
+        self.after.retain(|line| match entry(line) {
+            Some(pattern) if !crossed_barrier && adopt(pattern) => {
+                adopted_after.push(pattern.to_string());
+                false
+            }
+            Some(pattern) => {
+                crossed_barrier |= is_order_barrier(pattern);
+                true
+            }
+            None => true,
+        });
+
+        let mut adopted = adopted_before;
+        adopted.extend(adopted_after);
+        for pattern in &adopted {
+            self.add_managed(pattern);
+        }
+        adopted
+    }
+
+    /// Whether the file has a managed block yet
+    pub fn has_block(&self) -> bool {
+        self.managed.is_some()
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines: Vec<&str> = self.before.iter().map(String::as_str).collect();
+        if let Some(managed) = &self.managed {
+            lines.push(MANAGED_BEGIN);
+            lines.extend(managed.iter().map(String::as_str));
+            lines.push(MANAGED_END);
+        }
+        lines.extend(self.after.iter().map(String::as_str));
+
+        if lines.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", lines.join("\n"))
+        }
+    }
+}
+
+/// The pattern on a .stignore line, None for blank lines and comments
+fn entry(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    (!trimmed.is_empty() && !trimmed.starts_with("//")).then_some(trimmed)
+}
+
+/// Lines whose position relative to the agent's entries decides what gets ignored:
+/// `!` patterns and includes, which may hold them. Unparsable lines count too.
+fn is_order_barrier(pattern: &str) -> bool {
+    pattern.starts_with("#include")
+        || IgnorePattern::parse(pattern, 0)
+            .ok()
+            .flatten()
+            .is_none_or(|pattern| pattern.negated)
+}
+
+fn remove_entry(lines: &mut Vec<String>, pattern: &str) -> bool {
+    let count = lines.len();
+    lines.retain(|line| entry(line) != Some(pattern));
+    lines.len() != count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ignores(content: &str, path: &str) -> bool {
+        IgnoreFile::parse(content).unwrap().is_ignored(path)
+    }
+
+    #[test]
+    fn test_plain_name_matches_at_any_depth() {
+        assert!(ignores("sample", "sample"));
+        assert!(ignores("sample", "Movie 1/sample"));
+        assert!(ignores("sample", "Movie 1/sample/clip.mkv"));
+        assert!(!ignores("sample", "samples"));
+    }
+
+    #[test]
+    fn test_path_with_separator_matches_at_any_depth() {
+        assert!(ignores("Show B/Season 2", "Show B/Season 2"));
+        assert!(ignores("Show B/Season 2", "Show B/Season 2/E01.mkv"));
+        assert!(ignores("Show B/Season 2", "archive/Show B/Season 2"));
+        assert!(!ignores("Show B/Season 2", "Show B"));
+    }
+
+    #[test]
+    fn test_anchored_pattern() {
+        assert!(ignores("/foo", "foo"));
+        assert!(ignores("/foo", "foo/bar"));
+        assert!(!ignores("/foo", "bar/foo"));
+    }
+
+    #[test]
+    fn test_double_star_prefix_matches_root_too() {
+        assert!(ignores("**/sample", "sample"));
+        assert!(ignores("**/sample", "a/b/sample"));
+    }
+
+    #[test]
+    fn test_star_does_not_cross_separators() {
+        assert!(ignores("/Season *", "Season 1"));
+        assert!(ignores("/Season *", "Season 1/E01.mkv"));
+        assert!(!ignores("/a*c", "ab/c"));
+        assert!(ignores("/a**c", "ab/c"));
+    }
+
+    #[test]
+    fn test_question_mark_and_classes() {
+        assert!(ignores("/Season ?", "Season 1"));
+        assert!(!ignores("/Season ?", "Season 10"));
+        assert!(ignores("/Season [0-9]", "Season 4"));
+        assert!(!ignores("/Season [!0-9]", "Season 4"));
+        assert!(ignores("/Season [!0-9]", "Season X"));
+        assert!(ignores("/[]]", "]"));
+    }
+
+    #[test]
+    fn test_alternation() {
+        assert!(ignores("*.{mkv,mp4}", "a/movie.mkv"));
+        assert!(ignores("*.{mkv,mp4}", "movie.mp4"));
+        assert!(!ignores("*.{mkv,mp4}", "movie.avi"));
+        assert!(ignores("/{a,b{c,d}}", "bd"));
+    }
+
+    #[test]
+    fn test_escaped_metacharacters_are_literal() {
+        assert!(ignores(r"/Movie \[2023\]", "Movie [2023]"));
+        assert!(!ignores(r"/Movie \[2023\]", "Movie 2"));
+        assert!(ignores(r"/What\?", "What?"));
+        assert!(!ignores(r"/What\?", "Whats"));
+    }
+
+    #[test]
+    fn test_case_insensitive_prefix() {
+        assert!(ignores("(?i)movie*", "Movie 1 (2023)"));
+        assert!(!ignores("movie*", "Movie 1 (2023)"));
+    }
+
+    #[test]
+    fn test_negation_first_match_wins() {
+        let content = "!keep\n*\n";
+        assert!(!ignores(content, "keep"));
+        assert!(!ignores(content, "keep/file.txt"));
+        assert!(ignores(content, "other"));
+
+        // Negation after a broader match has no effect
+        assert!(ignores("*\n!keep\n", "keep"));
+    }
+
+    #[test]
+    fn test_combined_prefixes() {
+        let file = IgnoreFile::parse("(?d)!(?i)Keep\n").unwrap();
+        let pattern = &file.patterns[0];
+        assert!(pattern.negated && pattern.case_insensitive && pattern.deletable);
+        assert!(pattern.matches("KEEP"));
+    }
+
+    #[test]
+    fn test_comments_blank_lines_and_trailing_slash() {
+        let file = IgnoreFile::parse("// comment\n\n  \nfoo/\n").unwrap();
+        assert_eq!(file.patterns.len(), 1);
+        assert_eq!(file.patterns[0].line, 4);
+        assert!(file.is_ignored("foo"));
+    }
+
+    #[test]
+    fn test_first_match_reports_line() {
+        let file = IgnoreFile::parse("a\nShow B\n").unwrap();
+        let matched = file.first_match("Show B/Season 2").unwrap();
+        assert_eq!(matched.text, "Show B");
+        assert_eq!(matched.line, 2);
+    }
+
+    #[test]
+    fn test_evaluate_reports_inherited_matches() {
+        let file = IgnoreFile::parse("!/Show A/Season 1\nShow *\n").unwrap();
+
+        let direct = file.evaluate("Show B (1994)").unwrap();
+        assert!(direct.ignored());
+        assert!(!direct.inherited);
+        assert_eq!(direct.pattern.line, 2);
+
+        let inherited = file.evaluate("Show B (1994)/Season 2").unwrap();
+        assert!(inherited.ignored());
+        assert!(inherited.inherited);
+        assert_eq!(inherited.matched_path, "Show B (1994)");
+
+        let kept = file.evaluate("Show A/Season 1/E01.mkv").unwrap();
+        assert!(!kept.ignored());
+        assert_eq!(kept.matched_path, "Show A/Season 1");
+
+        assert!(file.evaluate("Movies").is_none());
+    }
+
+    #[test]
+    fn test_all_matches_reports_every_covering_line() {
+        let file =
+            IgnoreFile::parse("Show B*\n!/Show A\n/Show B (1994)/Season 2\nOther\n").unwrap();
+
+        let matches = file.all_matches("Show B (1994)/Season 2");
+        let lines: Vec<usize> = matches.iter().map(|m| m.pattern.line).collect();
+        assert_eq!(lines, vec![1, 3]);
+        assert!(matches[0].inherited);
+        assert_eq!(matches[0].matched_path, "Show B (1994)");
+        assert!(!matches[1].inherited);
+
+        assert!(file.all_matches("Movies").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(IgnoreFile::parse("[abc").is_err());
+        assert!(IgnoreFile::parse("{a,b").is_err());
+        assert!(IgnoreFile::parse("!").is_err());
+        assert!(IgnoreFile::parse("#include common.stignore").is_err());
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("common.stignore"), "// shared\n*.tmp\n").unwrap();
+        fs::write(
+            dir.path().join(".stignore"),
+            "#include common.stignore\nShow B\n",
+        )
+        .unwrap();
+
+        let file = IgnoreFile::load(&dir.path().join(".stignore")).unwrap();
+        assert!(file.is_ignored("a/b.tmp"));
+        assert!(file.is_ignored("Show B"));
+
+        let matched = file.first_match("b.tmp").unwrap();
+        assert_eq!(matched.line, 2);
+        assert_eq!(
+            matched.source.as_deref(),
+            Some(dir.path().join("common.stignore").as_path())
+        );
+    }
+
+    #[test]
+    fn test_parse_as_resolves_includes_next_to_the_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("common.stignore"), "*.tmp\n").unwrap();
+        let stignore_path = dir.path().join(".stignore");
+
+        let file =
+            IgnoreFile::parse_as("#include common.stignore\nShow B\n", &stignore_path).unwrap();
+        assert!(file.is_ignored("a/b.tmp"));
+        assert!(file.is_ignored("Show B"));
+
+        let result = IgnoreFile::parse_as("#include missing.stignore\n", &stignore_path);
+        assert!(matches!(result, Err(StignoreError::FileRead { .. })));
+        let result = IgnoreFile::parse_as("Show B\n[abc\n", &stignore_path);
+        assert!(matches!(
+            result,
+            Err(StignoreError::InvalidPattern { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_includes_stay_inside_the_folder() {
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("secret"), "password\n").unwrap();
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(dir.path().join("shared").join("common"), "*.tmp\n").unwrap();
+        fs::write(
+            dir.path().join("shared").join("nested"),
+            "#include ../../x\n",
+        )
+        .unwrap();
+        let stignore_path = dir.path().join(".stignore");
+
+        let file =
+            IgnoreFile::parse_as("#include ./shared/../shared/common\n", &stignore_path).unwrap();
+        assert!(file.is_ignored("a.tmp"));
+
+        let absolute = format!("#include {}\n", outside.path().join("secret").display());
+        let relative = format!(
+            "#include ../{}/secret\n",
+            outside.path().file_name().unwrap().to_string_lossy()
+        );
+        for content in [
+            absolute.as_str(),
+            relative.as_str(),
+            "#include shared/nested\n",
+        ] {
+            let result = IgnoreFile::parse_as(content, &stignore_path);
+            let Err(StignoreError::InvalidPattern { pattern, .. }) = result else {
+                panic!("{:?} was accepted: {:?}", content, result);
+            };
+            assert!(!pattern.contains("password"));
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+            let result = IgnoreFile::parse_as("#include link/secret\n", &stignore_path);
+            assert!(matches!(result, Err(StignoreError::InvalidPattern { .. })));
+        }
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a"), "#include b\n").unwrap();
+        fs::write(dir.path().join("b"), "#include a\n").unwrap();
+
+        let result = IgnoreFile::load(&dir.path().join("a"));
+        assert!(matches!(result, Err(StignoreError::IncludeCycle { .. })));
+    }
+
+    #[test]
+    fn test_missing_file_ignores_nothing() {
+        let dir = TempDir::new().unwrap();
+        let file = IgnoreFile::load(&dir.path().join(".stignore")).unwrap();
+        assert!(!file.is_ignored("anything"));
+    }
+
+    #[test]
+    fn test_managed_block_keeps_hand_written_lines() {
+        let content =
+            "// mine\n*.nfo\n// BEGIN stignore-manager\nMovie 1\n// END stignore-manager\n!keep\n";
+        let mut stignore = ManagedStignore::parse(content);
+        assert_eq!(stignore.render(), content);
+        assert_eq!(stignore.managed_entries(), vec!["Movie 1"]);
+        assert_eq!(stignore.user_entries(), vec!["*.nfo", "!keep"]);
+
+        stignore.add_managed("Movie 2");
+        assert!(stignore.remove_managed("Movie 1"));
+        assert!(!stignore.remove_managed("*.nfo"));
+        assert_eq!(
+            stignore.render(),
+            "// mine\n*.nfo\n// BEGIN stignore-manager\nMovie 2\n// END stignore-manager\n!keep\n"
+        );
+    }
+
+    #[test]
+    fn test_managed_block_is_created_and_adopts_entries() {
+        let mut stignore = ManagedStignore::parse("Movie 1\n*.nfo");
+        assert!(stignore.managed_entries().is_empty());
+        assert_eq!(stignore.render(), "Movie 1\n*.nfo\n");
+
+        let adopted = stignore.adopt(|pattern| !pattern.contains('*'));
+        assert_eq!(adopted, vec!["Movie 1"]);
+        stignore.add_managed("Movie 2");
+        assert_eq!(
+            stignore.render(),
+            "// BEGIN stignore-manager\nMovie 1\nMovie 2\n// END stignore-manager\n*.nfo\n"
+        );
+
+        // An unterminated block runs to the end of the file
+        let stignore = ManagedStignore::parse("// BEGIN stignore-manager\nMovie 1\n");
+        assert_eq!(stignore.managed_entries(), vec!["Movie 1"]);
+        assert!(stignore.user_entries().is_empty());
+    }
+
+    #[test]
+    fn test_adopt_never_moves_entries_past_negations() {
+        let content = "Movie1\n!Movie2/keep\nMovie2\n(?i)!Movie3/keep\n";
+        let paths = [
+            "Movie1",
+            "Movie2",
+            "Movie2/keep",
+            "Movie2/other",
+            "Movie3/keep",
+        ];
+        let results = |content: &str| -> Vec<bool> {
+            let file = IgnoreFile::parse(content).unwrap();
+            paths.iter().map(|path| file.is_ignored(path)).collect()
+        };
+
+        let mut stignore = ManagedStignore::parse(content);
+        let adopted = stignore.adopt(|pattern| !pattern.contains('!'));
+        assert_eq!(adopted, vec!["Movie1"]);
+        let adopted_content = stignore.render();
+        assert_eq!(
+            adopted_content,
+            "// BEGIN stignore-manager\nMovie1\n// END stignore-manager\n!Movie2/keep\nMovie2\n(?i)!Movie3/keep\n"
+        );
+        assert_eq!(results(&adopted_content), results(content));
+
+        // Entries before an existing block can't move down past a negation either
+        let content =
+            "Movie2\n!Movie2/keep\nMovie4\n// BEGIN stignore-manager\n// END stignore-manager\n";
+        let mut stignore = ManagedStignore::parse(content);
+        assert_eq!(
+            stignore.adopt(|pattern| !pattern.starts_with('!')),
+            vec!["Movie4"]
+        );
+        assert_eq!(results(&stignore.render()), results(content));
+    }
+
+    #[test]
+    fn test_escaped_names_only_match_themselves() {
+        let name = "Movie [Director's Cut] {1999} *?\\";
+        let line = format!("/{}", escape_pattern(name));
+        assert_eq!(line, "/Movie \\[Director's Cut\\] \\{1999\\} \\*\\?\\\\");
+        assert!(ignores(&line, name));
+        assert!(ignores(&line, &format!("{name}/clip.mkv")));
+        assert!(!ignores(&line, "Movie D"));
+        assert!(!ignores(&line, &format!("other/{name}")));
+
+        assert!(ignores(
+            &format!("/{}", escape_pattern("!Important")),
+            "!Important"
+        ));
+        assert!(ignores(
+            &format!("/{}", escape_pattern("#1 Hits")),
+            "#1 Hits"
+        ));
+    }
+}
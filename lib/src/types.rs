@@ -1,18 +1,40 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::hash::{Hash, Hasher};
 use std::ops::Add;
 
 pub const AGENT_API_V1_PREFIX: &str = "api/v1";
+/// Bumped whenever the agent API changes in a way older managers can't handle
+pub const AGENT_API_VERSION: u32 = 1;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ItemGroup {
     pub id: String,
     pub name: String,
+    /// Rounded down, kept for peers that predate `size_bytes`
     pub size_kb: u64,
     pub items: Vec<ItemGroup>,
     pub leaf: bool,
+    /// Exact size, 0 when the agent only reports `size_kb`
     #[serde(default)]
-    pub copy_count: u8,
+    pub size_bytes: u64,
+    /// Files at or below this item, 1 for a file
+    #[serde(default)]
+    pub file_count: u64,
+    /// Directories below this item, not counting itself
+    #[serde(default)]
+    pub dir_count: u64,
+    #[serde(default)]
+    pub copy_count: u32,
+    /// Names of the agents holding a copy, filled in by the manager when merging
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub present_on: BTreeSet<String>,
+    /// Set by the agent when its .stignore ignores the item or one of its parents
+    #[serde(default)]
+    pub ignored: bool,
+    /// Agents from `present_on` that ignore the item, so their copy won't stay in sync
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub ignored_on: BTreeSet<String>,
     #[serde(default)]
     pub has_conflicts: bool,
     #[serde(default)]
@@ -44,8 +66,61 @@ impl ItemGroup {
         Self::default()
     }
 
+    /// Records this item and everything below it as held by a single agent
+    pub fn mark_present_on(&mut self, agent_name: &str) {
+        self.present_on = BTreeSet::from([agent_name.to_string()]);
+        self.ignored_on = if self.ignored {
+            BTreeSet::from([agent_name.to_string()])
+        } else {
+            BTreeSet::new()
+        };
+        self.copy_count = 1;
+        for item in &mut self.items {
+            item.mark_present_on(agent_name);
+        }
+    }
+
+    /// Copies that Syncthing keeps in sync, i.e. not counting agents that ignore the item
+    pub fn effective_copy_count(&self) -> u32 {
+        if self.present_on.is_empty() {
+            return self.copy_count;
+        }
+        self.present_on.difference(&self.ignored_on).count() as u32
+    }
+
+    /// Exact size in bytes, estimated from `size_kb` for agents that don't report it
+    pub fn bytes(&self) -> u64 {
+        if self.size_bytes > 0 {
+            self.size_bytes
+        } else {
+            self.size_kb * 1024
+        }
+    }
+
+    /// Fills in `size_bytes` throughout the tree for agents that only report `size_kb`
+    pub fn fill_missing_bytes(&mut self) {
+        self.size_bytes = self.bytes();
+        for item in &mut self.items {
+            item.fill_missing_bytes();
+        }
+    }
+
+    /// Files are neither leaf directories nor have children of their own
+    pub fn is_dir(&self) -> bool {
+        self.leaf || !self.items.is_empty()
+    }
+
+    /// Directories with children are listed first, then leaf directories, then files
+    fn listing_rank(&self) -> u8 {
+        match (self.leaf, self.is_dir()) {
+            (false, true) => 0,
+            (true, _) => 1,
+            (false, false) => 2,
+        }
+    }
+
     pub fn has_insufficient_copies(&self, minimum_copies: u8) -> bool {
-        if self.copy_count < minimum_copies {
+        if self.effective_copy_count() < u32::from(minimum_copies) {
             return true;
         }
 
@@ -61,6 +136,8 @@ impl Add for ItemGroup {
     fn add(self, other: Self) -> Self {
         use std::collections::HashMap;
 
+        let self_bytes = self.bytes();
+        let other_bytes = other.bytes();
         let mut merged_items: HashMap<String, ItemGroup> = HashMap::new();
 
         for item in self.items {
@@ -82,11 +159,9 @@ impl Add for ItemGroup {
 
         let mut merged_items_vec: Vec<ItemGroup> = merged_items.into_values().collect();
         merged_items_vec.sort_by(|a, b| {
-            if a.leaf == b.leaf {
-                a.name.cmp(&b.name)
-            } else {
-                a.leaf.cmp(&b.leaf)
-            }
+            a.listing_rank()
+                .cmp(&b.listing_rank())
+                .then_with(|| a.name.cmp(&b.name))
         });
 
         let total_size_kb = if merged_items_vec.is_empty() {
@@ -102,9 +177,32 @@ impl Add for ItemGroup {
                 .sum::<u64>()
         };
 
+        let (size_bytes, file_count, dir_count) = if merged_items_vec.is_empty() {
+            if !self.id.is_empty() && (self_bytes > 0 || other_bytes == 0) {
+                (self_bytes, self.file_count, self.dir_count)
+            } else {
+                (other_bytes, other.file_count, other.dir_count)
+            }
+        } else {
+            merged_items_vec
+                .iter()
+                .fold((0, 0, 0), |(bytes, files, dirs), item| {
+                    (
+                        bytes + item.bytes(),
+                        files + item.file_count,
+                        dirs + item.dir_count + u64::from(item.is_dir()),
+                    )
+                })
+        };
+
         let self_empty = self.id.is_empty();
         let other_empty = other.id.is_empty();
 
+        let mut present_on = self.present_on;
+        present_on.extend(other.present_on);
+        let mut ignored_on = self.ignored_on;
+        ignored_on.extend(other.ignored_on);
+
         Self {
             id: if self.id.is_empty() {
                 other.id
@@ -119,13 +217,28 @@ impl Add for ItemGroup {
             size_kb: total_size_kb,
             items: merged_items_vec,
             leaf: self.leaf && other.leaf,
-            copy_count: if self_empty {
+            size_bytes,
+            file_count,
+            dir_count,
+            // An agent can only hold one copy, so the presence set wins when there is one
+            copy_count: if !present_on.is_empty() {
+                present_on.len() as u32
+            } else if self_empty {
                 other.copy_count
             } else if other_empty {
                 self.copy_count
             } else {
-                self.copy_count + other.copy_count
+                self.copy_count.saturating_add(other.copy_count)
             },
+            present_on,
+            ignored: if self_empty {
+                other.ignored
+            } else if other_empty {
+                self.ignored
+            } else {
+                self.ignored && other.ignored
+            },
+            ignored_on,
             has_conflicts: self.has_conflicts || other.has_conflicts,
             conflict_count: self.conflict_count + other.conflict_count,
             is_syncing: self.is_syncing || other.is_syncing,
@@ -166,6 +279,20 @@ pub struct NotFoundResponse {
 pub struct IgnoreRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    /// Report what would be written to .stignore without changing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnignoreRequest {
+    pub category_id: String,
+    pub folder_path: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also remove matching lines outside the managed block, which are refused otherwise
+    #[serde(default)]
+    pub include_user_entries: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -173,6 +300,18 @@ pub struct IgnoreResponse {
     pub success: bool,
     pub message: String,
     pub ignored_path: Option<String>,
+    /// Set instead of modifying .stignore when the request was a dry run
+    #[serde(default)]
+    pub preview: Option<IgnorePreview>,
+}
+
+/// What ignoring a path would do to its category's .stignore
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IgnorePreview {
+    /// The line that would be added to the managed block, None when the path is already ignored
+    pub line: Option<String>,
+    /// Existing rules matching the path or one of its ancestors, in file order
+    pub covering_patterns: Vec<MatchedIgnoreRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -181,9 +320,21 @@ pub struct IgnoreStatusRequest {
     pub folder_path: Vec<String>,
 }
 
+/// The .stignore rule that decided an item's ignore status
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MatchedIgnoreRule {
+    pub pattern: String,
+    pub line: usize,
+    /// Ancestor path the rule matched when the item is covered by a parent directory
+    #[serde(default)]
+    pub inherited_from: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct IgnoreStatusResponse {
     pub ignored: bool,
+    #[serde(default)]
+    pub matched_rule: Option<MatchedIgnoreRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -196,6 +347,8 @@ pub struct BulkIgnoreStatusItem {
     pub category_id: String,
     pub folder_path: Vec<String>,
     pub ignored: bool,
+    #[serde(default)]
+    pub matched_rule: Option<MatchedIgnoreRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -207,6 +360,9 @@ pub struct BulkIgnoreStatusResponse {
 pub struct DeleteRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    /// Report what would be deleted without touching the filesystem
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -214,6 +370,230 @@ pub struct DeleteResponse {
     pub success: bool,
     pub message: String,
     pub deleted_path: Option<String>,
+    /// Set when the item was moved into the agent's trash rather than removed
+    #[serde(default)]
+    pub trash_id: Option<String>,
+    /// Set instead of deleting anything when the request was a dry run
+    #[serde(default)]
+    pub preview: Option<DeletePreview>,
+}
+
+/// What deleting a path would remove
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DeletePreview {
+    pub exists: bool,
+    pub is_dir: bool,
+    /// Whether the item would go to the agent's trash rather than being removed for good
+    pub moves_to_trash: bool,
+    pub total_bytes: u64,
+    /// Every file that would be removed, relative to the category and sorted
+    pub files: Vec<String>,
+}
+
+/// An item moved into an agent's trash by a delete
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashEntry {
+    pub id: String,
+    pub category_id: String,
+    pub folder_path: Vec<String>,
+    pub is_dir: bool,
+    pub size_kb: u64,
+    /// Unix timestamp in seconds
+    pub deleted_at: u64,
+    /// Unix timestamp in seconds after which the item is purged
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashListResponse {
+    pub enabled: bool,
+    pub retention_days: u64,
+    pub items: Vec<TrashEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashRestoreRequest {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashPurgeRequest {
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Purges everything in the trash, required when no ID is given
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Why a revision of a category's .stignore was saved
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StignoreChange {
+    Ignore,
+    Unignore,
+    Revert,
+    /// Replaced as a whole through the raw .stignore API
+    Edit,
+    /// Entries written by hand were moved into the managed block
+    Adopt,
+    /// The file was changed by something other than the agent, or existed before history
+    /// was kept
+    Outside,
+}
+
+/// A saved copy of a category's .stignore, taken right after it changed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StignoreRevision {
+    pub id: String,
+    pub category_id: String,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+    pub change: StignoreChange,
+    /// The line added or removed, or the revision a revert went back to
+    pub detail: String,
+    pub line_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreHistoryCategory {
+    pub id: String,
+    pub name: String,
+    /// Newest first
+    pub revisions: Vec<StignoreRevision>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreHistoryResponse {
+    pub enabled: bool,
+    /// Revisions kept per category
+    pub retention: usize,
+    pub categories: Vec<StignoreHistoryCategory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreDiffRequest {
+    pub category_id: String,
+    /// Compares against an empty file when unset
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Compares against the current .stignore when unset
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Same,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreDiffResponse {
+    pub success: bool,
+    pub message: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreRevertRequest {
+    pub category_id: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreRevertResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// A category's .stignore as a whole. On a rejected write `content` and `etag` are those
+/// of the file as it is now.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreContentResponse {
+    pub success: bool,
+    pub message: String,
+    pub content: String,
+    /// Also sent as the `ETag` header, writes must send it back in `If-Match`
+    pub etag: String,
+    /// Patterns inside the block the agent maintains
+    #[serde(default)]
+    pub managed_entries: Vec<String>,
+    /// Patterns written by hand outside that block
+    #[serde(default)]
+    pub user_entries: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreWriteRequest {
+    pub content: String,
+}
+
+/// Moves entries written by hand into the managed block
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StignoreAdoptRequest {
+    /// The patterns to move. When unset every pattern naming a single path literally is
+    /// moved, which covers the lines older versions of the agent wrote.
+    #[serde(default)]
+    pub entries: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StignoreAdoptResponse {
+    pub success: bool,
+    pub message: String,
+    pub adopted: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HealthCategory {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+    pub api_version: u32,
+    pub uptime_seconds: u64,
+    pub categories: Vec<HealthCategory>,
+    pub features: Vec<String>,
+}
+
+/// Totals of one category, without its child items
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CategorySummary {
+    pub id: String,
+    pub name: String,
+    pub size_kb: u64,
+    pub size_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub item_count: usize,
+    pub has_conflicts: bool,
+    pub conflict_count: u32,
+    pub is_syncing: bool,
+    pub stversions_size_kb: u64,
+    pub stfolder_present: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategorySummaryResponse {
+    pub categories: Vec<CategorySummary>,
 }
 
 // Manager-side agent API types (for communicating with agents)
@@ -253,33 +633,29 @@ impl SortOrder {
         match self {
             SortOrder::NameAsc => {
                 items.sort_by(|a, b| {
-                    if a.leaf == b.leaf {
-                        a.name.to_lowercase().cmp(&b.name.to_lowercase())
-                    } else {
-                        a.leaf.cmp(&b.leaf)
-                    }
+                    a.listing_rank()
+                        .cmp(&b.listing_rank())
+                        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
                 });
             }
             SortOrder::NameDesc => {
                 items.sort_by(|a, b| {
-                    if a.leaf == b.leaf {
-                        b.name.to_lowercase().cmp(&a.name.to_lowercase())
-                    } else {
-                        a.leaf.cmp(&b.leaf)
-                    }
+                    a.listing_rank()
+                        .cmp(&b.listing_rank())
+                        .then_with(|| b.name.to_lowercase().cmp(&a.name.to_lowercase()))
                 });
             }
             SortOrder::SizeDesc => {
                 items.sort_by(|a, b| {
-                    b.size_kb
-                        .cmp(&a.size_kb)
+                    b.bytes()
+                        .cmp(&a.bytes())
                         .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
                 });
             }
             SortOrder::SizeAsc => {
                 items.sort_by(|a, b| {
-                    a.size_kb
-                        .cmp(&b.size_kb)
+                    a.bytes()
+                        .cmp(&b.bytes())
                         .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
                 });
             }
@@ -293,6 +669,21 @@ impl std::fmt::Display for SortOrder {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentHealthResponse {
+    pub status: String,
+    pub version: String,
+    pub api_version: u32,
+    pub uptime_seconds: u64,
+    pub categories: Vec<HealthCategory>,
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentCategorySummaryResponse {
+    pub categories: Vec<CategorySummary>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentCategoryListingResponse {
     pub items: Vec<ItemGroup>,
@@ -312,27 +703,123 @@ pub struct AgentItemInfoResponse {
 pub struct AgentIgnoreRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentIgnoreResponse {
     pub success: bool,
     pub message: String,
+    #[serde(default)]
+    pub preview: Option<IgnorePreview>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentUnignoreRequest {
+    pub category_id: String,
+    pub folder_path: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also remove matching lines outside the managed block, which are refused otherwise
+    #[serde(default)]
+    pub include_user_entries: bool,
 }
 
-pub type AgentUnignoreRequest = AgentIgnoreRequest;
 pub type AgentUnignoreResponse = AgentIgnoreResponse;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentDeleteRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentDeleteResponse {
     pub success: bool,
     pub message: String,
+    #[serde(default)]
+    pub preview: Option<DeletePreview>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentTrashListResponse {
+    pub enabled: bool,
+    pub retention_days: u64,
+    pub items: Vec<TrashEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentTrashRestoreRequest {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentTrashPurgeRequest {
+    pub id: Option<String>,
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentTrashResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreHistoryResponse {
+    pub enabled: bool,
+    pub retention: usize,
+    pub categories: Vec<StignoreHistoryCategory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreDiffRequest {
+    pub category_id: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreDiffResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreRevertRequest {
+    pub category_id: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreRevertResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreContentResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub etag: String,
+    #[serde(default)]
+    pub managed_entries: Vec<String>,
+    #[serde(default)]
+    pub user_entries: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentStignoreWriteRequest {
+    pub content: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -344,6 +831,8 @@ pub struct AgentIgnoreStatusRequest {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentIgnoreStatusResponse {
     pub ignored: bool,
+    #[serde(default)]
+    pub matched_rule: Option<MatchedIgnoreRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -356,6 +845,28 @@ pub struct AgentBulkIgnoreStatusResponse {
     pub items: Vec<AgentIgnoreStatusResponse>,
 }
 
+// Agent-to-manager enrolment types
+/// Sent by an agent to announce itself to the manager, then repeated as a heartbeat
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HeartbeatRequest {
+    pub name: String,
+    /// Where the manager can reach the agent, `host:port` or a URL
+    pub hostname: String,
+    pub api_key: String,
+    pub version: String,
+    pub api_version: u32,
+    pub categories: Vec<String>,
+    pub heartbeat_interval_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HeartbeatResponse {
+    pub success: bool,
+    pub message: String,
+    /// False while the agent waits for an Admin to approve it
+    pub approved: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -478,6 +989,69 @@ mod tests {
         assert_eq!(result.size_kb, 125);
     }
 
+    #[test]
+    fn test_item_group_add_tracks_presence() {
+        let mut on_a = ItemGroup {
+            id: "parent".to_string(),
+            name: "Parent".to_string(),
+            items: vec![ItemGroup {
+                id: "child".to_string(),
+                name: "Child".to_string(),
+                leaf: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut on_c = on_a.clone();
+        on_a.mark_present_on("A");
+        on_c.mark_present_on("C");
+
+        // Merging the same agent twice doesn't count it twice
+        let merged = on_a.clone() + on_c + on_a + ItemGroup::empty();
+        assert_eq!(merged.copy_count, 2);
+        assert_eq!(merged.present_on.iter().collect::<Vec<_>>(), vec!["A", "C"]);
+        assert_eq!(merged.items[0].present_on.len(), 2);
+
+        // Listings without presence data still serialize as before
+        let json = serde_json::to_string(&ItemGroup::empty()).unwrap();
+        assert!(!json.contains("present_on"));
+    }
+
+    #[test]
+    fn test_effective_copy_count_excludes_ignoring_agents() {
+        let item = ItemGroup {
+            id: "movie".to_string(),
+            name: "Movie".to_string(),
+            leaf: true,
+            ..Default::default()
+        };
+        let mut on_a = item.clone();
+        on_a.mark_present_on("A");
+        let mut on_b = ItemGroup {
+            ignored: true,
+            ..item.clone()
+        };
+        on_b.mark_present_on("B");
+
+        let merged = on_a.clone() + on_b.clone();
+        assert_eq!(merged.copy_count, 2);
+        assert_eq!(merged.effective_copy_count(), 1);
+        assert!(merged.has_insufficient_copies(2));
+        assert!(!merged.ignored);
+
+        // Lingering only on an agent that ignores it counts as no copies at all
+        assert_eq!(on_b.effective_copy_count(), 0);
+        assert!(on_b.has_insufficient_copies(1));
+
+        // Without presence data the raw count is all there is
+        let unmerged = ItemGroup {
+            copy_count: 2,
+            ignored: true,
+            ..item
+        };
+        assert_eq!(unmerged.effective_copy_count(), 2);
+    }
+
     #[test]
     fn test_agent_api_types_serialization() {
         let category_response = AgentCategoryListingResponse {
@@ -619,7 +1193,13 @@ mod tests {
             size_kb: 1000,
             items: vec![],
             leaf: false,
+            size_bytes: 0,
+            file_count: 0,
+            dir_count: 0,
             copy_count: 1,
+            present_on: BTreeSet::new(),
+            ignored: false,
+            ignored_on: BTreeSet::new(),
             has_conflicts: true,
             conflict_count: 2,
             is_syncing: false,
@@ -633,7 +1213,13 @@ mod tests {
             size_kb: 1000,
             items: vec![],
             leaf: false,
+            size_bytes: 0,
+            file_count: 0,
+            dir_count: 0,
             copy_count: 1,
+            present_on: BTreeSet::new(),
+            ignored: false,
+            ignored_on: BTreeSet::new(),
             has_conflicts: false,
             conflict_count: 0,
             is_syncing: true,
@@ -658,7 +1244,13 @@ mod tests {
             size_kb: 500,
             items: vec![],
             leaf: true,
+            size_bytes: 0,
+            file_count: 0,
+            dir_count: 0,
             copy_count: 1,
+            present_on: BTreeSet::new(),
+            ignored: false,
+            ignored_on: BTreeSet::new(),
             has_conflicts: true,
             conflict_count: 3,
             is_syncing: true,
@@ -680,4 +1272,68 @@ mod tests {
         assert_eq!(deserialized.stversions_size_kb, 1024);
         assert!(deserialized.stfolder_present);
     }
+
+    fn file(name: &str, size_bytes: u64) -> ItemGroup {
+        ItemGroup {
+            id: name.to_string(),
+            name: name.to_string(),
+            size_kb: size_bytes / 1024,
+            size_bytes,
+            file_count: 1,
+            copy_count: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merging_keeps_exact_sizes_and_counts() {
+        let subtitles = |files: Vec<ItemGroup>| ItemGroup {
+            id: "Subs".to_string(),
+            name: "Subs".to_string(),
+            size_kb: 0,
+            size_bytes: files.iter().map(|f| f.size_bytes).sum(),
+            file_count: files.len() as u64,
+            items: files,
+            leaf: true,
+            copy_count: 1,
+            ..Default::default()
+        };
+        let movie = |subs: ItemGroup| ItemGroup {
+            id: "Movie".to_string(),
+            name: "Movie".to_string(),
+            size_kb: 0,
+            size_bytes: subs.size_bytes,
+            file_count: subs.file_count,
+            dir_count: 1,
+            items: vec![subs],
+            copy_count: 1,
+            ..Default::default()
+        };
+
+        // Every subtitle is under a KB, each agent holds a different pair of them
+        let on_a = movie(subtitles(vec![file("en.srt", 900), file("fr.srt", 800)]));
+        let on_b = movie(subtitles(vec![file("en.srt", 900), file("de.srt", 700)]));
+
+        let merged = ItemGroup::empty() + on_a + on_b;
+        assert_eq!(merged.size_bytes, 2400);
+        assert_eq!(merged.file_count, 3);
+        assert_eq!(merged.dir_count, 1);
+        assert_eq!(merged.items[0].size_bytes, 2400);
+    }
+
+    #[test]
+    fn test_sizes_from_agents_without_byte_counts() {
+        let json = r#"{"id":"old","name":"Old","size_kb":3,"items":[],"leaf":true}"#;
+        let mut item: ItemGroup = serde_json::from_str(json).unwrap();
+        assert_eq!(item.size_bytes, 0);
+        assert_eq!(item.bytes(), 3072);
+
+        item.fill_missing_bytes();
+        assert_eq!(item.size_bytes, 3072);
+
+        let mut small = vec![file("small", 1000), item, file("big", 5000)];
+        SortOrder::SizeDesc.sort_items(&mut small);
+        let order: Vec<&str> = small.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(order, vec!["big", "Old", "small"]);
+    }
 }
@@ -10,6 +10,10 @@ pub enum AgentError {
     Timeout(reqwest::Error),
     InvalidResponse(String),
     OperationFailed(String),
+    /// The agent answered 404, the item or endpoint doesn't exist there
+    NotFound(String),
+    /// The agent answered 409 and refused the change, holds its message
+    Conflict(String),
 }
 
 impl std::fmt::Display for AgentError {
@@ -19,6 +23,8 @@ impl std::fmt::Display for AgentError {
             AgentError::Timeout(e) => write!(f, "Request timed out: {}", e),
             AgentError::InvalidResponse(msg) => write!(f, "Invalid response: {}", msg),
             AgentError::OperationFailed(msg) => write!(f, "Operation failed: {}", msg),
+            AgentError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            AgentError::Conflict(msg) => write!(f, "Refused: {}", msg),
         }
     }
 }
@@ -27,6 +33,38 @@ impl std::error::Error for AgentError {}
 
 pub const DEFAULT_API_PREFIX: &str = AGENT_API_V1_PREFIX;
 
+/// Outcome of replacing a category's .stignore on an agent
+#[derive(Debug)]
+pub enum StignoreWrite {
+    Written(AgentStignoreContentResponse),
+    /// The file changed since it was read, holds its current content and ETag
+    Conflict(AgentStignoreContentResponse),
+}
+
+fn send_error(agent: &Agent, e: reqwest::Error) -> AgentError {
+    if e.is_timeout() {
+        tracing::warn!("Request to agent '{}' timed out: {}", agent.name, e);
+        AgentError::Timeout(e)
+    } else {
+        tracing::warn!("Request to agent '{}' failed: {}", agent.name, e);
+        AgentError::RequestFailed(e)
+    }
+}
+
+/// Percent-encodes a value used as a single segment of an endpoint path
+fn encode_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct AgentClient {
     client: Client,
@@ -76,18 +114,13 @@ impl AgentClient {
         &self.api_prefix
     }
 
-    /// Generic method to make authenticated requests to agents
-    async fn make_request<T, R>(
+    /// Authenticated request to an agent endpoint, honouring the agent's own timeout
+    fn build_request(
         &self,
         agent: &Agent,
         endpoint: &str,
         method: Method,
-        body: Option<&T>,
-    ) -> Result<R, AgentError>
-    where
-        T: Serialize,
-        R: for<'de> Deserialize<'de>,
-    {
+    ) -> reqwest::RequestBuilder {
         let url = format_agent_url(&agent.hostname, &self.api_prefix, endpoint);
         tracing::debug!(
             "Making {} request to agent '{}' at URL: {}",
@@ -101,6 +134,26 @@ impl AgentClient {
             .request(method, &url)
             .header("X-API-Key", &agent.api_key);
 
+        if let Some(timeout_seconds) = agent.timeout_seconds {
+            request = request.timeout(Duration::from_secs(timeout_seconds));
+        }
+        request
+    }
+
+    /// Generic method to make authenticated requests to agents
+    async fn make_request<T, R>(
+        &self,
+        agent: &Agent,
+        endpoint: &str,
+        method: Method,
+        body: Option<&T>,
+    ) -> Result<R, AgentError>
+    where
+        T: Serialize,
+        R: for<'de> Deserialize<'de>,
+    {
+        let mut request = self.build_request(agent, endpoint, method);
+
         if let Some(body) = body {
             request = request
                 .header("Content-Type", "application/json")
@@ -112,15 +165,7 @@ impl AgentClient {
             "Sending request to agent '{}' (timeout configured)",
             agent.name
         );
-        let response = request.send().await.map_err(|e| {
-            if e.is_timeout() {
-                tracing::warn!("Request to agent '{}' timed out: {}", agent.name, e);
-                AgentError::Timeout(e)
-            } else {
-                tracing::warn!("Request to agent '{}' failed: {}", agent.name, e);
-                AgentError::RequestFailed(e)
-            }
-        })?;
+        let response = request.send().await.map_err(|e| send_error(agent, e))?;
 
         let status = response.status();
         tracing::debug!(
@@ -138,6 +183,14 @@ impl AgentClient {
                     status,
                     error_body
                 );
+                return Err(AgentError::NotFound(error_body));
+            } else if status == reqwest::StatusCode::CONFLICT {
+                tracing::debug!("Agent '{}' refused the request: {}", agent.name, error_body);
+                let message = serde_json::from_str::<serde_json::Value>(&error_body)
+                    .ok()
+                    .and_then(|body| body["message"].as_str().map(str::to_string))
+                    .unwrap_or(error_body);
+                return Err(AgentError::Conflict(message));
             } else {
                 tracing::error!(
                     "Agent '{}' returned error status {}: {}",
@@ -169,14 +222,35 @@ impl AgentClient {
         result
     }
 
+    /// Cheap liveness check that doesn't make the agent walk its categories
+    pub async fn get_health(&self, agent: &Agent) -> Result<AgentHealthResponse, AgentError> {
+        self.make_request::<(), _>(agent, "health", Method::GET, None)
+            .await
+    }
+
+    /// Per-category totals without the item trees
+    pub async fn get_category_summary(
+        &self,
+        agent: &Agent,
+    ) -> Result<AgentCategorySummaryResponse, AgentError> {
+        self.make_request::<(), _>(agent, "summary", Method::GET, None)
+            .await
+    }
+
     /// Get categories from an agent
     pub async fn get_categories(
         &self,
         agent: &Agent,
     ) -> Result<AgentCategoryListingResponse, AgentError> {
         tracing::info!("Getting categories from agent '{}'", agent.name);
-        self.make_request::<(), _>(agent, "categories", Method::GET, None)
-            .await
+        let mut response: AgentCategoryListingResponse = self
+            .make_request::<(), _>(agent, "categories", Method::GET, None)
+            .await?;
+        response
+            .items
+            .iter_mut()
+            .for_each(ItemGroup::fill_missing_bytes);
+        Ok(response)
     }
 
     /// Get item information from an agent
@@ -190,8 +264,11 @@ impl AgentClient {
             agent.name,
             request.item_path
         );
-        self.make_request(agent, "items", Method::POST, Some(request))
-            .await
+        let mut response: AgentItemInfoResponse = self
+            .make_request(agent, "items", Method::POST, Some(request))
+            .await?;
+        response.item.fill_missing_bytes();
+        Ok(response)
     }
 
     /// Ignore an item on an agent
@@ -200,23 +277,15 @@ impl AgentClient {
         agent: &Agent,
         request: &AgentIgnoreRequest,
     ) -> Result<AgentIgnoreResponse, AgentError> {
-        let response = self
+        let response: AgentIgnoreResponse = self
             .make_request(agent, "ignore", Method::POST, Some(request))
             .await?;
 
         // Check if the operation was successful
-        match response {
-            AgentIgnoreResponse {
-                success: true,
-                message,
-            } => Ok(AgentIgnoreResponse {
-                success: true,
-                message,
-            }),
-            AgentIgnoreResponse {
-                success: false,
-                message,
-            } => Err(AgentError::OperationFailed(message)),
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
         }
     }
 
@@ -226,22 +295,14 @@ impl AgentClient {
         agent: &Agent,
         request: &AgentUnignoreRequest,
     ) -> Result<AgentUnignoreResponse, AgentError> {
-        let response = self
+        let response: AgentUnignoreResponse = self
             .make_request(agent, "unignore", Method::POST, Some(request))
             .await?;
 
-        match response {
-            AgentUnignoreResponse {
-                success: true,
-                message,
-            } => Ok(AgentUnignoreResponse {
-                success: true,
-                message,
-            }),
-            AgentUnignoreResponse {
-                success: false,
-                message,
-            } => Err(AgentError::OperationFailed(message)),
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
         }
     }
 
@@ -261,27 +322,168 @@ impl AgentClient {
         agent: &Agent,
         request: &AgentDeleteRequest,
     ) -> Result<AgentDeleteResponse, AgentError> {
-        let response = self
+        let response: AgentDeleteResponse = self
             .make_request(agent, "delete", Method::POST, Some(request))
             .await?;
 
         // Check if the operation was successful
-        match response {
-            AgentDeleteResponse {
-                success: true,
-                message,
-            } => Ok(AgentDeleteResponse {
-                success: true,
-                message,
-            }),
-            AgentDeleteResponse {
-                success: false,
-                message,
-            } => Err(AgentError::OperationFailed(message)),
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
+        }
+    }
+
+    /// List the items in an agent's trash
+    pub async fn list_trash(&self, agent: &Agent) -> Result<AgentTrashListResponse, AgentError> {
+        self.make_request::<(), _>(agent, "trash", Method::GET, None)
+            .await
+    }
+
+    /// Restore an item from an agent's trash to where it was deleted from
+    pub async fn restore_trash_item(
+        &self,
+        agent: &Agent,
+        request: &AgentTrashRestoreRequest,
+    ) -> Result<AgentTrashResponse, AgentError> {
+        let response: AgentTrashResponse = self
+            .make_request(agent, "trash/restore", Method::POST, Some(request))
+            .await?;
+
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
+        }
+    }
+
+    /// Permanently remove one item, or everything, from an agent's trash
+    pub async fn purge_trash(
+        &self,
+        agent: &Agent,
+        request: &AgentTrashPurgeRequest,
+    ) -> Result<AgentTrashResponse, AgentError> {
+        let response: AgentTrashResponse = self
+            .make_request(agent, "trash/purge", Method::POST, Some(request))
+            .await?;
+
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
+        }
+    }
+
+    /// List the saved .stignore revisions of every category on an agent
+    pub async fn get_stignore_history(
+        &self,
+        agent: &Agent,
+    ) -> Result<AgentStignoreHistoryResponse, AgentError> {
+        self.make_request::<(), _>(agent, "history", Method::GET, None)
+            .await
+    }
+
+    /// Compare two saved .stignore revisions, or one with the current file
+    pub async fn diff_stignore(
+        &self,
+        agent: &Agent,
+        request: &AgentStignoreDiffRequest,
+    ) -> Result<AgentStignoreDiffResponse, AgentError> {
+        let response: AgentStignoreDiffResponse = self
+            .make_request(agent, "history/diff", Method::POST, Some(request))
+            .await?;
+
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
+        }
+    }
+
+    /// Read a category's whole .stignore along with its ETag
+    pub async fn get_stignore(
+        &self,
+        agent: &Agent,
+        category_id: &str,
+    ) -> Result<AgentStignoreContentResponse, AgentError> {
+        let endpoint = format!("categories/{}/stignore", encode_segment(category_id));
+        self.make_request::<(), _>(agent, &endpoint, Method::GET, None)
+            .await
+    }
+
+    /// Replace a category's whole .stignore, unless it no longer has the given ETag
+    pub async fn put_stignore(
+        &self,
+        agent: &Agent,
+        category_id: &str,
+        etag: &str,
+        request: &AgentStignoreWriteRequest,
+    ) -> Result<StignoreWrite, AgentError> {
+        let endpoint = format!("categories/{}/stignore", encode_segment(category_id));
+        let response = self
+            .build_request(agent, &endpoint, Method::PUT)
+            .header("If-Match", etag)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| send_error(agent, e))?;
+
+        let status = response.status();
+        match (
+            status,
+            response.json::<AgentStignoreContentResponse>().await,
+        ) {
+            (status, Ok(body)) if status.is_success() => Ok(StignoreWrite::Written(body)),
+            (reqwest::StatusCode::PRECONDITION_FAILED, Ok(body)) => {
+                Ok(StignoreWrite::Conflict(body))
+            }
+            (_, Ok(body)) => Err(AgentError::OperationFailed(body.message)),
+            (status, Err(e)) => Err(AgentError::InvalidResponse(format!(
+                "HTTP {}: {}",
+                status, e
+            ))),
+        }
+    }
+
+    /// Replace a category's .stignore with a saved revision
+    pub async fn revert_stignore(
+        &self,
+        agent: &Agent,
+        request: &AgentStignoreRevertRequest,
+    ) -> Result<AgentStignoreRevertResponse, AgentError> {
+        let response: AgentStignoreRevertResponse = self
+            .make_request(agent, "history/revert", Method::POST, Some(request))
+            .await?;
+
+        if response.success {
+            Ok(response)
+        } else {
+            Err(AgentError::OperationFailed(response.message))
         }
     }
 }
 
+/// Explains why an agent can't be managed properly, None when it speaks our API version
+pub fn compatibility_warning(api_version: u32, version: &str) -> Option<String> {
+    (api_version != AGENT_API_VERSION).then(|| {
+        format!(
+            "Agent v{} speaks API v{}, this manager (v{}) expects API v{}",
+            version,
+            api_version,
+            env!("CARGO_PKG_VERSION"),
+            AGENT_API_VERSION
+        )
+    })
+}
+
+/// Warning for agents answering 404 on the health endpoint, they are older than it
+pub fn missing_health_warning() -> String {
+    format!(
+        "Agent predates the health endpoint, upgrade it to a version speaking API v{}",
+        AGENT_API_VERSION
+    )
+}
+
 impl Default for AgentClient {
     fn default() -> Self {
         Self::new()
@@ -332,6 +534,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compatibility_warning() {
+        assert_eq!(compatibility_warning(AGENT_API_VERSION, "1.0.0"), None);
+        let warning = compatibility_warning(AGENT_API_VERSION + 1, "9.0.0").unwrap();
+        assert!(warning.contains("Agent v9.0.0"));
+        assert!(warning.contains(&format!("expects API v{}", AGENT_API_VERSION)));
+    }
+
     #[test]
     fn test_format_agent_url_empty_prefix() {
         assert_eq!(
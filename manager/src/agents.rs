@@ -1,13 +1,9 @@
+use crate::agent_client::{AgentClient, AgentError};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use stignore_lib::*;
-
-fn set_copy_count_recursive(item: &mut ItemGroup, count: u8) {
-    item.copy_count = count;
-    for sub_item in &mut item.items {
-        set_copy_count_recursive(sub_item, count);
-    }
-}
+use tokio::task::JoinSet;
 
 pub(crate) fn sort_all_items(items: &mut [ItemGroup]) {
     // Sort items by name
@@ -25,51 +21,119 @@ pub struct CategoryListingResponse {
     pub agent_items: Vec<AgentCategoryListingResponse>,
 }
 
-pub async fn list_categories(
-    agent_client: &crate::agent_client::AgentClient,
-    agents: Vec<Agent>,
-    disabled_agents: &std::collections::HashSet<String>,
-) -> CategoryListingResponse {
-    let mut agent_responses: Vec<AgentCategoryListingResponse> = vec![];
-    let mut consolidated: HashMap<String, ItemGroup> = HashMap::new();
-
-    for agent in agents {
+/// Sends a request to every enabled agent concurrently. Results come back in the same
+/// order as `agents`, None for agents that are disabled or missed the deadline.
+pub(crate) async fn fan_out<T, F, Fut>(
+    agents: &[Agent],
+    disabled_agents: &HashSet<String>,
+    deadline: Duration,
+    request: F,
+) -> Vec<Option<T>>
+where
+    T: Send + 'static,
+    F: Fn(Agent) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    let mut results: Vec<Option<T>> = agents.iter().map(|_| None).collect();
+    let mut pending: HashSet<usize> = HashSet::new();
+
+    let mut set = JoinSet::new();
+    for (index, agent) in agents.iter().enumerate() {
         if disabled_agents.contains(&agent.name) {
-            let empty_response = AgentCategoryListingResponse { items: vec![] };
-            agent_responses.push(empty_response);
             continue;
         }
+        pending.insert(index);
+        let response = request(agent.clone());
+        set.spawn(async move { (index, response.await) });
+    }
 
-        match agent_client.get_categories(&agent).await {
-            Ok(resp) => {
-                agent_responses.push(resp.clone());
-
-                for mut item in resp.items {
-                    // Use simple copy counting for performance - ignore checking disabled for now
-                    set_copy_count_recursive(&mut item, 1);
-                    match consolidated.get(&item.id) {
-                        Some(existing) => {
-                            // Merge with existing item using addition
-                            let merged = existing.clone() + item;
-                            consolidated.insert(merged.id.clone(), merged);
-                        }
-                        None => {
-                            consolidated.insert(item.id.clone(), item.clone());
-                        }
-                    };
-                }
+    let deadline = tokio::time::Instant::now() + deadline;
+    loop {
+        match tokio::time::timeout_at(deadline, set.join_next()).await {
+            Ok(Some(Ok((index, result)))) => {
+                pending.remove(&index);
+                results[index] = Some(result);
+            }
+            Ok(Some(Err(err))) => tracing::error!("Agent request task failed: {}", err),
+            Ok(None) => break,
+            Err(_) => {
+                let mut late: Vec<&str> =
+                    pending.iter().map(|&i| agents[i].name.as_str()).collect();
+                late.sort();
+                tracing::warn!(
+                    "Agents did not respond before the aggregation deadline: {}",
+                    late.join(", ")
+                );
+                set.abort_all();
+                break;
             }
-            Err(e) => {
+        }
+    }
+
+    results
+}
+
+pub async fn list_categories(
+    agent_client: &AgentClient,
+    agents: Vec<Agent>,
+    disabled_agents: &HashSet<String>,
+    deadline: Duration,
+) -> CategoryListingResponse {
+    let results = fan_out(&agents, disabled_agents, deadline, |agent| {
+        let client = agent_client.clone();
+        async move { client.get_categories(&agent).await }
+    })
+    .await;
+
+    let responses = agents
+        .iter()
+        .zip(results)
+        .map(|(agent, result)| match result {
+            Some(Ok(resp)) => (agent, Some(resp)),
+            Some(Err(e)) => {
                 tracing::warn!(
                     "Failed to get categories from agent '{}': {}",
                     agent.name,
                     e
                 );
-                // Create an empty response for the failed agent to maintain consistency
-                let empty_response = AgentCategoryListingResponse { items: vec![] };
-                agent_responses.push(empty_response);
-                // Don't add any items to consolidated for this agent
+                (agent, None)
             }
+            // Disabled, or too slow to answer
+            None => (agent, None),
+        })
+        .collect();
+
+    consolidate_categories(responses)
+}
+
+/// Merges per-agent listings, given in configuration order, into one sorted tree.
+/// Agents without a listing get an empty entry so `agent_items` lines up with the config.
+pub(crate) fn consolidate_categories(
+    responses: Vec<(&Agent, Option<AgentCategoryListingResponse>)>,
+) -> CategoryListingResponse {
+    let mut agent_responses: Vec<AgentCategoryListingResponse> = vec![];
+    let mut consolidated: HashMap<String, ItemGroup> = HashMap::new();
+
+    for (agent, response) in responses {
+        let Some(resp) = response else {
+            agent_responses.push(AgentCategoryListingResponse { items: vec![] });
+            continue;
+        };
+        agent_responses.push(resp.clone());
+
+        for mut item in resp.items {
+            // Ignore status isn't checked here, an agent holding the item counts as a copy
+            item.mark_present_on(&agent.name);
+            match consolidated.get(&item.id) {
+                Some(existing) => {
+                    // Merge with existing item using addition
+                    let merged = existing.clone() + item;
+                    consolidated.insert(merged.id.clone(), merged);
+                }
+                None => {
+                    consolidated.insert(item.id.clone(), item.clone());
+                }
+            };
         }
     }
 
@@ -89,38 +153,49 @@ pub struct ItemInfoResponse {
 }
 
 pub async fn item_info(
-    agent_client: &crate::agent_client::AgentClient,
+    agent_client: &AgentClient,
     agents: Vec<Agent>,
     item_path: Vec<&str>,
-    disabled_agents: &std::collections::HashSet<String>,
-) -> Result<ItemInfoResponse, crate::agent_client::AgentError> {
-    let mut agent_responses: Vec<(Agent, ItemGroup)> = vec![];
-    let mut consolidated: ItemGroup = ItemGroup::empty();
-
+    disabled_agents: &HashSet<String>,
+    deadline: Duration,
+) -> Result<ItemInfoResponse, AgentError> {
     let owned_path: Vec<String> = item_path.iter().map(|v| v.to_string()).collect();
 
-    for agent in agents {
-        if disabled_agents.contains(&agent.name) {
-            let empty_item = ItemGroup::empty();
-            agent_responses.push((agent, empty_item.clone()));
-            consolidated = empty_item + consolidated;
-            continue;
-        }
-
+    let results = fan_out(&agents, disabled_agents, deadline, |agent| {
+        let client = agent_client.clone();
         let body = AgentItemInfoRequest {
             item_path: owned_path.clone(),
         };
+        async move { client.get_item_info(&agent, &body).await }
+    })
+    .await;
+
+    // Disabled, unreachable, too slow or a 404 all show as an empty item
+    let items = agents
+        .into_iter()
+        .zip(results)
+        .map(|(agent, result)| (agent, result.and_then(Result::ok).map(|resp| resp.item)))
+        .collect();
 
-        match agent_client.get_item_info(&agent, &body).await {
-            Ok(resp) => {
-                agent_responses.push((agent, resp.item.clone()));
-                let mut item_with_count = resp.item.clone();
-                let count = if item_with_count.id.is_empty() { 0 } else { 1 };
-                set_copy_count_recursive(&mut item_with_count, count);
+    Ok(consolidate_item_info(items))
+}
+
+/// Merges each agent's copy of an item, given in configuration order
+pub(crate) fn consolidate_item_info(items: Vec<(Agent, Option<ItemGroup>)>) -> ItemInfoResponse {
+    let mut agent_responses: Vec<(Agent, ItemGroup)> = vec![];
+    let mut consolidated: ItemGroup = ItemGroup::empty();
+
+    for (agent, item) in items {
+        match item {
+            Some(item) => {
+                let mut item_with_count = item.clone();
+                if !item_with_count.id.is_empty() {
+                    item_with_count.mark_present_on(&agent.name);
+                }
+                agent_responses.push((agent, item));
                 consolidated = item_with_count + consolidated;
             }
-            Err(_) => {
-                // Handle error by creating an empty response (similar to 404 handling)
+            None => {
                 let empty_item = ItemGroup::empty();
                 agent_responses.push((agent, empty_item.clone()));
                 consolidated = empty_item + consolidated;
@@ -128,10 +203,25 @@ pub async fn item_info(
         }
     }
 
-    Ok(ItemInfoResponse {
+    ItemInfoResponse {
         agent_items: agent_responses,
         item: consolidated,
-    })
+    }
+}
+
+/// Walks an agent's category listing the same way the agent resolves `/items` requests:
+/// the first component is the category id, the rest are names, and leaf directories
+/// aren't descended into
+pub(crate) fn find_item(categories: &[ItemGroup], item_path: &[&str]) -> Option<ItemGroup> {
+    let (category_id, rest) = item_path.split_first()?;
+    let mut current = categories.iter().find(|c| c.id == *category_id)?;
+    for name in rest {
+        if current.leaf {
+            return None;
+        }
+        current = current.items.iter().find(|child| child.name == *name)?;
+    }
+    Some(current.clone())
 }
 
 #[cfg(test)]
@@ -139,7 +229,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_set_copy_count_recursive() {
+    fn test_mark_present_on_recursive() {
         let child = ItemGroup {
             id: "child".to_string(),
             name: "Child".to_string(),
@@ -160,10 +250,11 @@ mod tests {
             ..Default::default()
         };
 
-        set_copy_count_recursive(&mut parent, 3);
+        parent.mark_present_on("agent1");
 
-        assert_eq!(parent.copy_count, 3);
-        assert_eq!(parent.items[0].copy_count, 3);
+        assert_eq!(parent.copy_count, 1);
+        assert_eq!(parent.items[0].copy_count, 1);
+        assert!(parent.items[0].present_on.contains("agent1"));
     }
 
     #[test]
@@ -269,6 +360,7 @@ mod tests {
             name: "Test Agent".to_string(),
             hostname: "localhost:3000".to_string(),
             api_key: "test-key".to_string(),
+            timeout_seconds: None,
         };
 
         let item = ItemGroup {
@@ -386,13 +478,14 @@ mod tests {
             name: "agent1".to_string(),
             hostname: "localhost:3001".to_string(),
             api_key: "key1".to_string(),
+            timeout_seconds: None,
         };
 
         // Since we can't easily inject the mock client into the real function,
         // let's test the consolidation logic directly
         let mut consolidated: HashMap<String, ItemGroup> = HashMap::new();
         let mut item_with_count = test_item.clone();
-        set_copy_count_recursive(&mut item_with_count, 1);
+        item_with_count.mark_present_on("agent1");
         consolidated.insert(item_with_count.id.clone(), item_with_count);
 
         let mut sorted_items: Vec<ItemGroup> = consolidated.values().cloned().collect();
@@ -420,12 +513,12 @@ mod tests {
 
         // First agent's item
         let mut item1 = test_item.clone();
-        set_copy_count_recursive(&mut item1, 1);
+        item1.mark_present_on("agent1");
         consolidated.insert(item1.id.clone(), item1);
 
         // Second agent's item (same ID, should merge)
         let mut item2 = test_item.clone();
-        set_copy_count_recursive(&mut item2, 1);
+        item2.mark_present_on("agent2");
         match consolidated.get(&item2.id) {
             Some(existing) => {
                 let merged = existing.clone() + item2;
@@ -439,6 +532,10 @@ mod tests {
         let sorted_items: Vec<ItemGroup> = consolidated.values().cloned().collect();
         assert_eq!(sorted_items.len(), 1);
         assert_eq!(sorted_items[0].copy_count, 2); // Both agents contributed
+        assert_eq!(
+            sorted_items[0].present_on.iter().collect::<Vec<_>>(),
+            vec!["agent1", "agent2"]
+        );
     }
 
     #[tokio::test]
@@ -467,12 +564,12 @@ mod tests {
 
         // First agent's item
         let mut item1_with_count = item1.clone();
-        set_copy_count_recursive(&mut item1_with_count, 1);
+        item1_with_count.mark_present_on("agent1");
         consolidated.insert(item1_with_count.id.clone(), item1_with_count);
 
         // Second agent's different item
         let mut item2_with_count = item2.clone();
-        set_copy_count_recursive(&mut item2_with_count, 1);
+        item2_with_count.mark_present_on("agent2");
         consolidated.insert(item2_with_count.id.clone(), item2_with_count);
 
         let mut sorted_items: Vec<ItemGroup> = consolidated.values().cloned().collect();
@@ -0,0 +1,337 @@
+use crate::agent_client::{AgentClient, AgentError};
+use crate::agents::{self, CategoryListingResponse, ItemInfoResponse};
+use crate::disabled::DisabledAgents;
+use crate::registry::AgentRegistry;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use stignore_lib::*;
+
+/// One agent's category listing as of its last fetch
+#[derive(Debug, Clone)]
+pub struct AgentSnapshot {
+    pub result: Result<AgentCategoryListingResponse, Arc<AgentError>>,
+    pub latency_ms: u128,
+    pub fetched_at: SystemTime,
+}
+
+/// Last known category tree of every agent, shared by all pages so browsing doesn't
+/// fan out to the agents on every click. Entries are refreshed in the background and
+/// dropped after the manager changes something on an agent.
+#[derive(Clone, Default)]
+pub struct TreeCache {
+    snapshots: Arc<RwLock<HashMap<String, AgentSnapshot>>>,
+    /// Bumped by `invalidate`, a fetch that started before a bump is stale and dropped.
+    /// Only changed while holding the `snapshots` write lock.
+    generations: Arc<RwLock<HashMap<String, u64>>>,
+    /// Serialises fetches so concurrent page loads don't all hit a cold agent
+    fetch_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl TreeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refetches the given agents regardless of what is cached
+    pub async fn refresh(&self, client: &AgentClient, agents: &[Agent], deadline: Duration) {
+        let _guard = self.fetch_lock.lock().await;
+        self.fetch(client, agents, deadline).await;
+    }
+
+    /// Forgets an agent's tree so the next read fetches it again
+    pub fn invalidate(&self, agent_name: &str) {
+        let mut snapshots = self.snapshots.write().unwrap();
+        *self
+            .generations
+            .write()
+            .unwrap()
+            .entry(agent_name.to_string())
+            .or_default() += 1;
+        snapshots.remove(agent_name);
+    }
+
+    fn generation(&self, agent_name: &str) -> u64 {
+        self.generations
+            .read()
+            .unwrap()
+            .get(agent_name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns each agent's snapshot in configuration order, fetching any enabled agent
+    /// that isn't cached yet. Disabled agents, and agents that never answered, give None.
+    pub async fn snapshots(
+        &self,
+        client: &AgentClient,
+        agents: &[Agent],
+        disabled_agents: &HashSet<String>,
+        deadline: Duration,
+    ) -> Vec<Option<AgentSnapshot>> {
+        if !self.missing(agents, disabled_agents).is_empty() {
+            let _guard = self.fetch_lock.lock().await;
+            // Another request may have fetched them while we waited
+            let missing = self.missing(agents, disabled_agents);
+            if !missing.is_empty() {
+                self.fetch(client, &missing, deadline).await;
+            }
+        }
+
+        let snapshots = self.snapshots.read().unwrap();
+        agents
+            .iter()
+            .map(|agent| {
+                if disabled_agents.contains(&agent.name) {
+                    None
+                } else {
+                    snapshots.get(&agent.name).cloned()
+                }
+            })
+            .collect()
+    }
+
+    /// Cached equivalent of `agents::list_categories`
+    pub async fn list_categories(
+        &self,
+        client: &AgentClient,
+        agents: Vec<Agent>,
+        disabled_agents: &HashSet<String>,
+        deadline: Duration,
+    ) -> CategoryListingResponse {
+        let snapshots = self
+            .snapshots(client, &agents, disabled_agents, deadline)
+            .await;
+        let responses = agents
+            .iter()
+            .zip(snapshots)
+            .map(|(agent, snapshot)| (agent, snapshot.and_then(|s| s.result.ok())))
+            .collect();
+
+        agents::consolidate_categories(responses)
+    }
+
+    /// Cached equivalent of `agents::item_info`, items are looked up in each agent's tree
+    pub async fn item_info(
+        &self,
+        client: &AgentClient,
+        agents: Vec<Agent>,
+        item_path: Vec<&str>,
+        disabled_agents: &HashSet<String>,
+        deadline: Duration,
+    ) -> Result<ItemInfoResponse, AgentError> {
+        let snapshots = self
+            .snapshots(client, &agents, disabled_agents, deadline)
+            .await;
+
+        let items = agents
+            .into_iter()
+            .zip(snapshots)
+            .map(|(agent, snapshot)| {
+                let item = snapshot
+                    .and_then(|s| s.result.ok())
+                    .and_then(|listing| agents::find_item(&listing.items, &item_path));
+                (agent, item)
+            })
+            .collect();
+
+        Ok(agents::consolidate_item_info(items))
+    }
+
+    /// When the oldest tree of an enabled agent was fetched, None if nothing is cached
+    pub fn last_refreshed(
+        &self,
+        agents: &[Agent],
+        disabled_agents: &HashSet<String>,
+    ) -> Option<SystemTime> {
+        let snapshots = self.snapshots.read().unwrap();
+        agents
+            .iter()
+            .filter(|agent| !disabled_agents.contains(&agent.name))
+            .filter_map(|agent| snapshots.get(&agent.name))
+            .map(|snapshot| snapshot.fetched_at)
+            .min()
+    }
+
+    /// Refetches every enabled agent on an interval for as long as the process runs
+    pub fn spawn_refresh_task(
+        &self,
+        client: AgentClient,
+        agents: AgentRegistry,
+        disabled_agents: DisabledAgents,
+        deadline: Duration,
+        interval: Duration,
+    ) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let enabled: Vec<Agent> = agents
+                    .list()
+                    .into_iter()
+                    .filter(|agent| !disabled_agents.contains(&agent.name))
+                    .collect();
+                cache.refresh(&client, &enabled, deadline).await;
+                tracing::debug!("Refreshed cached trees for {} agents", enabled.len());
+            }
+        });
+    }
+
+    fn missing(&self, agents: &[Agent], disabled_agents: &HashSet<String>) -> Vec<Agent> {
+        let snapshots = self.snapshots.read().unwrap();
+        agents
+            .iter()
+            .filter(|agent| !disabled_agents.contains(&agent.name))
+            .filter(|agent| !snapshots.contains_key(&agent.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Callers must hold `fetch_lock`
+    async fn fetch(&self, client: &AgentClient, agents: &[Agent], deadline: Duration) {
+        let started: Vec<u64> = agents
+            .iter()
+            .map(|agent| self.generation(&agent.name))
+            .collect();
+        let results = agents::fan_out(agents, &HashSet::new(), deadline, |agent| {
+            let client = client.clone();
+            async move {
+                let start = Instant::now();
+                let result = client.get_categories(&agent).await;
+                (result, start.elapsed())
+            }
+        })
+        .await;
+
+        let mut snapshots = self.snapshots.write().unwrap();
+        for ((agent, result), started) in agents.iter().zip(results).zip(started) {
+            // Agents that missed the deadline keep whatever was cached before
+            let Some((result, latency)) = result else {
+                continue;
+            };
+            // Changed by the manager while we were fetching, the tree may predate the change
+            if self.generation(&agent.name) != started {
+                continue;
+            }
+            if let Err(err) = &result {
+                tracing::warn!(
+                    "Failed to get categories from agent '{}': {}",
+                    agent.name,
+                    err
+                );
+            }
+            snapshots.insert(
+                agent.name.clone(),
+                AgentSnapshot {
+                    result: result.map_err(Arc::new),
+                    latency_ms: latency.as_millis(),
+                    fetched_at: SystemTime::now(),
+                },
+            );
+        }
+    }
+}
+
+/// Describes how long ago the cache was refreshed, for display
+pub fn format_age(refreshed: Option<SystemTime>) -> String {
+    let Some(refreshed) = refreshed else {
+        return "never".to_string();
+    };
+    let seconds = refreshed.elapsed().unwrap_or_default().as_secs();
+    match seconds {
+        0..5 => "just now".to_string(),
+        5..60 => format!("{}s ago", seconds),
+        60..3600 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        let now = SystemTime::now();
+        assert_eq!(format_age(None), "never");
+        assert_eq!(format_age(Some(now)), "just now");
+        assert_eq!(format_age(Some(now - Duration::from_secs(42))), "42s ago");
+        assert_eq!(format_age(Some(now - Duration::from_secs(300))), "5m ago");
+        assert_eq!(format_age(Some(now - Duration::from_secs(7300))), "2h ago");
+    }
+
+    #[test]
+    fn test_invalidate_and_last_refreshed() {
+        let cache = TreeCache::new();
+        let agent = Agent {
+            name: "agent-1".to_string(),
+            hostname: "localhost:1".to_string(),
+            api_key: String::new(),
+            timeout_seconds: None,
+        };
+        let agents = vec![agent.clone()];
+        assert!(cache.last_refreshed(&agents, &HashSet::new()).is_none());
+
+        let fetched_at = SystemTime::now() - Duration::from_secs(30);
+        cache.snapshots.write().unwrap().insert(
+            agent.name.clone(),
+            AgentSnapshot {
+                result: Ok(AgentCategoryListingResponse { items: vec![] }),
+                latency_ms: 1,
+                fetched_at,
+            },
+        );
+        assert_eq!(
+            cache.last_refreshed(&agents, &HashSet::new()),
+            Some(fetched_at)
+        );
+        let disabled = HashSet::from([agent.name.clone()]);
+        assert!(cache.last_refreshed(&agents, &disabled).is_none());
+
+        cache.invalidate(&agent.name);
+        assert!(cache.missing(&agents, &HashSet::new()).len() == 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_started_before_invalidate_is_dropped() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/categories"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(AgentCategoryListingResponse { items: vec![] })
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let cache = TreeCache::new();
+        let agents = vec![Agent {
+            name: "agent-1".to_string(),
+            hostname: mock_server.uri().trim_start_matches("http://").to_string(),
+            api_key: String::new(),
+            timeout_seconds: None,
+        }];
+        let client = AgentClient::new();
+
+        let refresh = {
+            let cache = cache.clone();
+            let agents = agents.clone();
+            tokio::spawn(async move {
+                cache
+                    .refresh(&client, &agents, Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cache.invalidate("agent-1");
+        refresh.await.unwrap();
+
+        assert_eq!(cache.missing(&agents, &HashSet::new()).len(), 1);
+    }
+}
@@ -6,7 +6,7 @@ use axum::{
     routing::{get, post},
 };
 
-use crate::agents;
+use crate::agent_client::{self, AgentError};
 use crate::auth::{self, AuthUser};
 use axum_template::{Key, RenderHtml};
 use serde::{Deserialize, Serialize};
@@ -35,6 +35,11 @@ pub fn router() -> Router<AppState> {
         .route("/infopanel.html", post(infopanel))
         .route("/agent-modal.html", post(agent_modal))
         .route("/agents/toggle", post(toggle_agent))
+        .route("/agents/add", post(add_agent))
+        .route("/agents/edit", post(edit_agent))
+        .route("/agents/remove", post(remove_agent))
+        .route("/agents/approve", post(approve_agent))
+        .route("/agents/reject", post(reject_agent))
         .route("/agents-table.html", get(agents_table))
         .route("/agent-status-pill.html", get(agent_status_pill))
         .route("/ignore", post(ignore_item))
@@ -44,6 +49,15 @@ pub fn router() -> Router<AppState> {
         .route("/bulk-ignore", post(bulk_ignore_item))
         .route("/bulk-unignore", post(bulk_unignore_item))
         .route("/bulk-delete", post(bulk_delete_item))
+        .route("/trash.html", post(trash_list))
+        .route("/trash/restore", post(restore_trash_item))
+        .route("/trash/purge", post(purge_trash))
+        .route("/history.html", post(stignore_history))
+        .route("/history/diff.html", post(stignore_diff))
+        .route("/history/revert", post(revert_stignore))
+        .route("/stignore-editor.html", post(stignore_editor))
+        .route("/stignore/save", post(save_stignore))
+        .route("/refresh", post(refresh_agent_data))
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -51,10 +65,20 @@ struct ItemGroupWithFlags {
     pub id: String,
     pub safe_id: String,
     pub name: String,
-    pub size_kb: u64,
+    pub size_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
     pub items: Vec<ItemGroupWithFlags>,
     pub leaf: bool,
-    pub copy_count: u8,
+    /// A loose file next to directories rather than a directory
+    pub is_file: bool,
+    pub copy_count: u32,
+    pub present_on: Vec<String>,
+    /// Agents holding a copy that their .stignore excludes from syncing
+    pub ignored_on: Vec<String>,
+    /// Enabled agents without a copy
+    pub missing_on: Vec<String>,
+    pub effective_copies: u32,
     pub has_insufficient_copies: bool,
     pub has_conflicts: bool,
     pub conflict_count: u32,
@@ -69,10 +93,17 @@ impl From<&ItemGroup> for ItemGroupWithFlags {
             id: item.id.clone(),
             safe_id: sanitize_id(&item.id),
             name: item.name.clone(),
-            size_kb: item.size_kb,
+            size_bytes: item.bytes(),
+            file_count: item.file_count,
+            dir_count: item.dir_count,
             items: vec![], // Will be filled separately
             leaf: item.leaf,
+            is_file: !item.is_dir(),
             copy_count: item.copy_count,
+            present_on: item.present_on.iter().cloned().collect(),
+            ignored_on: item.ignored_on.iter().cloned().collect(),
+            missing_on: vec![], // Will be set separately
+            effective_copies: item.effective_copy_count(),
             has_insufficient_copies: false, // Will be set separately
             has_conflicts: item.has_conflicts,
             conflict_count: item.conflict_count,
@@ -83,36 +114,70 @@ impl From<&ItemGroup> for ItemGroupWithFlags {
     }
 }
 
-fn convert_item_with_flags(item: &ItemGroup, minimum_copies: u8) -> ItemGroupWithFlags {
+fn convert_item_with_flags(
+    item: &ItemGroup,
+    minimum_copies: u8,
+    agent_names: &[String],
+) -> ItemGroupWithFlags {
     let mut converted = ItemGroupWithFlags::from(item);
+    converted.missing_on = missing_agents(item, agent_names);
     converted.has_insufficient_copies = item.has_insufficient_copies(minimum_copies);
     converted.items = item
         .items
         .iter()
-        .map(|child| convert_item_with_flags(child, minimum_copies))
+        .map(|child| convert_item_with_flags(child, minimum_copies, agent_names))
         .collect();
     converted
 }
 
+/// Names of the enabled agents, in configuration order
+fn enabled_agent_names(agents: &[Agent], disabled_agents: &HashSet<String>) -> Vec<String> {
+    agents
+        .iter()
+        .filter(|agent| !disabled_agents.contains(&agent.name))
+        .map(|agent| agent.name.clone())
+        .collect()
+}
+
+fn missing_agents(item: &ItemGroup, agent_names: &[String]) -> Vec<String> {
+    agent_names
+        .iter()
+        .filter(|name| !item.present_on.contains(*name))
+        .cloned()
+        .collect()
+}
+
 async fn itemlist(State(state): State<AppState>, auth_user: AuthUser) -> impl IntoResponse {
     let mut context = state.context.clone();
     auth::inject_auth_context(&mut context, &auth_user);
 
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
-    let response =
-        agents::list_categories(&state.agent_client, state.config.agents, &disabled_agents).await;
-    // Items are already sorted by agents::list_categories
+    let disabled_agents = state.disabled_agents.names();
+    let deadline = state.config.manager.aggregation_deadline();
+    let response = state
+        .tree_cache
+        .list_categories(
+            &state.agent_client,
+            state.agents.list(),
+            &disabled_agents,
+            deadline,
+        )
+        .await;
+    // Items are already sorted by agents::consolidate_categories
 
     // Convert to ItemGroupWithFlags with has_insufficient_copies field
+    let agent_names = enabled_agent_names(&state.agents.list(), &disabled_agents);
     let items_with_flags: Vec<ItemGroupWithFlags> = response
         .items
         .iter()
-        .map(|item| convert_item_with_flags(item, state.config.manager.minimum_copies))
+        .map(|item| {
+            convert_item_with_flags(item, state.config.manager.minimum_copies, &agent_names)
+        })
         .collect();
 
     context.insert("items", &items_with_flags);
 
     context.insert("minimum_copies", &state.config.manager.minimum_copies);
+    crate::pages::insert_cache_age(&mut context, &state);
 
     RenderHtml(
         Key("components/itemlist.html".to_string()),
@@ -136,30 +201,202 @@ struct AgentModalRequest {
 struct IgnoreItemRequest {
     agent_name: String,
     item_path: Vec<String>,
+    /// Go ahead even if fewer than `minimum_copies` synced copies would remain
+    #[serde(default)]
+    override_minimum_copies: bool,
 }
 
 #[derive(Deserialize, Debug)]
 struct DeleteItemRequest {
     agent_name: String,
     item_path: Vec<String>,
+    /// Go ahead even if fewer than `minimum_copies` synced copies would remain
+    #[serde(default)]
+    override_minimum_copies: bool,
 }
 
 #[derive(Serialize, Debug)]
 struct IgnoreItemResponse {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimum_copies_override: Option<ReplicationOverride>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UnignoreItemRequest {
+    agent_name: String,
+    item_path: Vec<String>,
+    /// Also remove a matching line written by hand, the agent refuses to otherwise
+    #[serde(default)]
+    include_user_entries: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct UnignoreItemResponse {
+    success: bool,
+    message: String,
+    /// The agent refused because the matching line was written by hand
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    user_entry: bool,
 }
 
 #[derive(Serialize, Debug)]
 struct DeleteItemResponse {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimum_copies_override: Option<ReplicationOverride>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TrashListRequest {
+    agent_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TrashItemRequest {
+    agent_name: String,
+    #[serde(default)]
+    id: Option<String>,
+    /// Purges the whole trash, only honoured when no ID is given
+    #[serde(default)]
+    all: bool,
+}
+
+/// A trashed item as shown in the trash modal
+#[derive(Serialize, Debug)]
+struct TrashItemView {
+    id: String,
+    category_id: String,
+    path: String,
+    is_dir: bool,
+    size_kb: u64,
+    days_left: u64,
+}
+
+impl From<&TrashEntry> for TrashItemView {
+    fn from(entry: &TrashEntry) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            id: entry.id.clone(),
+            category_id: entry.category_id.clone(),
+            path: entry.folder_path.join(" / "),
+            is_dir: entry.is_dir,
+            size_kb: entry.size_kb,
+            days_left: entry.expires_at.saturating_sub(now).div_ceil(24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct TrashItemResponse {
+    success: bool,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryRequest {
+    agent_name: String,
+    /// Shows the agent's first category when unset
+    #[serde(default)]
+    category_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryDiffRequest {
+    agent_name: String,
+    category_id: String,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryRevertRequest {
+    agent_name: String,
+    category_id: String,
+    id: String,
+}
+
+#[derive(Serialize, Debug)]
+struct HistoryRevertResponse {
+    success: bool,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct StignoreEditorRequest {
+    agent_name: String,
+    category_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct StignoreSaveRequest {
+    agent_name: String,
+    category_id: String,
+    /// ETag of the content the editor was opened with
+    etag: String,
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+struct StignoreSaveResponse {
+    success: bool,
+    message: String,
+    /// Set when the file changed on the agent since the editor was opened
+    conflict: bool,
+    etag: String,
+    /// The agent's current content when there was a conflict
+    content: String,
+}
+
+/// A .stignore revision as shown in the history modal
+#[derive(Serialize, Debug)]
+struct RevisionView {
+    id: String,
+    change: StignoreChange,
+    detail: String,
+    line_count: usize,
+    age: String,
+    /// The revision before this one, what its changes are compared against. Empty for
+    /// the oldest, which is compared against an empty file.
+    previous_id: String,
+}
+
+impl RevisionView {
+    fn list(revisions: &[StignoreRevision]) -> Vec<RevisionView> {
+        revisions
+            .iter()
+            .enumerate()
+            .map(|(index, revision)| RevisionView {
+                id: revision.id.clone(),
+                change: revision.change,
+                detail: revision.detail.clone(),
+                line_count: revision.line_count,
+                age: crate::cache::format_age(Some(
+                    std::time::UNIX_EPOCH + std::time::Duration::from_secs(revision.created_at),
+                )),
+                previous_id: revisions
+                    .get(index + 1)
+                    .map(|previous| previous.id.clone())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Deserialize, Debug)]
 struct ToggleAgentRequest {
     agent_name: String,
     enabled: bool,
+    /// Why the agent is being disabled, shown on the agents overview
+    #[serde(default)]
+    reason: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -170,19 +407,54 @@ struct ToggleAgentResponse {
     enabled: bool,
 }
 
+#[derive(Deserialize, Debug)]
+struct AddAgentRequest {
+    agent_name: String,
+    hostname: String,
+    api_key: String,
+    #[serde(default)]
+    timeout_seconds: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EditAgentRequest {
+    agent_name: String,
+    hostname: String,
+    /// Left out or blank keeps the current key
+    #[serde(default)]
+    api_key: Option<String>,
+    /// Left out falls back to the manager wide timeout
+    #[serde(default)]
+    timeout_seconds: Option<u64>,
+}
+
+/// Names a single agent, used to remove, approve or reject it
+#[derive(Deserialize, Debug)]
+struct AgentNameRequest {
+    agent_name: String,
+}
+
+#[derive(Serialize, Debug)]
+struct AgentRegistryResponse {
+    success: bool,
+    message: String,
+    agent_name: String,
+}
+
 #[derive(Serialize, Debug)]
 struct AgentItemWithStatus {
     agent: Agent,
     item: ItemGroup,
     sync_status: String,
     ignored: bool,
+    matched_rule: Option<MatchedIgnoreRule>,
 }
 
 #[derive(Serialize, Debug)]
 struct MergedItem {
     name: String,
     present: bool,
-    size_kb: u64,
+    size_bytes: u64,
     items: usize,
     is_partial: bool,
 }
@@ -207,7 +479,7 @@ async fn check_ignored_status_bulk(
     agent_client: &crate::agent_client::AgentClient,
     agent_items: &[(Agent, ItemGroup)],
     item_path: &[String],
-) -> std::collections::HashMap<String, bool> {
+) -> std::collections::HashMap<String, AgentIgnoreStatusResponse> {
     let mut results = std::collections::HashMap::new();
 
     // Filter out empty strings from item_path
@@ -241,20 +513,14 @@ async fn check_ignored_status_bulk(
             }],
         };
 
-        match agent_client
+        // Agents that fail to answer are left out and treated as not ignored
+        if let Ok(bulk_response) = agent_client
             .check_ignore_status_bulk(agent, &bulk_request)
             .await
         {
-            Ok(bulk_response) => {
-                // For this simple case, we only sent one item so take the first result
-                if let Some(first_result) = bulk_response.items.first() {
-                    results.insert(agent.name.clone(), first_result.ignored);
-                } else {
-                    results.insert(agent.name.clone(), false);
-                }
-            }
-            Err(_) => {
-                results.insert(agent.name.clone(), false);
+            // For this simple case, we only sent one item so take the first result
+            if let Some(first_result) = bulk_response.items.into_iter().next() {
+                results.insert(agent.name.clone(), first_result);
             }
         }
     }
@@ -282,7 +548,7 @@ async fn calculate_sync_status(
     for (agent, item_group) in agent_items {
         let agent_item_ids = collect_all_item_ids(item_group);
 
-        let sync_status = if item_group.size_kb == 0 {
+        let sync_status = if item_group.bytes() == 0 {
             "Missing".to_string()
         } else if agent_item_ids == all_item_ids {
             "In Sync".to_string()
@@ -292,17 +558,18 @@ async fn calculate_sync_status(
             "Partial".to_string()
         };
 
-        // Get the ignore status from our bulk results
-        let ignored = ignore_status_results
-            .get(&agent.name)
-            .copied()
-            .unwrap_or(false);
+        // Get the ignore status from our bulk results, unknown counts as not ignored
+        let (ignored, matched_rule) = match ignore_status_results.get(&agent.name) {
+            Some(status) => (status.ignored, status.matched_rule.clone()),
+            None => (false, None),
+        };
 
         result.push(AgentItemWithStatus {
             agent: agent.clone(),
             item: item_group.clone(),
             sync_status,
             ignored,
+            matched_rule,
         });
     }
 
@@ -324,15 +591,20 @@ async fn infopanel(
         .map(AsRef::as_ref)
         .collect();
 
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
+    let disabled_agents = state.disabled_agents.names();
+    let agent_names = enabled_agent_names(&state.agents.list(), &disabled_agents);
 
-    match agents::item_info(
-        &state.agent_client,
-        state.config.agents,
-        item_path,
-        &disabled_agents,
-    )
-    .await
+    let deadline = state.config.manager.aggregation_deadline();
+    match state
+        .tree_cache
+        .item_info(
+            &state.agent_client,
+            state.agents.list(),
+            item_path,
+            &disabled_agents,
+            deadline,
+        )
+        .await
     {
         Ok(response) => {
             let agent_items_with_status = calculate_sync_status(
@@ -351,6 +623,7 @@ async fn infopanel(
                 .collect();
 
             context.insert("item", &response.item);
+            context.insert("missing_on", &missing_agents(&response.item, &agent_names));
             context.insert("agent_items", &agent_items_with_status);
             context.insert("parent_names", &filtered_item_path);
             context.insert("item_path", &payload.item_path);
@@ -386,15 +659,19 @@ async fn agent_modal(
         .map(AsRef::as_ref)
         .collect();
 
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
+    let disabled_agents = state.disabled_agents.names();
 
-    match agents::item_info(
-        &state.agent_client,
-        state.config.agents,
-        item_path_parts.clone(),
-        &disabled_agents,
-    )
-    .await
+    let deadline = state.config.manager.aggregation_deadline();
+    match state
+        .tree_cache
+        .item_info(
+            &state.agent_client,
+            state.agents.list(),
+            item_path_parts.clone(),
+            &disabled_agents,
+            deadline,
+        )
+        .await
     {
         Ok(response) => {
             // Calculate sync status for all agents first
@@ -420,8 +697,8 @@ async fn agent_modal(
                         all_item_ids.insert(item.name.clone());
                         max_sizes
                             .entry(item.name.clone())
-                            .and_modify(|max| *max = (*max).max(item.size_kb))
-                            .or_insert(item.size_kb);
+                            .and_modify(|max| *max = (*max).max(item.bytes()))
+                            .or_insert(item.bytes());
                     }
                 }
 
@@ -434,7 +711,7 @@ async fn agent_modal(
                         .iter()
                         .find(|i| i.name == item_name);
 
-                    let current_size = current_agent_item.map(|i| i.size_kb).unwrap_or(0);
+                    let current_size = current_agent_item.map(|i| i.bytes()).unwrap_or(0);
                     let max_size = max_sizes.get(&item_name).unwrap_or(&0);
                     let is_partial =
                         current_agent_item.is_some() && current_size < *max_size && *max_size > 0;
@@ -442,7 +719,7 @@ async fn agent_modal(
                     merged_items.push(MergedItem {
                         name: item_name.to_string(),
                         present: current_agent_item.is_some(),
-                        size_kb: current_size,
+                        size_bytes: current_size,
                         items: current_agent_item.map(|i| i.items.len()).unwrap_or(0),
                         is_partial,
                     });
@@ -474,6 +751,186 @@ async fn agent_modal(
     )
 }
 
+/// Returned instead of forwarding a request that would leave too few synced copies
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplicationGuardResponse {
+    pub success: bool,
+    pub message: String,
+    pub below_minimum_copies: bool,
+    /// Agents that would still hold a synced copy afterwards
+    pub remaining_on: Vec<String>,
+    pub minimum_copies: u8,
+}
+
+/// An Admin going ahead although too few synced copies would remain
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplicationOverride {
+    pub overridden_by: String,
+    /// Why the action would have been refused
+    pub reason: String,
+}
+
+/// Checks that ignoring or deleting an item on `agent_names` leaves at least
+/// `minimum_copies` synced copies of it and of everything inside it. Copies already
+/// ignored somewhere, or on agents that are disabled or didn't answer, don't count.
+/// Actions that don't remove a synced copy are always allowed, even when the item is
+/// already under-replicated.
+///
+/// The item is asked for fresh rather than read from the tree cache, a stale or partial
+/// snapshot could hide the copy being removed. Targets that can't say whether they hold
+/// the item are refused.
+async fn check_replication(
+    state: &AppState,
+    item_path: &[String],
+    agent_names: &[String],
+) -> Result<(), ReplicationGuardResponse> {
+    let minimum_copies = state.config.manager.minimum_copies;
+    if minimum_copies == 0 {
+        return Ok(());
+    }
+    // The targets are asked even when disabled, their copy is the one at stake
+    let skipped: HashSet<String> = state
+        .disabled_agents
+        .names()
+        .into_iter()
+        .filter(|name| !agent_names.contains(name))
+        .collect();
+    let agents = state.agents.list();
+    let request = AgentItemInfoRequest {
+        item_path: item_path.to_vec(),
+    };
+    let results = crate::agents::fan_out(
+        &agents,
+        &skipped,
+        state.config.manager.aggregation_deadline(),
+        |agent| {
+            let client = state.agent_client.clone();
+            let request = request.clone();
+            async move { client.get_item_info(&agent, &request).await }
+        },
+    )
+    .await;
+
+    let mut items = Vec::with_capacity(agents.len());
+    for (agent, result) in agents.into_iter().zip(results) {
+        let item = match result {
+            Some(Ok(response)) => Some(response.item),
+            Some(Err(AgentError::NotFound(_))) => None,
+            unknown if agent_names.contains(&agent.name) => {
+                let reason = match unknown {
+                    Some(Err(err)) => err.to_string(),
+                    _ => "no answer in time".to_string(),
+                };
+                return Err(ReplicationGuardResponse {
+                    success: false,
+                    message: format!(
+                        "Refused: couldn't tell whether {} holds a synced copy ({}). An Admin can override this.",
+                        agent.name, reason
+                    ),
+                    below_minimum_copies: true,
+                    remaining_on: vec![],
+                    minimum_copies,
+                });
+            }
+            _ => None,
+        };
+        items.push((agent, item));
+    }
+    let response = crate::agents::consolidate_item_info(items);
+
+    let Some((inner_path, remaining_on)) =
+        find_shortfall(&response.item, agent_names, minimum_copies)
+    else {
+        return Ok(());
+    };
+
+    let remaining = if remaining_on.is_empty() {
+        "no synced copies would remain".to_string()
+    } else {
+        format!(
+            "only {} synced {} would remain ({})",
+            remaining_on.len(),
+            if remaining_on.len() == 1 {
+                "copy"
+            } else {
+                "copies"
+            },
+            remaining_on.join(", ")
+        )
+    };
+    let subject = if inner_path.is_empty() {
+        String::new()
+    } else {
+        format!(" of '{}'", inner_path.join("/"))
+    };
+    Err(ReplicationGuardResponse {
+        success: false,
+        message: format!(
+            "Refused: {}{}, below the minimum of {}. An Admin can override this.",
+            remaining, subject, minimum_copies
+        ),
+        below_minimum_copies: true,
+        remaining_on,
+        minimum_copies,
+    })
+}
+
+/// First item, the given one before its children, that would lose a synced copy and
+/// drop below the minimum. Returns its path below `item` and the agents still syncing it.
+fn find_shortfall(
+    item: &ItemGroup,
+    agent_names: &[String],
+    minimum_copies: u8,
+) -> Option<(Vec<String>, Vec<String>)> {
+    let synced: Vec<&String> = item.present_on.difference(&item.ignored_on).collect();
+    let removes_a_copy = synced.iter().any(|name| agent_names.contains(name));
+    let remaining_on: Vec<String> = synced
+        .into_iter()
+        .filter(|name| !agent_names.contains(name))
+        .cloned()
+        .collect();
+
+    if removes_a_copy && remaining_on.len() < usize::from(minimum_copies) {
+        return Some((vec![], remaining_on));
+    }
+
+    item.items.iter().find_map(|child| {
+        let (mut path, remaining_on) = find_shortfall(child, agent_names, minimum_copies)?;
+        path.insert(0, child.name.clone());
+        Some((path, remaining_on))
+    })
+}
+
+/// Runs the replication check unless overridden. Overrides are logged and returned
+/// with who made them, so the response of the action records them.
+async fn enforce_replication(
+    state: &AppState,
+    auth_user: &AuthUser,
+    action: &str,
+    item_path: &[String],
+    agent_names: &[String],
+    override_minimum_copies: bool,
+) -> Result<Option<ReplicationOverride>, axum::response::Response> {
+    match check_replication(state, item_path, agent_names).await {
+        Ok(()) => Ok(None),
+        Err(refusal) if override_minimum_copies => {
+            tracing::warn!(
+                user = %auth_user.username,
+                "Minimum copies overridden to {} '{}' on {}: {}",
+                action,
+                item_path.join("/"),
+                agent_names.join(", "),
+                refusal.message
+            );
+            Ok(Some(ReplicationOverride {
+                overridden_by: auth_user.username.clone(),
+                reason: refusal.message,
+            }))
+        }
+        Err(refusal) => Err((StatusCode::CONFLICT, Json(refusal)).into_response()),
+    }
+}
+
 async fn ignore_item(
     State(state): State<AppState>,
     auth_user: AuthUser,
@@ -484,22 +941,19 @@ async fn ignore_item(
             StatusCode::FORBIDDEN,
             Json(IgnoreItemResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: "Access denied: Admin role required".to_string(),
             }),
         )
             .into_response();
     }
     // Find the agent by name
-    let agent = match state
-        .config
-        .agents
-        .iter()
-        .find(|a| a.name == payload.agent_name)
-    {
+    let agent = match state.agents.get(&payload.agent_name) {
         Some(agent) => agent,
         None => {
             return Json(IgnoreItemResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: format!("Agent '{}' not found", payload.agent_name),
             })
             .into_response();
@@ -518,6 +972,7 @@ async fn ignore_item(
     let (category_id, folder_path) = if filtered_item_path.is_empty() {
         return Json(IgnoreItemResponse {
             success: false,
+            minimum_copies_override: None,
             message: "No valid path provided".to_string(),
         })
         .into_response();
@@ -533,21 +988,43 @@ async fn ignore_item(
         (category_id, folder_path)
     };
 
+    let minimum_copies_override = match enforce_replication(
+        &state,
+        &auth_user,
+        "ignore",
+        &filtered_item_path,
+        std::slice::from_ref(&agent.name),
+        payload.override_minimum_copies,
+    )
+    .await
+    {
+        Ok(minimum_copies_override) => minimum_copies_override,
+        Err(refusal) => return refusal,
+    };
+
     // Build the ignore request for the agent
     let ignore_request = AgentIgnoreRequest {
         category_id,
         folder_path,
+        dry_run: false,
     };
 
     // Send the ignore request to the agent
-    match state.agent_client.ignore_item(agent, &ignore_request).await {
+    let result = state
+        .agent_client
+        .ignore_item(&agent, &ignore_request)
+        .await;
+    state.tree_cache.invalidate(&agent.name);
+    match result {
         Ok(_) => Json(IgnoreItemResponse {
             success: true,
+            minimum_copies_override,
             message: format!("Successfully ignored item on {}", agent.name),
         })
         .into_response(),
         Err(e) => Json(IgnoreItemResponse {
             success: false,
+            minimum_copies_override: None,
             message: format!("Failed to ignore item: {}", e),
         })
         .into_response(),
@@ -557,28 +1034,25 @@ async fn ignore_item(
 async fn unignore_item(
     State(state): State<AppState>,
     auth_user: AuthUser,
-    Json(payload): Json<IgnoreItemRequest>,
+    Json(payload): Json<UnignoreItemRequest>,
 ) -> impl IntoResponse {
     if !auth_user.is_admin() {
         return (
             StatusCode::FORBIDDEN,
-            Json(IgnoreItemResponse {
+            Json(UnignoreItemResponse {
                 success: false,
+                user_entry: false,
                 message: "Access denied: Admin role required".to_string(),
             }),
         )
             .into_response();
     }
-    let agent = match state
-        .config
-        .agents
-        .iter()
-        .find(|a| a.name == payload.agent_name)
-    {
+    let agent = match state.agents.get(&payload.agent_name) {
         Some(agent) => agent,
         None => {
-            return Json(IgnoreItemResponse {
+            return Json(UnignoreItemResponse {
                 success: false,
+                user_entry: false,
                 message: format!("Agent '{}' not found", payload.agent_name),
             })
             .into_response();
@@ -593,8 +1067,9 @@ async fn unignore_item(
         .collect();
 
     let (category_id, folder_path) = if filtered_item_path.is_empty() {
-        return Json(IgnoreItemResponse {
+        return Json(UnignoreItemResponse {
             success: false,
+            user_entry: false,
             message: "No valid path provided".to_string(),
         })
         .into_response();
@@ -612,20 +1087,25 @@ async fn unignore_item(
     let unignore_request = AgentUnignoreRequest {
         category_id,
         folder_path,
+        dry_run: false,
+        include_user_entries: payload.include_user_entries,
     };
 
-    match state
+    let result = state
         .agent_client
-        .unignore_item(agent, &unignore_request)
-        .await
-    {
-        Ok(_) => Json(IgnoreItemResponse {
+        .unignore_item(&agent, &unignore_request)
+        .await;
+    state.tree_cache.invalidate(&agent.name);
+    match result {
+        Ok(_) => Json(UnignoreItemResponse {
             success: true,
+            user_entry: false,
             message: format!("Successfully un-ignored item on {}", agent.name),
         })
         .into_response(),
-        Err(e) => Json(IgnoreItemResponse {
+        Err(e) => Json(UnignoreItemResponse {
             success: false,
+            user_entry: matches!(e, AgentError::Conflict(_)),
             message: format!("Failed to un-ignore item: {}", e),
         })
         .into_response(),
@@ -642,22 +1122,19 @@ async fn delete_item(
             StatusCode::FORBIDDEN,
             Json(DeleteItemResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: "Access denied: Admin role required".to_string(),
             }),
         )
             .into_response();
     }
     // Find the agent by name
-    let agent = match state
-        .config
-        .agents
-        .iter()
-        .find(|a| a.name == payload.agent_name)
-    {
+    let agent = match state.agents.get(&payload.agent_name) {
         Some(agent) => agent,
         None => {
             return Json(DeleteItemResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: format!("Agent '{}' not found", payload.agent_name),
             })
             .into_response();
@@ -676,6 +1153,7 @@ async fn delete_item(
     let (category_id, folder_path) = if filtered_item_path.is_empty() {
         return Json(DeleteItemResponse {
             success: false,
+            minimum_copies_override: None,
             message: "No valid path provided".to_string(),
         })
         .into_response();
@@ -691,21 +1169,43 @@ async fn delete_item(
         (category_id, folder_path)
     };
 
+    let minimum_copies_override = match enforce_replication(
+        &state,
+        &auth_user,
+        "delete",
+        &filtered_item_path,
+        std::slice::from_ref(&agent.name),
+        payload.override_minimum_copies,
+    )
+    .await
+    {
+        Ok(minimum_copies_override) => minimum_copies_override,
+        Err(refusal) => return refusal,
+    };
+
     // Build the delete request for the agent
     let delete_request = AgentDeleteRequest {
         category_id,
         folder_path,
+        dry_run: false,
     };
 
     // Send the delete request to the agent
-    match state.agent_client.delete_item(agent, &delete_request).await {
+    let result = state
+        .agent_client
+        .delete_item(&agent, &delete_request)
+        .await;
+    state.tree_cache.invalidate(&agent.name);
+    match result {
         Ok(_) => Json(DeleteItemResponse {
             success: true,
+            minimum_copies_override,
             message: format!("Successfully deleted item on {}", agent.name),
         })
         .into_response(),
         Err(e) => Json(DeleteItemResponse {
             success: false,
+            minimum_copies_override: None,
             message: format!("Failed to delete item: {}", e),
         })
         .into_response(),
@@ -718,18 +1218,35 @@ pub struct DeleteItemDetailsRequest {
     pub item_path: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Number of file paths sent to the delete confirmation, the rest are only counted
+const DELETE_DETAILS_MAX_FILES: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct DeleteItemDetailsResponse {
     pub success: bool,
     pub message: Option<String>,
     pub name: String,
-    pub is_leaf: bool,
+    pub exists: bool,
     pub is_dir: bool,
+    pub moves_to_trash: bool,
     pub size_kb: u64,
-    pub item_count: usize,
+    pub total_bytes: u64,
+    pub file_count: usize,
+    /// The first `DELETE_DETAILS_MAX_FILES` files that would be removed
+    pub files: Vec<String>,
     pub agent_name: String,
 }
 
+impl DeleteItemDetailsResponse {
+    fn failed(message: String, agent_name: String) -> Self {
+        DeleteItemDetailsResponse {
+            message: Some(message),
+            agent_name,
+            ..Default::default()
+        }
+    }
+}
+
 async fn delete_item_details(
     State(state): State<AppState>,
     auth_user: AuthUser,
@@ -738,38 +1255,21 @@ async fn delete_item_details(
     if !auth_user.is_admin() {
         return (
             StatusCode::FORBIDDEN,
-            Json(DeleteItemDetailsResponse {
-                success: false,
-                message: Some("Access denied: Admin role required".to_string()),
-                name: String::new(),
-                is_leaf: false,
-                is_dir: false,
-                size_kb: 0,
-                item_count: 0,
-                agent_name: payload.agent_name,
-            }),
+            Json(DeleteItemDetailsResponse::failed(
+                "Access denied: Admin role required".to_string(),
+                payload.agent_name,
+            )),
         )
             .into_response();
     }
     // Find the agent by name
-    let agent = match state
-        .config
-        .agents
-        .iter()
-        .find(|a| a.name == payload.agent_name)
-    {
+    let agent = match state.agents.get(&payload.agent_name) {
         Some(agent) => agent,
         None => {
-            return Json(DeleteItemDetailsResponse {
-                success: false,
-                message: Some(format!("Agent '{}' not found", payload.agent_name)),
-                name: String::new(),
-                is_leaf: false,
-                is_dir: false,
-                size_kb: 0,
-                item_count: 0,
-                agent_name: payload.agent_name,
-            })
+            return Json(DeleteItemDetailsResponse::failed(
+                format!("Agent '{}' not found", payload.agent_name),
+                payload.agent_name,
+            ))
             .into_response();
         }
     };
@@ -782,50 +1282,53 @@ async fn delete_item_details(
         .cloned()
         .collect();
 
-    if filtered_item_path.is_empty() {
-        return Json(DeleteItemDetailsResponse {
-            success: false,
-            message: Some("No valid path provided".to_string()),
-            name: String::new(),
-            is_leaf: false,
-            is_dir: false,
-            size_kb: 0,
-            item_count: 0,
-            agent_name: payload.agent_name,
-        })
+    if filtered_item_path.len() < 2 {
+        return Json(DeleteItemDetailsResponse::failed(
+            "No valid path provided".to_string(),
+            payload.agent_name,
+        ))
         .into_response();
     }
 
-    let request = AgentItemInfoRequest {
-        item_path: filtered_item_path,
+    // Ask the agent what a delete would actually remove
+    let request = AgentDeleteRequest {
+        category_id: filtered_item_path[0].clone(),
+        folder_path: filtered_item_path[1..].to_vec(),
+        dry_run: true,
     };
 
-    match state.agent_client.get_item_info(agent, &request).await {
-        Ok(resp) => {
-            let is_dir = !resp.item.items.is_empty() || request.item_path.len() <= 2;
-            Json(DeleteItemDetailsResponse {
-                success: true,
-                message: None,
-                name: resp.item.name,
-                is_leaf: resp.item.leaf,
-                is_dir,
-                size_kb: resp.item.size_kb,
-                item_count: resp.item.items.len(),
-                agent_name: agent.name.clone(),
-            })
-            .into_response()
-        }
-        Err(e) => Json(DeleteItemDetailsResponse {
-            success: false,
-            message: Some(format!("Failed to retrieve item details: {}", e)),
-            name: String::new(),
-            is_leaf: false,
-            is_dir: false,
-            size_kb: 0,
-            item_count: 0,
-            agent_name: payload.agent_name,
+    match state.agent_client.delete_item(&agent, &request).await {
+        Ok(AgentDeleteResponse {
+            preview: Some(preview),
+            ..
+        }) => Json(DeleteItemDetailsResponse {
+            success: true,
+            message: None,
+            name: filtered_item_path.last().cloned().unwrap_or_default(),
+            exists: preview.exists,
+            is_dir: preview.is_dir,
+            moves_to_trash: preview.moves_to_trash,
+            size_kb: preview.total_bytes / 1024,
+            total_bytes: preview.total_bytes,
+            file_count: preview.files.len(),
+            files: preview
+                .files
+                .into_iter()
+                .take(DELETE_DETAILS_MAX_FILES)
+                .collect(),
+            agent_name: agent.name.clone(),
         })
         .into_response(),
+        Ok(_) => Json(DeleteItemDetailsResponse::failed(
+            format!("{} did not return a delete preview", agent.name),
+            payload.agent_name,
+        ))
+        .into_response(),
+        Err(e) => Json(DeleteItemDetailsResponse::failed(
+            format!("Failed to retrieve item details: {}", e),
+            payload.agent_name,
+        ))
+        .into_response(),
     }
 }
 
@@ -833,12 +1336,35 @@ async fn delete_item_details(
 pub struct BulkIgnoreRequest {
     pub agent_names: Vec<String>,
     pub item_path: Vec<String>,
+    /// Go ahead even if fewer than `minimum_copies` synced copies would remain
+    #[serde(default)]
+    pub override_minimum_copies: bool,
+    /// Ask each agent what would happen instead of changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BulkUnignoreRequest {
+    pub agent_names: Vec<String>,
+    pub item_path: Vec<String>,
+    /// Ask each agent what would happen instead of changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also remove matching lines written by hand, the agents refuse to otherwise
+    #[serde(default)]
+    pub include_user_entries: bool,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct BulkDeleteRequest {
     pub agent_names: Vec<String>,
     pub item_path: Vec<String>,
+    /// Go ahead even if fewer than `minimum_copies` synced copies would remain
+    #[serde(default)]
+    pub override_minimum_copies: bool,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -846,6 +1372,13 @@ pub struct BulkActionResult {
     pub agent_name: String,
     pub success: bool,
     pub message: String,
+    #[serde(default)]
+    pub ignore_preview: Option<IgnorePreview>,
+    #[serde(default)]
+    pub delete_preview: Option<DeletePreview>,
+    /// The agent refused to un-ignore because the matching line was written by hand
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub user_entry: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -853,6 +1386,9 @@ pub struct BulkActionResponse {
     pub success: bool,
     pub message: String,
     pub results: Vec<BulkActionResult>,
+    /// Set when an Admin went ahead despite the minimum copies check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum_copies_override: Option<ReplicationOverride>,
 }
 
 async fn bulk_ignore_item(
@@ -865,6 +1401,7 @@ async fn bulk_ignore_item(
             StatusCode::FORBIDDEN,
             Json(BulkActionResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: "Access denied: Admin role required".to_string(),
                 results: vec![],
             }),
@@ -881,6 +1418,7 @@ async fn bulk_ignore_item(
     if filtered_item_path.is_empty() {
         return Json(BulkActionResponse {
             success: false,
+            minimum_copies_override: None,
             message: "No valid path provided".to_string(),
             results: vec![],
         })
@@ -894,21 +1432,55 @@ async fn bulk_ignore_item(
         vec![]
     };
 
+    // Previews go through so they can show what would happen
+    let minimum_copies_override = if payload.dry_run {
+        None
+    } else {
+        match enforce_replication(
+            &state,
+            &auth_user,
+            "ignore",
+            &filtered_item_path,
+            &payload.agent_names,
+            payload.override_minimum_copies,
+        )
+        .await
+        {
+            Ok(minimum_copies_override) => minimum_copies_override,
+            Err(refusal) => return refusal,
+        }
+    };
+
     let ignore_request = AgentIgnoreRequest {
         category_id,
         folder_path,
+        dry_run: payload.dry_run,
     };
 
     let mut results = Vec::new();
     let mut overall_success = true;
 
     for agent_name in &payload.agent_names {
-        if let Some(agent) = state.config.agents.iter().find(|a| &a.name == agent_name) {
-            match state.agent_client.ignore_item(agent, &ignore_request).await {
-                Ok(_) => results.push(BulkActionResult {
+        if let Some(agent) = state.agents.get(agent_name) {
+            let result = state
+                .agent_client
+                .ignore_item(&agent, &ignore_request)
+                .await;
+            if !payload.dry_run {
+                state.tree_cache.invalidate(agent_name);
+            }
+            match result {
+                Ok(response) => results.push(BulkActionResult {
                     agent_name: agent_name.clone(),
                     success: true,
-                    message: format!("Ignored item on {}", agent_name),
+                    message: if payload.dry_run {
+                        response.message
+                    } else {
+                        format!("Ignored item on {}", agent_name)
+                    },
+                    ignore_preview: response.preview,
+                    delete_preview: None,
+                    user_entry: false,
                 }),
                 Err(e) => {
                     overall_success = false;
@@ -916,6 +1488,9 @@ async fn bulk_ignore_item(
                         agent_name: agent_name.clone(),
                         success: false,
                         message: format!("Failed on {}: {}", agent_name, e),
+                        ignore_preview: None,
+                        delete_preview: None,
+                        user_entry: false,
                     });
                 }
             }
@@ -925,16 +1500,22 @@ async fn bulk_ignore_item(
                 agent_name: agent_name.clone(),
                 success: false,
                 message: format!("Agent '{}' not found", agent_name),
+                ignore_preview: None,
+                delete_preview: None,
+                user_entry: false,
             });
         }
     }
 
     Json(BulkActionResponse {
         success: overall_success,
-        message: if overall_success {
-            format!("Successfully ignored item across {} agents", results.len())
-        } else {
+        minimum_copies_override,
+        message: if !overall_success {
             "Bulk ignore completed with errors".to_string()
+        } else if payload.dry_run {
+            format!("Previewed ignore across {} agents", results.len())
+        } else {
+            format!("Successfully ignored item across {} agents", results.len())
         },
         results,
     })
@@ -944,13 +1525,14 @@ async fn bulk_ignore_item(
 async fn bulk_unignore_item(
     State(state): State<AppState>,
     auth_user: AuthUser,
-    Json(payload): Json<BulkIgnoreRequest>,
+    Json(payload): Json<BulkUnignoreRequest>,
 ) -> impl IntoResponse {
     if !auth_user.is_admin() {
         return (
             StatusCode::FORBIDDEN,
             Json(BulkActionResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: "Access denied: Admin role required".to_string(),
                 results: vec![],
             }),
@@ -967,6 +1549,7 @@ async fn bulk_unignore_item(
     if filtered_item_path.is_empty() {
         return Json(BulkActionResponse {
             success: false,
+            minimum_copies_override: None,
             message: "No valid path provided".to_string(),
             results: vec![],
         })
@@ -983,22 +1566,28 @@ async fn bulk_unignore_item(
     let unignore_request = AgentUnignoreRequest {
         category_id,
         folder_path,
+        dry_run: payload.dry_run,
+        include_user_entries: payload.include_user_entries,
     };
 
     let mut results = Vec::new();
     let mut overall_success = true;
 
     for agent_name in &payload.agent_names {
-        if let Some(agent) = state.config.agents.iter().find(|a| &a.name == agent_name) {
-            match state
+        if let Some(agent) = state.agents.get(agent_name) {
+            let result = state
                 .agent_client
-                .unignore_item(agent, &unignore_request)
-                .await
-            {
+                .unignore_item(&agent, &unignore_request)
+                .await;
+            state.tree_cache.invalidate(agent_name);
+            match result {
                 Ok(_) => results.push(BulkActionResult {
                     agent_name: agent_name.clone(),
                     success: true,
                     message: format!("Un-ignored item on {}", agent_name),
+                    ignore_preview: None,
+                    delete_preview: None,
+                    user_entry: false,
                 }),
                 Err(e) => {
                     overall_success = false;
@@ -1006,6 +1595,9 @@ async fn bulk_unignore_item(
                         agent_name: agent_name.clone(),
                         success: false,
                         message: format!("Failed on {}: {}", agent_name, e),
+                        ignore_preview: None,
+                        delete_preview: None,
+                        user_entry: matches!(e, AgentError::Conflict(_)),
                     });
                 }
             }
@@ -1015,12 +1607,16 @@ async fn bulk_unignore_item(
                 agent_name: agent_name.clone(),
                 success: false,
                 message: format!("Agent '{}' not found", agent_name),
+                ignore_preview: None,
+                delete_preview: None,
+                user_entry: false,
             });
         }
     }
 
     Json(BulkActionResponse {
         success: overall_success,
+        minimum_copies_override: None,
         message: if overall_success {
             format!(
                 "Successfully un-ignored item across {} agents",
@@ -1044,6 +1640,7 @@ async fn bulk_delete_item(
             StatusCode::FORBIDDEN,
             Json(BulkActionResponse {
                 success: false,
+                minimum_copies_override: None,
                 message: "Access denied: Admin role required".to_string(),
                 results: vec![],
             }),
@@ -1060,6 +1657,7 @@ async fn bulk_delete_item(
     if filtered_item_path.is_empty() {
         return Json(BulkActionResponse {
             success: false,
+            minimum_copies_override: None,
             message: "No valid path provided".to_string(),
             results: vec![],
         })
@@ -1073,21 +1671,55 @@ async fn bulk_delete_item(
         vec![]
     };
 
+    // Previews go through so they can show what would happen
+    let minimum_copies_override = if payload.dry_run {
+        None
+    } else {
+        match enforce_replication(
+            &state,
+            &auth_user,
+            "delete",
+            &filtered_item_path,
+            &payload.agent_names,
+            payload.override_minimum_copies,
+        )
+        .await
+        {
+            Ok(minimum_copies_override) => minimum_copies_override,
+            Err(refusal) => return refusal,
+        }
+    };
+
     let delete_request = AgentDeleteRequest {
         category_id,
         folder_path,
+        dry_run: payload.dry_run,
     };
 
     let mut results = Vec::new();
     let mut overall_success = true;
 
     for agent_name in &payload.agent_names {
-        if let Some(agent) = state.config.agents.iter().find(|a| &a.name == agent_name) {
-            match state.agent_client.delete_item(agent, &delete_request).await {
-                Ok(_) => results.push(BulkActionResult {
+        if let Some(agent) = state.agents.get(agent_name) {
+            let result = state
+                .agent_client
+                .delete_item(&agent, &delete_request)
+                .await;
+            if !payload.dry_run {
+                state.tree_cache.invalidate(agent_name);
+            }
+            match result {
+                Ok(response) => results.push(BulkActionResult {
                     agent_name: agent_name.clone(),
                     success: true,
-                    message: format!("Deleted item on {}", agent_name),
+                    message: if payload.dry_run {
+                        response.message
+                    } else {
+                        format!("Deleted item on {}", agent_name)
+                    },
+                    ignore_preview: None,
+                    delete_preview: response.preview,
+                    user_entry: false,
                 }),
                 Err(e) => {
                     overall_success = false;
@@ -1095,6 +1727,9 @@ async fn bulk_delete_item(
                         agent_name: agent_name.clone(),
                         success: false,
                         message: format!("Failed on {}: {}", agent_name, e),
+                        ignore_preview: None,
+                        delete_preview: None,
+                        user_entry: false,
                     });
                 }
             }
@@ -1104,16 +1739,22 @@ async fn bulk_delete_item(
                 agent_name: agent_name.clone(),
                 success: false,
                 message: format!("Agent '{}' not found", agent_name),
+                ignore_preview: None,
+                delete_preview: None,
+                user_entry: false,
             });
         }
     }
 
     Json(BulkActionResponse {
         success: overall_success,
-        message: if overall_success {
-            format!("Successfully deleted item across {} agents", results.len())
-        } else {
+        minimum_copies_override,
+        message: if !overall_success {
             "Bulk delete completed with errors".to_string()
+        } else if payload.dry_run {
+            format!("Previewed delete across {} agents", results.len())
+        } else {
+            format!("Successfully deleted item across {} agents", results.len())
         },
         results,
     })
@@ -1137,16 +1778,34 @@ async fn toggle_agent(
         )
             .into_response();
     }
-    let mut disabled = state.disabled_agents.write().unwrap();
-    if payload.enabled {
-        disabled.remove(&payload.agent_name);
+    let result = if payload.enabled {
+        // Whatever was cached is from before the agent was disabled
+        state.tree_cache.invalidate(&payload.agent_name);
+        state.disabled_agents.enable(&payload.agent_name)
     } else {
-        disabled.insert(payload.agent_name.clone());
-    }
+        state
+            .disabled_agents
+            .disable(&payload.agent_name, &auth_user.username, payload.reason)
+    };
 
-    let status_str = if payload.enabled {
-        "enabled"
-    } else {
+    // The change is applied in memory either way, it just won't survive a restart
+    if let Err(e) = result {
+        tracing::error!("Failed to save disabled agents: {}", e);
+        return Json(ToggleAgentResponse {
+            success: false,
+            message: format!(
+                "Agent '{}' was toggled but the change could not be saved: {}",
+                payload.agent_name, e
+            ),
+            agent_name: payload.agent_name,
+            enabled: payload.enabled,
+        })
+        .into_response();
+    }
+
+    let status_str = if payload.enabled {
+        "enabled"
+    } else {
         "disabled"
     };
     Json(ToggleAgentResponse {
@@ -1158,11 +1817,573 @@ async fn toggle_agent(
     .into_response()
 }
 
+fn agent_registry_response(
+    agent_name: String,
+    result: Result<String, crate::registry::RegistryError>,
+) -> axum::response::Response {
+    match result {
+        Ok(message) => Json(AgentRegistryResponse {
+            success: true,
+            message,
+            agent_name,
+        })
+        .into_response(),
+        Err(e) => Json(AgentRegistryResponse {
+            success: false,
+            message: e.to_string(),
+            agent_name,
+        })
+        .into_response(),
+    }
+}
+
+fn admin_required(agent_name: String) -> axum::response::Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(AgentRegistryResponse {
+            success: false,
+            message: "Access denied: Admin role required".to_string(),
+            agent_name,
+        }),
+    )
+        .into_response()
+}
+
+async fn add_agent(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<AddAgentRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return admin_required(payload.agent_name);
+    }
+    let agent_name = payload.agent_name.trim().to_string();
+    let result = state
+        .agents
+        .add(Agent {
+            name: agent_name.clone(),
+            hostname: payload.hostname.trim().to_string(),
+            api_key: payload.api_key.trim().to_string(),
+            timeout_seconds: payload.timeout_seconds,
+        })
+        .map(|()| {
+            tracing::info!(user = %auth_user.username, "Added agent '{}'", agent_name);
+            state.tree_cache.invalidate(&agent_name);
+            format!("Agent '{}' added", agent_name)
+        });
+
+    agent_registry_response(agent_name, result)
+}
+
+async fn edit_agent(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<EditAgentRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return admin_required(payload.agent_name);
+    }
+    let update = crate::registry::AgentUpdate {
+        hostname: Some(payload.hostname.trim().to_string()),
+        api_key: payload
+            .api_key
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty()),
+        timeout_seconds: Some(payload.timeout_seconds),
+    };
+    let result = state
+        .agents
+        .update(&payload.agent_name, update)
+        .map(|agent| {
+            tracing::info!(user = %auth_user.username, "Updated agent '{}'", agent.name);
+            // Cached data may have come from the old hostname
+            state.tree_cache.invalidate(&agent.name);
+            format!("Agent '{}' updated", agent.name)
+        });
+
+    agent_registry_response(payload.agent_name, result)
+}
+
+async fn remove_agent(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<AgentNameRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return admin_required(payload.agent_name);
+    }
+    let result = state.agents.remove(&payload.agent_name).map(|agent| {
+        tracing::info!(user = %auth_user.username, "Removed agent '{}'", agent.name);
+        state.tree_cache.invalidate(&agent.name);
+        state.enrolment.forget(&agent.name);
+        // An agent added back later under the same name starts out enabled
+        if let Err(e) = state.disabled_agents.enable(&agent.name) {
+            tracing::warn!("Failed to save disabled agents: {}", e);
+        }
+        format!("Agent '{}' removed", agent.name)
+    });
+
+    agent_registry_response(payload.agent_name, result)
+}
+
+async fn approve_agent(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<AgentNameRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return admin_required(payload.agent_name);
+    }
+    let Some(pending) = state.enrolment.approve(&payload.agent_name) else {
+        return agent_registry_response(
+            payload.agent_name.clone(),
+            Err(crate::registry::RegistryError::NotFound(payload.agent_name)),
+        );
+    };
+    let result = state
+        .agents
+        .add(Agent {
+            name: pending.name.clone(),
+            hostname: pending.hostname,
+            api_key: pending.api_key,
+            timeout_seconds: None,
+        })
+        .map(|()| {
+            tracing::info!(user = %auth_user.username, "Approved agent '{}'", pending.name);
+            format!("Agent '{}' approved", pending.name)
+        });
+    if result.is_err() {
+        // Not registered after all, the next heartbeat puts it back in the pending list
+        state.enrolment.forget(&pending.name);
+    }
+
+    agent_registry_response(payload.agent_name, result)
+}
+
+async fn reject_agent(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<AgentNameRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return admin_required(payload.agent_name);
+    }
+    let result = if state.enrolment.reject(&payload.agent_name) {
+        tracing::info!(user = %auth_user.username, "Rejected agent '{}'", payload.agent_name);
+        Ok(format!("Agent '{}' rejected", payload.agent_name))
+    } else {
+        Err(crate::registry::RegistryError::NotFound(
+            payload.agent_name.clone(),
+        ))
+    };
+
+    agent_registry_response(payload.agent_name, result)
+}
+
+async fn trash_list(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<TrashListRequest>,
+) -> impl IntoResponse {
+    let mut context = state.context.clone();
+    auth::inject_auth_context(&mut context, &auth_user);
+    context.insert("agent_name", &payload.agent_name);
+
+    match state.agents.get(&payload.agent_name) {
+        Some(agent) => match state.agent_client.list_trash(&agent).await {
+            Ok(trash) => {
+                context.insert("enabled", &trash.enabled);
+                context.insert("retention_days", &trash.retention_days);
+                let items: Vec<TrashItemView> =
+                    trash.items.iter().map(TrashItemView::from).collect();
+                context.insert("items", &items);
+            }
+            Err(e) => {
+                context.insert("error", &format!("Failed to load trash: {}", e));
+            }
+        },
+        None => {
+            context.insert(
+                "error",
+                &format!("Agent '{}' not found", payload.agent_name),
+            );
+        }
+    }
+
+    RenderHtml(
+        Key("components/trash-list.html".to_string()),
+        state.engine,
+        context.into_json(),
+    )
+}
+
+async fn restore_trash_item(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<TrashItemRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(TrashItemResponse {
+                success: false,
+                message: "Access denied: Admin role required".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    let Some(agent) = state.agents.get(&payload.agent_name) else {
+        return Json(TrashItemResponse {
+            success: false,
+            message: format!("Agent '{}' not found", payload.agent_name),
+        })
+        .into_response();
+    };
+    let Some(id) = payload.id else {
+        return Json(TrashItemResponse {
+            success: false,
+            message: "No trash item provided".to_string(),
+        })
+        .into_response();
+    };
+
+    let request = AgentTrashRestoreRequest { id };
+    let result = state
+        .agent_client
+        .restore_trash_item(&agent, &request)
+        .await;
+    state.tree_cache.invalidate(&agent.name);
+    match result {
+        Ok(response) => Json(TrashItemResponse {
+            success: true,
+            message: format!("{} on {}", response.message, agent.name),
+        })
+        .into_response(),
+        Err(e) => Json(TrashItemResponse {
+            success: false,
+            message: format!("Failed to restore item: {}", e),
+        })
+        .into_response(),
+    }
+}
+
+async fn purge_trash(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<TrashItemRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(TrashItemResponse {
+                success: false,
+                message: "Access denied: Admin role required".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    let Some(agent) = state.agents.get(&payload.agent_name) else {
+        return Json(TrashItemResponse {
+            success: false,
+            message: format!("Agent '{}' not found", payload.agent_name),
+        })
+        .into_response();
+    };
+
+    let request = match (payload.id, payload.all) {
+        (Some(id), _) => AgentTrashPurgeRequest {
+            id: Some(id),
+            all: false,
+        },
+        (None, true) => AgentTrashPurgeRequest {
+            id: None,
+            all: true,
+        },
+        (None, false) => {
+            return Json(TrashItemResponse {
+                success: false,
+                message: "No trash item provided".to_string(),
+            })
+            .into_response();
+        }
+    };
+    match state.agent_client.purge_trash(&agent, &request).await {
+        Ok(response) => Json(TrashItemResponse {
+            success: true,
+            message: format!("{} on {}", response.message, agent.name),
+        })
+        .into_response(),
+        Err(e) => Json(TrashItemResponse {
+            success: false,
+            message: format!("Failed to purge trash: {}", e),
+        })
+        .into_response(),
+    }
+}
+
+async fn stignore_history(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<HistoryRequest>,
+) -> impl IntoResponse {
+    let mut context = state.context.clone();
+    auth::inject_auth_context(&mut context, &auth_user);
+    context.insert("agent_name", &payload.agent_name);
+
+    match state.agents.get(&payload.agent_name) {
+        Some(agent) => match state.agent_client.get_stignore_history(&agent).await {
+            Ok(history) => {
+                context.insert("enabled", &history.enabled);
+                context.insert("retention", &history.retention);
+                let categories: Vec<serde_json::Value> = history
+                    .categories
+                    .iter()
+                    .map(|category| serde_json::json!({"id": category.id, "name": category.name}))
+                    .collect();
+                context.insert("categories", &categories);
+                let selected = history
+                    .categories
+                    .iter()
+                    .find(|category| Some(&category.id) == payload.category_id.as_ref())
+                    .or(history.categories.first());
+                if let Some(selected) = selected {
+                    context.insert("category_id", &selected.id);
+                    context.insert("revisions", &RevisionView::list(&selected.revisions));
+                }
+            }
+            Err(e) => {
+                context.insert("error", &format!("Failed to load .stignore history: {}", e));
+            }
+        },
+        None => {
+            context.insert(
+                "error",
+                &format!("Agent '{}' not found", payload.agent_name),
+            );
+        }
+    }
+
+    RenderHtml(
+        Key("components/stignore-history.html".to_string()),
+        state.engine,
+        context.into_json(),
+    )
+}
+
+async fn stignore_diff(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<HistoryDiffRequest>,
+) -> impl IntoResponse {
+    let mut context = state.context.clone();
+    auth::inject_auth_context(&mut context, &auth_user);
+
+    match state.agents.get(&payload.agent_name) {
+        Some(agent) => {
+            let request = AgentStignoreDiffRequest {
+                category_id: payload.category_id,
+                from: payload.from,
+                to: payload.to,
+            };
+            match state.agent_client.diff_stignore(&agent, &request).await {
+                Ok(diff) => context.insert("lines", &diff.lines),
+                Err(e) => context.insert("error", &format!("Failed to compare revisions: {}", e)),
+            }
+        }
+        None => {
+            context.insert(
+                "error",
+                &format!("Agent '{}' not found", payload.agent_name),
+            );
+        }
+    }
+
+    RenderHtml(
+        Key("components/stignore-diff.html".to_string()),
+        state.engine,
+        context.into_json(),
+    )
+}
+
+async fn revert_stignore(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<HistoryRevertRequest>,
+) -> impl IntoResponse {
+    if !auth_user.is_admin() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(HistoryRevertResponse {
+                success: false,
+                message: "Access denied: Admin role required".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    let Some(agent) = state.agents.get(&payload.agent_name) else {
+        return Json(HistoryRevertResponse {
+            success: false,
+            message: format!("Agent '{}' not found", payload.agent_name),
+        })
+        .into_response();
+    };
+
+    let request = AgentStignoreRevertRequest {
+        category_id: payload.category_id,
+        id: payload.id,
+    };
+    let result = state.agent_client.revert_stignore(&agent, &request).await;
+    // Ignore flags of the agent's items may have changed
+    state.tree_cache.invalidate(&agent.name);
+    match result {
+        Ok(response) => Json(HistoryRevertResponse {
+            success: true,
+            message: format!("{} on {}", response.message, agent.name),
+        })
+        .into_response(),
+        Err(e) => Json(HistoryRevertResponse {
+            success: false,
+            message: format!("Failed to revert .stignore: {}", e),
+        })
+        .into_response(),
+    }
+}
+
+async fn stignore_editor(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<StignoreEditorRequest>,
+) -> impl IntoResponse {
+    let mut context = state.context.clone();
+    auth::inject_auth_context(&mut context, &auth_user);
+    context.insert("agent_name", &payload.agent_name);
+    context.insert("category_id", &payload.category_id);
+
+    if !auth_user.is_admin() {
+        context.insert("error", "Access denied: Admin role required");
+    } else if let Some(agent) = state.agents.get(&payload.agent_name) {
+        match state
+            .agent_client
+            .get_stignore(&agent, &payload.category_id)
+            .await
+        {
+            Ok(stignore) => {
+                context.insert("content", &stignore.content);
+                context.insert("etag", &stignore.etag);
+                context.insert("managed_count", &stignore.managed_entries.len());
+                context.insert("user_count", &stignore.user_entries.len());
+            }
+            Err(e) => context.insert("error", &format!("Failed to load .stignore: {}", e)),
+        }
+    } else {
+        context.insert(
+            "error",
+            &format!("Agent '{}' not found", payload.agent_name),
+        );
+    }
+
+    RenderHtml(
+        Key("components/stignore-editor.html".to_string()),
+        state.engine,
+        context.into_json(),
+    )
+}
+
+async fn save_stignore(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<StignoreSaveRequest>,
+) -> impl IntoResponse {
+    let failure = |message: String| StignoreSaveResponse {
+        success: false,
+        message,
+        conflict: false,
+        etag: String::new(),
+        content: String::new(),
+    };
+    if !auth_user.is_admin() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(failure("Access denied: Admin role required".to_string())),
+        )
+            .into_response();
+    }
+    let Some(agent) = state.agents.get(&payload.agent_name) else {
+        return Json(failure(format!("Agent '{}' not found", payload.agent_name))).into_response();
+    };
+
+    let request = AgentStignoreWriteRequest {
+        content: payload.content,
+    };
+    let result = state
+        .agent_client
+        .put_stignore(&agent, &payload.category_id, &payload.etag, &request)
+        .await;
+    match result {
+        Ok(agent_client::StignoreWrite::Written(response)) => {
+            // Ignore flags of the agent's items may have changed
+            state.tree_cache.invalidate(&agent.name);
+            Json(StignoreSaveResponse {
+                success: true,
+                message: format!("Saved .stignore on {}", agent.name),
+                conflict: false,
+                etag: response.etag,
+                content: String::new(),
+            })
+            .into_response()
+        }
+        Ok(agent_client::StignoreWrite::Conflict(current)) => Json(StignoreSaveResponse {
+            success: false,
+            message: format!(
+                "The .stignore on {} changed since it was opened",
+                agent.name
+            ),
+            conflict: true,
+            etag: current.etag,
+            content: current.content,
+        })
+        .into_response(),
+        Err(e) => Json(failure(format!("Failed to save .stignore: {}", e))).into_response(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Refetches every enabled agent's tree instead of waiting for the background refresh
+async fn refresh_agent_data(State(state): State<AppState>) -> impl IntoResponse {
+    let disabled_agents = state.disabled_agents.names();
+    let enabled: Vec<Agent> = state
+        .agents
+        .list()
+        .into_iter()
+        .filter(|agent| !disabled_agents.contains(&agent.name))
+        .collect();
+
+    state
+        .tree_cache
+        .refresh(
+            &state.agent_client,
+            &enabled,
+            state.config.manager.aggregation_deadline(),
+        )
+        .await;
+
+    Json(RefreshResponse {
+        success: true,
+        message: format!("Refreshed data from {} agents", enabled.len()),
+    })
+}
+
 async fn agents_table(State(state): State<AppState>, auth_user: AuthUser) -> impl IntoResponse {
     let mut context = state.context.clone();
     auth::inject_auth_context(&mut context, &auth_user);
     let agent_summaries = crate::pages::build_agent_summaries(&state).await;
     context.insert("agents", &agent_summaries);
+    context.insert("pending_agents", &state.enrolment.pending());
 
     RenderHtml(
         Key("components/agents-table.html".to_string()),
@@ -1171,6 +2392,14 @@ async fn agents_table(State(state): State<AppState>, auth_user: AuthUser) -> imp
     )
 }
 
+enum AgentProbe {
+    Disabled,
+    Reported {
+        online: bool,
+        warning: Option<String>,
+    },
+}
+
 pub async fn agent_status_pill(
     State(state): State<AppState>,
     auth_user: AuthUser,
@@ -1178,8 +2407,8 @@ pub async fn agent_status_pill(
     let mut context = state.context.clone();
     auth::inject_auth_context(&mut context, &auth_user);
 
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
-    let total = state.config.agents.len();
+    let disabled_agents = state.disabled_agents.names();
+    let total = state.agents.len();
 
     if total == 0 {
         context.insert("status_level", "empty");
@@ -1199,15 +2428,41 @@ pub async fn agent_status_pill(
     }
 
     let mut set = tokio::task::JoinSet::new();
-    for agent in state.config.agents.clone() {
+    for agent in state.agents.list() {
         let is_enabled = !disabled_agents.contains(&agent.name);
+        let heartbeat = state.enrolment.heartbeat(&agent.name);
         let client = state.agent_client.clone();
         set.spawn(async move {
             if !is_enabled {
-                (agent.name, false, true)
+                AgentProbe::Disabled
+            } else if let Some(heartbeat) = heartbeat {
+                // Agents that send heartbeats don't need probing
+                AgentProbe::Reported {
+                    online: heartbeat.is_fresh(),
+                    warning: agent_client::compatibility_warning(
+                        heartbeat.api_version,
+                        &heartbeat.version,
+                    ),
+                }
             } else {
-                let is_online = client.get_categories(&agent).await.is_ok();
-                (agent.name, is_online, false)
+                match client.get_health(&agent).await {
+                    Ok(health) => AgentProbe::Reported {
+                        online: true,
+                        warning: agent_client::compatibility_warning(
+                            health.api_version,
+                            &health.version,
+                        ),
+                    },
+                    // Still answers, just too old to know about /health
+                    Err(AgentError::NotFound(_)) => AgentProbe::Reported {
+                        online: true,
+                        warning: Some(agent_client::missing_health_warning()),
+                    },
+                    Err(_) => AgentProbe::Reported {
+                        online: false,
+                        warning: None,
+                    },
+                }
             }
         });
     }
@@ -1215,22 +2470,28 @@ pub async fn agent_status_pill(
     let mut online_count = 0;
     let mut disabled_count = 0;
     let mut offline_count = 0;
+    let mut incompatible_count = 0;
 
     while let Some(res) = set.join_next().await {
-        if let Ok((_name, is_online, is_disabled)) = res {
-            if is_disabled {
-                disabled_count += 1;
-            } else if is_online {
-                online_count += 1;
-            } else {
-                offline_count += 1;
+        match res {
+            Ok(AgentProbe::Disabled) => disabled_count += 1,
+            Ok(AgentProbe::Reported { online, warning }) => {
+                if online {
+                    online_count += 1;
+                } else {
+                    offline_count += 1;
+                }
+                if warning.is_some() {
+                    incompatible_count += 1;
+                }
             }
+            Err(_) => {}
         }
     }
 
     let enabled_count = total - disabled_count;
 
-    let status_level = if enabled_count == 0 {
+    let status_level = if enabled_count == 0 || incompatible_count > 0 {
         "warning"
     } else if online_count == enabled_count && offline_count == 0 {
         if disabled_count > 0 {
@@ -1263,6 +2524,13 @@ pub async fn agent_status_pill(
     if offline_count > 0 {
         tooltip_parts.push(format!("{} unreachable", offline_count));
     }
+    if incompatible_count > 0 {
+        tooltip_parts.push(format!("{} incompatible", incompatible_count));
+    }
+    let pending_count = state.enrolment.pending().len();
+    if pending_count > 0 {
+        tooltip_parts.push(format!("{} awaiting approval", pending_count));
+    }
     tooltip_parts.push("Click to view Agents".to_string());
     let tooltip = tooltip_parts.join(" • ");
 
@@ -1367,9 +2635,17 @@ async fn dynamic_items(
         }
     };
 
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
-    let response =
-        agents::list_categories(&state.agent_client, state.config.agents, &disabled_agents).await;
+    let disabled_agents = state.disabled_agents.names();
+    let deadline = state.config.manager.aggregation_deadline();
+    let response = state
+        .tree_cache
+        .list_categories(
+            &state.agent_client,
+            state.agents.list(),
+            &disabled_agents,
+            deadline,
+        )
+        .await;
 
     let sort_order = SortOrder::from_query(sort.as_deref());
 
@@ -1401,9 +2677,12 @@ async fn dynamic_items(
         let mut sorted_items = items.clone();
         sort_order.sort_items(&mut sorted_items);
 
+        let agent_names = enabled_agent_names(&state.agents.list(), &disabled_agents);
         let items_with_flags: Vec<ItemGroupWithFlags> = sorted_items
             .iter()
-            .map(|item| convert_item_with_flags(item, state.config.manager.minimum_copies))
+            .map(|item| {
+                convert_item_with_flags(item, state.config.manager.minimum_copies, &agent_names)
+            })
             .collect();
 
         context.insert("items", &items_with_flags);
@@ -2,6 +2,12 @@ use std::process::exit;
 use stignore_lib::{ManagerData, load_manager_config, resolve_config_path};
 
 pub fn load_config(explicit_filename: Option<&str>) -> ManagerData {
+    load_config_with_path(explicit_filename).1
+}
+
+/// Same as `load_config` but also returns the file it was read from, so runtime
+/// changes can be written back to it
+pub fn load_config_with_path(explicit_filename: Option<&str>) -> (String, ManagerData) {
     let default_paths = [
         "/app/config.toml",
         "/config.toml",
@@ -22,7 +28,7 @@ pub fn load_config(explicit_filename: Option<&str>) -> ManagerData {
     match load_manager_config(&path) {
         Ok(data) => {
             tracing::info!("Loaded configuration from {}", path);
-            data
+            (path, data)
         }
         Err(err) => {
             eprintln!("Failed to load configuration from '{}': {}", path, err);
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Who turned an agent off, when and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisabledAgent {
+    pub disabled_by: String,
+    /// Unix timestamp in seconds
+    pub disabled_at: u64,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl DisabledAgent {
+    pub fn since(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.disabled_at)
+    }
+}
+
+/// Agents switched off from the agents page. When backed by a state file every change
+/// is written to it, so the set survives manager restarts.
+#[derive(Clone, Default)]
+pub struct DisabledAgents {
+    entries: Arc<RwLock<BTreeMap<String, DisabledAgent>>>,
+    path: Option<PathBuf>,
+}
+
+impl DisabledAgents {
+    /// Keeps the set in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the set from a state file, a missing file means no agent is disabled
+    pub fn load(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            entries: Arc::new(RwLock::new(entries)),
+            path: Some(path),
+        })
+    }
+
+    /// Names of every disabled agent
+    pub fn names(&self) -> HashSet<String> {
+        self.entries.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn contains(&self, agent_name: &str) -> bool {
+        self.entries.read().unwrap().contains_key(agent_name)
+    }
+
+    pub fn get(&self, agent_name: &str) -> Option<DisabledAgent> {
+        self.entries.read().unwrap().get(agent_name).cloned()
+    }
+
+    pub fn disable(
+        &self,
+        agent_name: &str,
+        disabled_by: &str,
+        reason: Option<String>,
+    ) -> std::io::Result<()> {
+        let disabled_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut entries = self.entries.write().unwrap();
+        let mut updated = entries.clone();
+        updated.insert(
+            agent_name.to_string(),
+            DisabledAgent {
+                disabled_by: disabled_by.to_string(),
+                disabled_at,
+                reason: reason.filter(|r| !r.trim().is_empty()),
+            },
+        );
+        self.save(&updated)?;
+        *entries = updated;
+        Ok(())
+    }
+
+    pub fn enable(&self, agent_name: &str) -> std::io::Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        let mut updated = entries.clone();
+        if updated.remove(agent_name).is_none() {
+            return Ok(());
+        }
+        self.save(&updated)?;
+        *entries = updated;
+        Ok(())
+    }
+
+    /// Callers must hold the write lock so concurrent toggles can't reorder the writes
+    fn save(&self, entries: &BTreeMap<String, DisabledAgent>) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(entries)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        crate::write_atomically(path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_agents_survive_a_reload() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("disabled_agents.json");
+
+        let disabled = DisabledAgents::load(&path).unwrap();
+        assert!(disabled.names().is_empty());
+
+        disabled
+            .disable("nas", "alice", Some("Replacing disks".to_string()))
+            .unwrap();
+        disabled
+            .disable("laptop", "bob", Some("  ".to_string()))
+            .unwrap();
+        disabled.enable("laptop").unwrap();
+
+        let reloaded = DisabledAgents::load(&path).unwrap();
+        assert_eq!(reloaded.names(), HashSet::from(["nas".to_string()]));
+        let entry = reloaded.get("nas").unwrap();
+        assert_eq!(entry.disabled_by, "alice");
+        assert_eq!(entry.reason.as_deref(), Some("Replacing disks"));
+        assert!(entry.disabled_at > 0);
+        assert!(!temp_dir.path().join("disabled_agents.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_corrupt_state_file_is_an_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("disabled_agents.json");
+        std::fs::write(&path, "not json").unwrap();
+
+        assert!(DisabledAgents::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_failed_save_leaves_the_set_unchanged() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_dir = temp_dir.path().join("state");
+        std::fs::create_dir(&state_dir).unwrap();
+        let disabled = DisabledAgents::load(state_dir.join("disabled_agents.json")).unwrap();
+        disabled.disable("nas", "alice", None).unwrap();
+
+        std::fs::remove_dir_all(&state_dir).unwrap();
+        assert!(disabled.disable("laptop", "bob", None).is_err());
+        assert!(disabled.enable("nas").is_err());
+
+        assert_eq!(disabled.names(), HashSet::from(["nas".to_string()]));
+    }
+}
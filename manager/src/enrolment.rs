@@ -0,0 +1,262 @@
+use axum::{Json, extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use stignore_lib::*;
+
+use super::AppState;
+
+/// Heartbeats missed before an agent counts as offline
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// What an agent last reported about itself
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    pub hostname: String,
+    pub version: String,
+    pub api_version: u32,
+    pub categories: Vec<String>,
+    pub interval: Duration,
+    pub received_at: SystemTime,
+}
+
+impl Heartbeat {
+    fn from_request(request: &HeartbeatRequest) -> Self {
+        Self {
+            hostname: request.hostname.clone(),
+            version: request.version.clone(),
+            api_version: request.api_version,
+            categories: request.categories.clone(),
+            interval: Duration::from_secs(request.heartbeat_interval_seconds.max(1)),
+            received_at: SystemTime::now(),
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.received_at.elapsed().unwrap_or_default() <= self.interval * MISSED_HEARTBEATS
+    }
+}
+
+/// An agent that announced itself but hasn't been approved yet
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingAgent {
+    pub name: String,
+    pub hostname: String,
+    #[serde(skip)]
+    pub api_key: String,
+    pub version: String,
+    #[serde(skip)]
+    pub api_version: u32,
+    pub compatibility_warning: Option<String>,
+    pub categories: Vec<String>,
+    #[serde(skip)]
+    pub interval: Duration,
+    #[serde(skip)]
+    pub first_seen: SystemTime,
+    #[serde(skip)]
+    pub last_seen: SystemTime,
+}
+
+/// Heartbeats and registrations from self-registering agents. Kept in memory only,
+/// agents keep sending heartbeats so everything is back shortly after a restart.
+#[derive(Clone, Default)]
+pub struct Enrolment {
+    heartbeats: Arc<RwLock<HashMap<String, Heartbeat>>>,
+    pending: Arc<RwLock<BTreeMap<String, PendingAgent>>>,
+    rejected: Arc<RwLock<HashSet<String>>>,
+}
+
+impl Enrolment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heartbeat(&self, agent_name: &str) -> Option<Heartbeat> {
+        self.heartbeats.read().unwrap().get(agent_name).cloned()
+    }
+
+    pub fn pending(&self) -> Vec<PendingAgent> {
+        self.pending.read().unwrap().values().cloned().collect()
+    }
+
+    /// Takes an agent out of the pending list so it can be added to the registry
+    pub fn approve(&self, agent_name: &str) -> Option<PendingAgent> {
+        let pending = self.pending.write().unwrap().remove(agent_name)?;
+        self.heartbeats.write().unwrap().insert(
+            pending.name.clone(),
+            Heartbeat {
+                hostname: pending.hostname.clone(),
+                version: pending.version.clone(),
+                api_version: pending.api_version,
+                categories: pending.categories.clone(),
+                interval: pending.interval,
+                received_at: pending.last_seen,
+            },
+        );
+        Some(pending)
+    }
+
+    /// Drops a pending agent and ignores it until the manager restarts
+    pub fn reject(&self, agent_name: &str) -> bool {
+        let removed = self.pending.write().unwrap().remove(agent_name).is_some();
+        if removed {
+            self.rejected
+                .write()
+                .unwrap()
+                .insert(agent_name.to_string());
+        }
+        removed
+    }
+
+    pub fn forget(&self, agent_name: &str) {
+        self.heartbeats.write().unwrap().remove(agent_name);
+    }
+
+    fn record_heartbeat(&self, request: &HeartbeatRequest) {
+        self.heartbeats
+            .write()
+            .unwrap()
+            .insert(request.name.clone(), Heartbeat::from_request(request));
+    }
+
+    fn record_pending(&self, request: &HeartbeatRequest) {
+        let heartbeat = Heartbeat::from_request(request);
+        let now = heartbeat.received_at;
+        let mut pending = self.pending.write().unwrap();
+        let first_seen = pending
+            .get(&request.name)
+            .map(|existing| existing.first_seen)
+            .unwrap_or(now);
+        pending.insert(
+            request.name.clone(),
+            PendingAgent {
+                name: request.name.clone(),
+                hostname: request.hostname.clone(),
+                api_key: request.api_key.clone(),
+                version: request.version.clone(),
+                api_version: request.api_version,
+                compatibility_warning: crate::agent_client::compatibility_warning(
+                    request.api_version,
+                    &request.version,
+                ),
+                categories: request.categories.clone(),
+                interval: heartbeat.interval,
+                first_seen,
+                last_seen: now,
+            },
+        );
+    }
+
+    fn is_rejected(&self, agent_name: &str) -> bool {
+        self.rejected.read().unwrap().contains(agent_name)
+    }
+}
+
+fn heartbeat_response(status: StatusCode, approved: bool, message: String) -> impl IntoResponse {
+    (
+        status,
+        Json(HeartbeatResponse {
+            success: status.is_success(),
+            message,
+            approved,
+        }),
+    )
+}
+
+/// Registration and heartbeat endpoint for agents, authenticated with the shared
+/// enrolment token rather than proxy headers
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<HeartbeatRequest>,
+) -> impl IntoResponse {
+    let Some(expected_token) = &state.config.manager.enrolment_token else {
+        return heartbeat_response(
+            StatusCode::NOT_FOUND,
+            false,
+            "Agent enrolment is not enabled on this manager".to_string(),
+        );
+    };
+    let token = headers
+        .get("X-Enrolment-Token")
+        .and_then(|header| header.to_str().ok());
+    if token != Some(expected_token.as_str()) {
+        tracing::warn!(
+            "Heartbeat from '{}' with a bad enrolment token",
+            request.name
+        );
+        return heartbeat_response(
+            StatusCode::UNAUTHORIZED,
+            false,
+            "Invalid enrolment token".to_string(),
+        );
+    }
+    if request.name.trim().is_empty()
+        || request.hostname.trim().is_empty()
+        || request.api_key.trim().is_empty()
+    {
+        return heartbeat_response(
+            StatusCode::BAD_REQUEST,
+            false,
+            "name, hostname and api_key are required".to_string(),
+        );
+    }
+
+    let Some(agent) = state.agents.get(&request.name) else {
+        if state.enrolment.is_rejected(&request.name) {
+            return heartbeat_response(
+                StatusCode::FORBIDDEN,
+                false,
+                format!("Agent '{}' was rejected by an Admin", request.name),
+            );
+        }
+        state.enrolment.record_pending(&request);
+        return heartbeat_response(
+            StatusCode::OK,
+            false,
+            format!(
+                "Agent '{}' is waiting for an Admin to approve it",
+                request.name
+            ),
+        );
+    };
+
+    // The token is shared, so only the agent itself may move its registration
+    if agent.api_key != request.api_key {
+        tracing::warn!(
+            "Heartbeat for '{}' doesn't match the registered API key",
+            request.name
+        );
+        return heartbeat_response(
+            StatusCode::FORBIDDEN,
+            false,
+            format!(
+                "API key doesn't match the registered agent '{}'",
+                request.name
+            ),
+        );
+    }
+
+    if agent.hostname != request.hostname {
+        let update = crate::registry::AgentUpdate {
+            hostname: Some(request.hostname.clone()),
+            ..Default::default()
+        };
+        match state.agents.update(&agent.name, update) {
+            Ok(_) => {
+                tracing::info!(
+                    "Agent '{}' moved from {} to {}",
+                    agent.name,
+                    agent.hostname,
+                    request.hostname
+                );
+                state.tree_cache.invalidate(&agent.name);
+            }
+            Err(e) => tracing::warn!("Failed to update hostname of '{}': {}", agent.name, e),
+        }
+    }
+
+    state.enrolment.record_heartbeat(&request);
+    heartbeat_response(StatusCode::OK, true, "Heartbeat received".to_string())
+}
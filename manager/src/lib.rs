@@ -1,14 +1,21 @@
 pub mod agent_client;
 pub mod agents;
 pub mod auth;
+pub mod cache;
 pub mod components;
 pub mod config;
+pub mod disabled;
+pub mod enrolment;
 pub mod pages;
+pub mod registry;
 
 use axum::extract::FromRef;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::{Router, routing::get};
+use axum::{
+    Router,
+    routing::{get, post},
+};
 use axum_template::TemplateEngine;
 use tera::Value;
 use tower_http::compression::CompressionLayer;
@@ -85,15 +92,41 @@ pub struct AppState {
     pub context: Context,
     pub config: stignore_lib::ManagerData,
     pub agent_client: agent_client::AgentClient,
-    pub disabled_agents: std::sync::Arc<std::sync::RwLock<std::collections::HashSet<String>>>,
+    pub agents: registry::AgentRegistry,
+    pub disabled_agents: disabled::DisabledAgents,
+    pub enrolment: enrolment::Enrolment,
+    pub tree_cache: cache::TreeCache,
 }
 
+/// Writes next to the target and renames over it, so a crash never leaves half a file.
+/// An existing file keeps its permissions.
+pub(crate) fn write_atomically(path: &std::path::Path, content: &str) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    std::fs::write(&tmp_path, content)?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        std::fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Formats a size in bytes, `humansize(unit="kb")` for values that are still in KB
 pub fn humansize_filter(
-    kb: f64,
-    _kwargs: tera::Kwargs,
+    size: f64,
+    kwargs: tera::Kwargs,
     _state: &tera::State,
 ) -> Result<Value, tera::Error> {
-    let bytes = kb * 1024.0;
+    let bytes = match kwargs.get::<&str>("unit")? {
+        None | Some("b") => size,
+        Some("kb") => size * 1024.0,
+        Some(unit) => {
+            return Err(tera::Error::message(format!(
+                "humansize: unknown unit '{}', expected \"b\" or \"kb\"",
+                unit
+            )));
+        }
+    };
 
     let formatted = if bytes < 1024.0 {
         format!("{:.0} B", bytes)
@@ -120,6 +153,7 @@ pub fn create_app(state: AppState) -> Router {
         .route("/health", get(health_check))
         .route("/healthz", get(health_check))
         .route("/agents", get(pages::agents_overview))
+        .route("/api/v1/heartbeat", post(enrolment::heartbeat))
         .route_service("/favicon.ico", ServeFile::new("assets/favicon.ico"))
         .nest_service("/assets", ServeDir::new("assets"))
         .nest("/components", components::router())
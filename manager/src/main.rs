@@ -1,5 +1,6 @@
 use stignore_manager::{
-    AppState, Context, TeraEngine, agent_client, config, create_app, humansize_filter,
+    AppState, Context, TeraEngine, agent_client, cache, config, create_app, disabled, enrolment,
+    humansize_filter, registry,
 };
 
 use std::env;
@@ -21,7 +22,7 @@ async fn main() {
     let args: Vec<String> = env::args().collect();
     let explicit_config = args.get(1).map(String::as_str);
 
-    let data = config::load_config(explicit_config);
+    let (config_path, data) = config::load_config_with_path(explicit_config);
 
     /* setup templates and context */
     let mut tera = Tera::default();
@@ -32,16 +33,40 @@ async fn main() {
     context.insert("version", env!("CARGO_PKG_VERSION"));
     context.insert("repo_url", "https://github.com/dalmura/stignore-manager");
 
+    /* agents disabled from the UI before the last restart stay disabled */
+    let disabled_agents = match &data.manager.disabled_agents_path {
+        Some(path) => match disabled::DisabledAgents::load(path) {
+            Ok(disabled_agents) => disabled_agents,
+            Err(e) => {
+                tracing::error!("Failed to load disabled agents from {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => disabled::DisabledAgents::new(),
+    };
+
     let app_state = AppState {
         engine: TeraEngine(tera),
         context,
         config: data.clone(),
         agent_client: agent_client::AgentClient::with_timeout(data.manager.agent_timeout_seconds),
-        disabled_agents: std::sync::Arc::new(std::sync::RwLock::new(
-            std::collections::HashSet::new(),
-        )),
+        agents: registry::AgentRegistry::with_config_file(data.agents.clone(), config_path),
+        disabled_agents,
+        enrolment: enrolment::Enrolment::new(),
+        tree_cache: cache::TreeCache::new(),
     };
 
+    /* keep the cached agent trees fresh, the first tick fills the cache at startup */
+    if let Some(interval) = data.manager.tree_refresh_interval() {
+        app_state.tree_cache.spawn_refresh_task(
+            app_state.agent_client.clone(),
+            app_state.agents.clone(),
+            app_state.disabled_agents.clone(),
+            data.manager.aggregation_deadline(),
+            interval,
+        );
+    }
+
     let app = create_app(app_state);
 
     /* bind to the port and listen */
@@ -2,14 +2,16 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse};
 
 use axum_template::{Key, RenderHtml};
 use serde::Serialize;
+use std::time::Instant;
 
 use super::AppState;
-use crate::agents;
+use crate::{agent_client, agents, cache};
 
 #[derive(Serialize)]
 pub struct CategoryInfo {
     pub name: String,
-    pub size_kb: u64,
+    pub size_bytes: u64,
+    pub file_count: u64,
     pub item_count: usize,
     pub stversions_size_kb: u64,
     pub stfolder_present: bool,
@@ -21,12 +23,19 @@ pub struct CategoryInfo {
 pub struct AgentSummary {
     pub name: String,
     pub url: String,
-    pub total_size_kb: u64,
+    pub total_size_bytes: u64,
     pub categories: Vec<CategoryInfo>,
     pub status: String,
     pub status_message: Option<String>,
     pub enabled: bool,
     pub latency_ms: Option<u128>,
+    pub timeout_seconds: Option<u64>,
+    pub last_heartbeat: Option<String>,
+    pub agent_version: Option<String>,
+    pub compatibility_warning: Option<String>,
+    pub disabled_by: Option<String>,
+    pub disabled_ago: Option<String>,
+    pub disabled_reason: Option<String>,
 }
 
 use crate::auth::{self, AuthUser};
@@ -47,79 +56,117 @@ pub async fn root(State(state): State<AppState>, auth_user: AuthUser) -> impl In
 
 pub async fn build_agent_summaries(state: &AppState) -> Vec<AgentSummary> {
     let mut agent_summaries = Vec::new();
-    let disabled_agents = state.disabled_agents.read().unwrap().clone();
+    let disabled_agents = state.disabled_agents.names();
+    let agents = state.agents.list();
 
-    // Test connectivity to each agent individually first
-    for agent in &state.config.agents {
+    // Agents sending heartbeats already told us their version
+    let mut skip_health = disabled_agents.clone();
+    skip_health.extend(
+        agents
+            .iter()
+            .filter(|agent| state.enrolment.heartbeat(&agent.name).is_some())
+            .map(|agent| agent.name.clone()),
+    );
+    let deadline = state.config.manager.aggregation_deadline();
+
+    // Only the per-category totals are fetched, the item trees aren't needed here
+    let summary_client = state.agent_client.clone();
+    let health_client = state.agent_client.clone();
+    let (summaries, health) = tokio::join!(
+        agents::fan_out(&agents, &disabled_agents, deadline, move |agent| {
+            let client = summary_client.clone();
+            async move {
+                let start = Instant::now();
+                let result = client.get_category_summary(&agent).await;
+                (result, start.elapsed())
+            }
+        }),
+        agents::fan_out(&agents, &skip_health, deadline, move |agent| {
+            let client = health_client.clone();
+            async move { client.get_health(&agent).await }
+        }),
+    );
+
+    for ((agent, summary), health) in agents.iter().zip(summaries).zip(health) {
         let is_enabled = !disabled_agents.contains(&agent.name);
-        let mut total_size_kb = 0u64;
+        let disabled = state.disabled_agents.get(&agent.name);
+        let heartbeat = state.enrolment.heartbeat(&agent.name);
+        let (agent_version, compatibility_warning) = match (&heartbeat, health) {
+            (Some(heartbeat), _) => (
+                Some(heartbeat.version.clone()),
+                agent_client::compatibility_warning(heartbeat.api_version, &heartbeat.version),
+            ),
+            (None, Some(Ok(health))) => (
+                Some(health.version.clone()),
+                agent_client::compatibility_warning(health.api_version, &health.version),
+            ),
+            (None, Some(Err(agent_client::AgentError::NotFound(_)))) => {
+                (None, Some(agent_client::missing_health_warning()))
+            }
+            (None, _) => (None, None),
+        };
+        let mut total_size_bytes = 0u64;
         let mut category_infos = Vec::new();
         let mut status_message = None;
         let mut latency_ms = None;
 
         let agent_status = if !is_enabled {
-            status_message = Some("Agent is manually disabled by user".to_string());
+            status_message = Some(match &disabled {
+                Some(disabled) => {
+                    let mut message = format!(
+                        "Disabled by {} {}",
+                        disabled.disabled_by,
+                        cache::format_age(Some(disabled.since()))
+                    );
+                    if let Some(reason) = &disabled.reason {
+                        message.push_str(&format!(": {}", reason));
+                    }
+                    message
+                }
+                None => "Agent is manually disabled by user".to_string(),
+            });
             "Disabled".to_string()
-        } else {
-            let start_time = std::time::Instant::now();
-            match state.agent_client.get_categories(agent).await {
-                Ok(categories_response) => {
-                    latency_ms = Some(start_time.elapsed().as_millis());
-                    // Agent is reachable, now get detailed info for each category
-                    for category in &categories_response.items {
-                        match agents::item_info(
-                            &state.agent_client,
-                            vec![agent.clone()],
-                            vec![category.id.as_str()],
-                            &disabled_agents,
-                        )
-                        .await
+        } else if let Some((result, latency)) = summary {
+            latency_ms = Some(latency.as_millis());
+            match result {
+                Ok(summary_response) => {
+                    for category in summary_response.categories {
+                        // Agents that predate size_bytes only report KB
+                        let size_bytes = category.size_bytes.max(category.size_kb * 1024);
+                        if size_bytes > 0
+                            || category.item_count > 0
+                            || category.stversions_size_kb > 0
                         {
-                            Ok(item_response) => {
-                                if let Some((_, item_group)) = item_response
-                                    .agent_items
-                                    .iter()
-                                    .find(|(a, _)| a.name == agent.name)
-                                {
-                                    let size_kb = item_group.size_kb;
-                                    let item_count = item_group.items.len();
-
-                                    if size_kb > 0
-                                        || item_count > 0
-                                        || item_group.stversions_size_kb > 0
-                                    {
-                                        category_infos.push(CategoryInfo {
-                                            name: category.name.clone(),
-                                            size_kb,
-                                            item_count,
-                                            stversions_size_kb: item_group.stversions_size_kb,
-                                            stfolder_present: item_group.stfolder_present,
-                                            has_conflicts: item_group.has_conflicts,
-                                            is_syncing: item_group.is_syncing,
-                                        });
-                                    }
-
-                                    total_size_kb += size_kb;
-                                }
-                            }
-                            Err(_) => {
-                                // Skip this category if there's an error
-                                continue;
-                            }
+                            category_infos.push(CategoryInfo {
+                                name: category.name,
+                                size_bytes,
+                                file_count: category.file_count,
+                                item_count: category.item_count,
+                                stversions_size_kb: category.stversions_size_kb,
+                                stfolder_present: category.stfolder_present,
+                                has_conflicts: category.has_conflicts,
+                                is_syncing: category.is_syncing,
+                            });
                         }
+
+                        total_size_bytes += size_bytes;
                     }
 
                     // Sort categories by name
                     category_infos.sort_by(|a, b| a.name.cmp(&b.name));
 
-                    if total_size_kb > 0 {
+                    if total_size_bytes > 0 {
                         "Active".to_string()
                     } else {
                         "Empty".to_string()
                     }
                 }
                 Err(e) => {
-                    latency_ms = Some(start_time.elapsed().as_millis());
+                    tracing::warn!(
+                        "Failed to get category summary from agent '{}': {}",
+                        agent.name,
+                        e
+                    );
                     // Agent is not reachable, determine the type of error
                     let status = match e {
                         crate::agent_client::AgentError::Timeout(_) => "Timeout".to_string(),
@@ -127,14 +174,18 @@ pub async fn build_agent_summaries(state: &AppState) -> Vec<AgentSummary> {
                             "Unreachable".to_string()
                         }
                         crate::agent_client::AgentError::InvalidResponse(_) => "Error".to_string(),
-                        crate::agent_client::AgentError::OperationFailed(_) => "Error".to_string(),
+                        crate::agent_client::AgentError::OperationFailed(_)
+                        | crate::agent_client::AgentError::NotFound(_)
+                        | crate::agent_client::AgentError::Conflict(_) => "Error".to_string(),
                     };
 
                     status_message = Some(match e {
                         crate::agent_client::AgentError::Timeout(_) => {
                             format!(
                                 "Request timed out after {} seconds",
-                                state.config.manager.agent_timeout_seconds
+                                agent
+                                    .timeout_seconds
+                                    .unwrap_or(state.config.manager.agent_timeout_seconds)
                             )
                         }
                         crate::agent_client::AgentError::RequestFailed(_) => {
@@ -146,22 +197,45 @@ pub async fn build_agent_summaries(state: &AppState) -> Vec<AgentSummary> {
                         crate::agent_client::AgentError::OperationFailed(msg) => {
                             format!("Operation failed: {}", msg)
                         }
+                        crate::agent_client::AgentError::NotFound(msg) => {
+                            format!("Not found: {}", msg)
+                        }
+                        crate::agent_client::AgentError::Conflict(msg) => {
+                            format!("Refused: {}", msg)
+                        }
                     });
 
                     status
                 }
             }
+        } else {
+            status_message = Some(format!(
+                "No response within {} seconds",
+                state.config.manager.aggregation_deadline_seconds
+            ));
+            "Timeout".to_string()
         };
 
         let summary = AgentSummary {
             name: agent.name.clone(),
             url: agent.hostname.clone(),
-            total_size_kb,
+            total_size_bytes,
             categories: category_infos,
             status: agent_status,
             status_message,
             enabled: is_enabled,
             latency_ms,
+            timeout_seconds: agent.timeout_seconds,
+            last_heartbeat: heartbeat
+                .as_ref()
+                .map(|heartbeat| cache::format_age(Some(heartbeat.received_at))),
+            agent_version,
+            compatibility_warning,
+            disabled_ago: disabled
+                .as_ref()
+                .map(|disabled| cache::format_age(Some(disabled.since()))),
+            disabled_by: disabled.as_ref().map(|d| d.disabled_by.clone()),
+            disabled_reason: disabled.and_then(|d| d.reason),
         };
         agent_summaries.push(summary);
     }
@@ -182,6 +256,7 @@ pub async fn agents_overview(
 
     let agent_summaries = build_agent_summaries(&state).await;
     context.insert("agents", &agent_summaries);
+    context.insert("pending_agents", &state.enrolment.pending());
 
     RenderHtml(
         Key("pages/agents_overview.html".to_string()),
@@ -190,6 +265,15 @@ pub async fn agents_overview(
     )
 }
 
+/// Adds how old the cached agent trees are, shown next to the "refresh now" button
+pub fn insert_cache_age(context: &mut crate::Context, state: &AppState) {
+    let disabled_agents = state.disabled_agents.names();
+    let refreshed = state
+        .tree_cache
+        .last_refreshed(&state.agents.list(), &disabled_agents);
+    context.insert("last_refreshed", &cache::format_age(refreshed));
+}
+
 pub async fn not_found(State(state): State<AppState>, auth_user: AuthUser) -> impl IntoResponse {
     let mut context = state.context.clone();
     auth::inject_auth_context(&mut context, &auth_user);
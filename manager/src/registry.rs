@@ -0,0 +1,369 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use stignore_lib::{Agent, expand_env_vars};
+
+#[derive(Debug)]
+pub enum RegistryError {
+    NotFound(String),
+    AlreadyExists(String),
+    Invalid(String),
+    Persist(std::io::Error),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::NotFound(name) => write!(f, "Agent '{}' not found", name),
+            RegistryError::AlreadyExists(name) => write!(f, "Agent '{}' already exists", name),
+            RegistryError::Invalid(msg) => write!(f, "Invalid agent: {}", msg),
+            RegistryError::Persist(e) => write!(f, "Failed to save the config file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Fields that can be changed on an existing agent, None keeps the current value
+#[derive(Debug, Default)]
+pub struct AgentUpdate {
+    pub hostname: Option<String>,
+    pub api_key: Option<String>,
+    pub timeout_seconds: Option<Option<u64>>,
+}
+
+/// The agents the manager talks to. Starts out as the `[[agents]]` of the config file
+/// and can be changed at runtime, every change is written back to that file before it
+/// takes effect.
+#[derive(Clone, Default)]
+pub struct AgentRegistry {
+    agents: Arc<RwLock<Vec<Agent>>>,
+    config_path: Option<PathBuf>,
+}
+
+impl AgentRegistry {
+    /// Keeps runtime changes in memory only
+    pub fn new(agents: Vec<Agent>) -> Self {
+        Self {
+            agents: Arc::new(RwLock::new(agents)),
+            config_path: None,
+        }
+    }
+
+    pub fn with_config_file(agents: Vec<Agent>, config_path: impl Into<PathBuf>) -> Self {
+        Self {
+            agents: Arc::new(RwLock::new(agents)),
+            config_path: Some(config_path.into()),
+        }
+    }
+
+    pub fn list(&self) -> Vec<Agent> {
+        self.agents.read().unwrap().clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Agent> {
+        self.agents
+            .read()
+            .unwrap()
+            .iter()
+            .find(|agent| agent.name == name)
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn add(&self, agent: Agent) -> Result<(), RegistryError> {
+        validate(&agent)?;
+        let mut agents = self.agents.write().unwrap();
+        if agents.iter().any(|existing| existing.name == agent.name) {
+            return Err(RegistryError::AlreadyExists(agent.name));
+        }
+
+        let mut updated = agents.clone();
+        updated.push(agent);
+        self.save(&updated)?;
+        *agents = updated;
+        Ok(())
+    }
+
+    pub fn update(&self, name: &str, update: AgentUpdate) -> Result<Agent, RegistryError> {
+        let mut agents = self.agents.write().unwrap();
+        let mut updated = agents.clone();
+        let agent = updated
+            .iter_mut()
+            .find(|agent| agent.name == name)
+            .ok_or_else(|| RegistryError::NotFound(name.to_string()))?;
+
+        if let Some(hostname) = update.hostname {
+            agent.hostname = hostname;
+        }
+        if let Some(api_key) = update.api_key {
+            agent.api_key = api_key;
+        }
+        if let Some(timeout_seconds) = update.timeout_seconds {
+            agent.timeout_seconds = timeout_seconds;
+        }
+        validate(agent)?;
+
+        let agent = agent.clone();
+        self.save(&updated)?;
+        *agents = updated;
+        Ok(agent)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<Agent, RegistryError> {
+        let mut agents = self.agents.write().unwrap();
+        let index = agents
+            .iter()
+            .position(|agent| agent.name == name)
+            .ok_or_else(|| RegistryError::NotFound(name.to_string()))?;
+
+        let mut updated = agents.clone();
+        let removed = updated.remove(index);
+        self.save(&updated)?;
+        *agents = updated;
+        Ok(removed)
+    }
+
+    fn save(&self, agents: &[Agent]) -> Result<(), RegistryError> {
+        match &self.config_path {
+            Some(path) => write_agents(path, agents).map_err(RegistryError::Persist),
+            None => Ok(()),
+        }
+    }
+}
+
+fn validate(agent: &Agent) -> Result<(), RegistryError> {
+    if agent.name.trim().is_empty() {
+        return Err(RegistryError::Invalid("name is required".to_string()));
+    }
+    if agent.hostname.trim().is_empty() {
+        return Err(RegistryError::Invalid("hostname is required".to_string()));
+    }
+    if agent.api_key.trim().is_empty() {
+        return Err(RegistryError::Invalid("api_key is required".to_string()));
+    }
+    if agent.timeout_seconds == Some(0) {
+        return Err(RegistryError::Invalid(
+            "timeout_seconds must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces the `[[agents]]` of the config file, leaving the rest of it as is, comments
+/// and layout included. The file is parsed before env var expansion so `${VAR}`
+/// references survive, as long as they still expand to the value in use.
+fn write_agents(path: &Path, agents: &[Agent]) -> std::io::Result<()> {
+    let raw = std::fs::read_to_string(path)?;
+    let mut document: toml_edit::DocumentMut = raw
+        .parse()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let previous = document
+        .get("agents")
+        .and_then(|agents| agents.as_array_of_tables())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut entries = toml_edit::ArrayOfTables::new();
+    for agent in agents {
+        // Existing entries are edited in place to keep their comments and key order
+        let mut entry = previous
+            .iter()
+            .find(|entry| {
+                entry
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .is_some_and(|name| expand_env_vars(name) == agent.name)
+            })
+            .cloned()
+            .unwrap_or_default();
+
+        set_unless_equivalent(&mut entry, "name", &agent.name);
+        set_unless_equivalent(&mut entry, "hostname", &agent.hostname);
+        set_unless_equivalent(&mut entry, "api_key", &agent.api_key);
+        match agent.timeout_seconds {
+            Some(timeout_seconds) => {
+                let timeout_seconds = timeout_seconds as i64;
+                if entry.get("timeout_seconds").and_then(|t| t.as_integer())
+                    != Some(timeout_seconds)
+                {
+                    entry["timeout_seconds"] = toml_edit::value(timeout_seconds);
+                }
+            }
+            None => {
+                entry.remove("timeout_seconds");
+            }
+        }
+        entries.push(entry);
+    }
+
+    // An empty array of tables isn't written at all, but the config needs the key
+    document["agents"] = if entries.is_empty() {
+        toml_edit::value(toml_edit::Array::new())
+    } else {
+        toml_edit::Item::ArrayOfTables(entries)
+    };
+
+    crate::write_atomically(path, &document.to_string())
+}
+
+/// Sets a string unless the file already holds it, possibly as an env var reference
+fn set_unless_equivalent(entry: &mut toml_edit::Table, key: &str, value: &str) {
+    let unchanged = entry
+        .get(key)
+        .and_then(|raw| raw.as_str())
+        .is_some_and(|raw| expand_env_vars(raw) == value);
+    if !unchanged {
+        entry[key] = toml_edit::value(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(name: &str, hostname: &str) -> Agent {
+        Agent {
+            name: name.to_string(),
+            hostname: hostname.to_string(),
+            api_key: "key".to_string(),
+            timeout_seconds: None,
+        }
+    }
+
+    #[test]
+    fn test_changes_are_written_back_to_the_config_file() {
+        unsafe {
+            std::env::set_var("TEST_REGISTRY_NAS_KEY", "nas-secret");
+        }
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[manager]
+port = 8000
+minimum_copies = 2
+
+[[agents]]
+name = "nas"
+hostname = "nas:3001"
+api_key = "${TEST_REGISTRY_NAS_KEY}"
+"#,
+        )
+        .unwrap();
+
+        let data = stignore_lib::load_manager_config(path.to_str().unwrap()).unwrap();
+        let registry = AgentRegistry::with_config_file(data.agents, &path);
+
+        registry.add(agent("laptop", "laptop:3001")).unwrap();
+        registry
+            .update(
+                "nas",
+                AgentUpdate {
+                    hostname: Some("nas.lan:3001".to_string()),
+                    timeout_seconds: Some(Some(30)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("${TEST_REGISTRY_NAS_KEY}"));
+        assert!(!written.contains("nas-secret"));
+
+        let reloaded = stignore_lib::load_manager_config(path.to_str().unwrap()).unwrap();
+        assert_eq!(reloaded.manager.port, 8000);
+        assert_eq!(reloaded.agents.len(), 2);
+        assert_eq!(reloaded.agents[0].hostname, "nas.lan:3001");
+        assert_eq!(reloaded.agents[0].api_key, "nas-secret");
+        assert_eq!(reloaded.agents[0].timeout_seconds, Some(30));
+        assert_eq!(reloaded.agents[1].name, "laptop");
+
+        registry.remove("laptop").unwrap();
+        let reloaded = stignore_lib::load_manager_config(path.to_str().unwrap()).unwrap();
+        assert_eq!(reloaded.agents.len(), 1);
+
+        unsafe {
+            std::env::remove_var("TEST_REGISTRY_NAS_KEY");
+        }
+    }
+
+    #[test]
+    fn test_writing_agents_keeps_the_rest_of_the_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"# stignore manager
+[manager]
+# Keep two copies of everything
+minimum_copies = 2
+port = 8000
+
+[[agents]]
+name = "nas"
+# The NAS in the cupboard
+hostname = "nas:3001"
+api_key = "key"
+"#,
+        )
+        .unwrap();
+
+        let data = stignore_lib::load_manager_config(path.to_str().unwrap()).unwrap();
+        let registry = AgentRegistry::with_config_file(data.agents, &path);
+        registry.add(agent("laptop", "laptop:3001")).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(
+            "# stignore manager\n[manager]\n# Keep two copies of everything\nminimum_copies = 2\nport = 8000\n"
+        ));
+        assert!(written.contains("# The NAS in the cupboard\nhostname = \"nas:3001\""));
+        assert!(written.contains("name = \"laptop\""));
+
+        registry.remove("nas").unwrap();
+        registry.remove("laptop").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("# Keep two copies of everything"));
+        let reloaded = stignore_lib::load_manager_config(path.to_str().unwrap()).unwrap();
+        assert!(reloaded.agents.is_empty());
+    }
+
+    #[test]
+    fn test_rejected_changes_leave_the_registry_untouched() {
+        let registry = AgentRegistry::new(vec![agent("nas", "nas:3001")]);
+
+        assert!(matches!(
+            registry.add(agent("nas", "other:3001")),
+            Err(RegistryError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            registry.add(agent("laptop", " ")),
+            Err(RegistryError::Invalid(_))
+        ));
+        assert!(matches!(
+            registry.update(
+                "nas",
+                AgentUpdate {
+                    timeout_seconds: Some(Some(0)),
+                    ..Default::default()
+                }
+            ),
+            Err(RegistryError::Invalid(_))
+        ));
+        assert!(matches!(
+            registry.remove("laptop"),
+            Err(RegistryError::NotFound(_))
+        ));
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get("nas").unwrap().hostname, "nas:3001");
+        assert_eq!(registry.get("nas").unwrap().timeout_seconds, None);
+    }
+}
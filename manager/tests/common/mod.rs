@@ -8,7 +8,10 @@ use wiremock::matchers::{header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 use stignore_lib::*;
-use stignore_manager::{AppState, Context, TeraEngine, agent_client, humansize_filter};
+use stignore_manager::{
+    AppState, Context, TeraEngine, agent_client, cache, disabled, enrolment, humansize_filter,
+    registry,
+};
 
 pub fn create_test_config() -> ManagerData {
     ManagerData {
@@ -16,6 +19,10 @@ pub fn create_test_config() -> ManagerData {
             port: 8080,
             minimum_copies: 2,
             agent_timeout_seconds: 5,
+            aggregation_deadline_seconds: 10,
+            tree_refresh_interval_seconds: 60,
+            disabled_agents_path: None,
+            enrolment_token: None,
             auth: AuthConfig::default(),
         },
         agents: vec![
@@ -23,11 +30,13 @@ pub fn create_test_config() -> ManagerData {
                 name: "test-agent-1".to_string(),
                 hostname: "localhost:3001".to_string(),
                 api_key: "test-key-1".to_string(),
+                timeout_seconds: None,
             },
             Agent {
                 name: "test-agent-2".to_string(),
                 hostname: "localhost:3002".to_string(),
                 api_key: "test-key-2".to_string(),
+                timeout_seconds: None,
             },
         ],
     }
@@ -70,9 +79,10 @@ pub fn create_test_app_state(config: ManagerData) -> AppState {
         context,
         config: config.clone(),
         agent_client: agent_client::AgentClient::with_timeout(config.manager.agent_timeout_seconds),
-        disabled_agents: std::sync::Arc::new(std::sync::RwLock::new(
-            std::collections::HashSet::new(),
-        )),
+        agents: registry::AgentRegistry::new(config.agents.clone()),
+        disabled_agents: disabled::DisabledAgents::new(),
+        enrolment: enrolment::Enrolment::new(),
+        tree_cache: cache::TreeCache::new(),
     }
 }
 
@@ -103,6 +113,19 @@ pub fn create_mock_category_response() -> AgentCategoryListingResponse {
     AgentCategoryListingResponse { items: vec![item1] }
 }
 
+pub fn create_mock_category_summary_response() -> AgentCategorySummaryResponse {
+    AgentCategorySummaryResponse {
+        categories: vec![CategorySummary {
+            id: "Movies".to_string(),
+            name: "Movies".to_string(),
+            size_kb: 1024,
+            item_count: 1,
+            stfolder_present: true,
+            ..Default::default()
+        }],
+    }
+}
+
 pub fn create_mock_item_info_response() -> AgentItemInfoResponse {
     let item = ItemGroup {
         id: "Movies/Action/movie.mkv".to_string(),
@@ -117,9 +140,34 @@ pub fn create_mock_item_info_response() -> AgentItemInfoResponse {
     AgentItemInfoResponse { item }
 }
 
+pub fn create_mock_health_response(api_version: u32) -> AgentHealthResponse {
+    AgentHealthResponse {
+        status: "ok".to_string(),
+        version: "0.1.0-test".to_string(),
+        api_version,
+        uptime_seconds: 60,
+        categories: vec![HealthCategory {
+            id: "Movies".to_string(),
+            name: "Movies".to_string(),
+        }],
+        features: vec![],
+    }
+}
+
 pub async fn setup_mock_agent_server() -> MockServer {
     let mock_server = MockServer::start().await;
 
+    // Mock health endpoint
+    Mock::given(method("GET"))
+        .and(path("/api/v1/health"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(create_mock_health_response(AGENT_API_VERSION)),
+        )
+        .mount(&mock_server)
+        .await;
+
     // Mock categories endpoint
     Mock::given(method("GET"))
         .and(path("/api/v1/categories"))
@@ -128,6 +176,16 @@ pub async fn setup_mock_agent_server() -> MockServer {
         .mount(&mock_server)
         .await;
 
+    // Mock category summary endpoint
+    Mock::given(method("GET"))
+        .and(path("/api/v1/summary"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(create_mock_category_summary_response()),
+        )
+        .mount(&mock_server)
+        .await;
+
     // Mock item info endpoint
     Mock::given(method("POST"))
         .and(path("/api/v1/items"))
@@ -156,12 +214,17 @@ pub fn create_test_config_with_mock_server(server_uri: &str) -> ManagerData {
             port: 8080,
             minimum_copies: 2,
             agent_timeout_seconds: 5,
+            aggregation_deadline_seconds: 10,
+            tree_refresh_interval_seconds: 60,
+            disabled_agents_path: None,
+            enrolment_token: None,
             auth: AuthConfig::default(),
         },
         agents: vec![Agent {
             name: "test-agent-1".to_string(),
             hostname: server_uri.replace("http://", ""),
             api_key: "test-key-1".to_string(),
+            timeout_seconds: None,
         }],
     }
 }
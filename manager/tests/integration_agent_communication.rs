@@ -20,6 +20,18 @@ async fn test_agent_category_listing_success() {
     assert_eq!(response.items[0].name, "Movies");
 }
 
+#[tokio::test]
+async fn test_agent_health_success() {
+    let mock_server = setup_mock_agent_server().await;
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let client = stignore_manager::agent_client::AgentClient::with_timeout(5);
+
+    let health = client.get_health(&config.agents[0]).await.unwrap();
+    assert_eq!(health.status, "ok");
+    assert_eq!(health.api_version, AGENT_API_VERSION);
+    assert_eq!(health.categories[0].id, "Movies");
+}
+
 #[tokio::test]
 async fn test_agent_item_info_success() {
     let mock_server = setup_mock_agent_server().await;
@@ -46,6 +58,7 @@ async fn test_agent_ignore_item_success() {
     let request = AgentIgnoreRequest {
         category_id: "Movies".to_string(),
         folder_path: vec!["Action".to_string(), "movie.mkv".to_string()],
+        dry_run: false,
     };
 
     let result = client.ignore_item(&config.agents[0], &request).await;
@@ -161,6 +174,10 @@ async fn test_multi_agent_aggregation_success() {
             port: 8080,
             minimum_copies: 2,
             agent_timeout_seconds: 5,
+            aggregation_deadline_seconds: 10,
+            tree_refresh_interval_seconds: 60,
+            disabled_agents_path: None,
+            enrolment_token: None,
             auth: AuthConfig::default(),
         },
         agents: vec![
@@ -168,21 +185,34 @@ async fn test_multi_agent_aggregation_success() {
                 name: "agent-1".to_string(),
                 hostname: mock_server1.uri().replace("http://", ""),
                 api_key: "test-key-1".to_string(),
+                timeout_seconds: None,
             },
             Agent {
                 name: "agent-2".to_string(),
                 hostname: mock_server2.uri().replace("http://", ""),
                 api_key: "test-key-2".to_string(),
+                timeout_seconds: None,
             },
         ],
     };
 
     let client = stignore_manager::agent_client::AgentClient::with_timeout(5);
     let disabled = std::collections::HashSet::new();
-    let result = stignore_manager::agents::list_categories(&client, config.agents, &disabled).await;
+    let result = stignore_manager::agents::list_categories(
+        &client,
+        config.agents,
+        &disabled,
+        Duration::from_secs(10),
+    )
+    .await;
 
     // Should aggregate data from both agents
     assert!(!result.items.is_empty());
+    assert_eq!(result.items[0].copy_count, 2);
+    assert_eq!(
+        result.items[0].present_on.iter().collect::<Vec<_>>(),
+        vec!["agent-1", "agent-2"]
+    );
 }
 
 #[tokio::test]
@@ -203,6 +233,10 @@ async fn test_agent_partial_failure_handling() {
             port: 8080,
             minimum_copies: 2,
             agent_timeout_seconds: 5,
+            aggregation_deadline_seconds: 10,
+            tree_refresh_interval_seconds: 60,
+            disabled_agents_path: None,
+            enrolment_token: None,
             auth: AuthConfig::default(),
         },
         agents: vec![
@@ -210,19 +244,120 @@ async fn test_agent_partial_failure_handling() {
                 name: "good-agent".to_string(),
                 hostname: mock_server_good.uri().replace("http://", ""),
                 api_key: "test-key-1".to_string(),
+                timeout_seconds: None,
             },
             Agent {
                 name: "bad-agent".to_string(),
                 hostname: mock_server_bad.uri().replace("http://", ""),
                 api_key: "test-key-1".to_string(),
+                timeout_seconds: None,
             },
         ],
     };
 
     let client = stignore_manager::agent_client::AgentClient::with_timeout(5);
     let disabled = std::collections::HashSet::new();
-    let result = stignore_manager::agents::list_categories(&client, config.agents, &disabled).await;
+    let result = stignore_manager::agents::list_categories(
+        &client,
+        config.agents,
+        &disabled,
+        Duration::from_secs(10),
+    )
+    .await;
 
     // Should still return data from the good agent
     assert!(!result.items.is_empty());
+    assert_eq!(
+        result.items[0].present_on.iter().collect::<Vec<_>>(),
+        vec!["good-agent"]
+    );
+}
+
+async fn mock_categories_with_delay(category_id: &str, delay: Duration) -> MockServer {
+    let mock_server = MockServer::start().await;
+    let response = AgentCategoryListingResponse {
+        items: vec![ItemGroup {
+            id: category_id.to_string(),
+            name: category_id.to_string(),
+            size_kb: 1,
+            ..Default::default()
+        }],
+    };
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(response)
+                .set_delay(delay),
+        )
+        .mount(&mock_server)
+        .await;
+
+    mock_server
+}
+
+fn agent_for(name: &str, mock_server: &MockServer) -> Agent {
+    Agent {
+        name: name.to_string(),
+        hostname: mock_server.uri().replace("http://", ""),
+        api_key: "test-key-1".to_string(),
+        timeout_seconds: None,
+    }
+}
+
+#[tokio::test]
+async fn test_list_categories_queries_agents_concurrently_in_config_order() {
+    // The first agent answers last, its results must still come first
+    let slow = mock_categories_with_delay("Slow", Duration::from_millis(700)).await;
+    let fast = mock_categories_with_delay("Fast", Duration::from_millis(500)).await;
+    let agents = vec![
+        agent_for("slow-agent", &slow),
+        agent_for("fast-agent", &fast),
+    ];
+
+    let client = stignore_manager::agent_client::AgentClient::with_timeout(5);
+    let disabled = std::collections::HashSet::new();
+    let start = std::time::Instant::now();
+    let result = stignore_manager::agents::list_categories(
+        &client,
+        agents,
+        &disabled,
+        Duration::from_secs(10),
+    )
+    .await;
+
+    // Sequential requests would take at least 1.2s
+    assert!(start.elapsed() < Duration::from_millis(1100));
+    assert_eq!(result.agent_items.len(), 2);
+    assert_eq!(result.agent_items[0].items[0].id, "Slow");
+    assert_eq!(result.agent_items[1].items[0].id, "Fast");
+    assert_eq!(result.items.len(), 2);
+}
+
+#[tokio::test]
+async fn test_list_categories_gives_up_on_agents_after_deadline() {
+    let stuck = mock_categories_with_delay("Stuck", Duration::from_secs(4)).await;
+    let fast = mock_categories_with_delay("Fast", Duration::ZERO).await;
+    let agents = vec![
+        agent_for("stuck-agent", &stuck),
+        agent_for("fast-agent", &fast),
+    ];
+
+    let client = stignore_manager::agent_client::AgentClient::with_timeout(5);
+    let disabled = std::collections::HashSet::new();
+    let start = std::time::Instant::now();
+    let result = stignore_manager::agents::list_categories(
+        &client,
+        agents,
+        &disabled,
+        Duration::from_millis(500),
+    )
+    .await;
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(result.agent_items.len(), 2);
+    assert!(result.agent_items[0].items.is_empty());
+    assert_eq!(result.agent_items[1].items[0].id, "Fast");
+    assert_eq!(result.items.len(), 1);
 }
@@ -168,3 +168,34 @@ async fn test_auth_custom_headers_and_roles() {
 
     assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
 }
+
+#[tokio::test]
+async fn test_agent_registry_requires_admin() {
+    let config = create_auth_config(true);
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    for (endpoint, body) in [
+        (
+            "/components/agents/add",
+            json!({"agent_name": "nas", "hostname": "nas:3001", "api_key": "key"}),
+        ),
+        (
+            "/components/agents/edit",
+            json!({"agent_name": "test-agent-1", "hostname": "elsewhere:3001"}),
+        ),
+        (
+            "/components/agents/remove",
+            json!({"agent_name": "test-agent-1"}),
+        ),
+    ] {
+        let response = server
+            .post(endpoint)
+            .add_header("X-Proxy-User", "bob")
+            .add_header("X-Proxy-Role", "Reader")
+            .json(&body)
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use common::*;
+use serde_json::json;
+use stignore_lib::*;
+
+fn enrolment_server() -> TestServer {
+    let mut config = create_test_config();
+    config.manager.enrolment_token = Some("enrol-me".to_string());
+    config.agents.clear();
+    TestServer::new(create_test_app(config)).unwrap()
+}
+
+fn laptop_heartbeat(hostname: &str, api_key: &str) -> serde_json::Value {
+    json!({
+        "name": "laptop",
+        "hostname": hostname,
+        "api_key": api_key,
+        "version": "0.1.0",
+        "api_version": AGENT_API_VERSION,
+        "categories": ["Movies"],
+        "heartbeat_interval_seconds": 30
+    })
+}
+
+#[tokio::test]
+async fn test_heartbeat_requires_enrolment_to_be_configured() {
+    let app = create_test_app(create_test_config());
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("10.0.0.5:3000", "laptop-key"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_heartbeat_rejects_a_bad_token() {
+    let server = enrolment_server();
+
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "guess")
+        .json(&laptop_heartbeat("10.0.0.5:3000", "laptop-key"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    let body: HeartbeatResponse = response.json();
+    assert!(!body.success);
+}
+
+#[tokio::test]
+async fn test_registered_agent_is_approved_and_followed_across_ip_changes() {
+    let server = enrolment_server();
+
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("10.0.0.5:3000", "laptop-key"))
+        .await;
+    response.assert_status_ok();
+    let body: HeartbeatResponse = response.json();
+    assert!(body.success);
+    assert!(!body.approved);
+
+    let table = server.get("/components/agents-table.html").await;
+    table.assert_text_contains("Awaiting approval");
+    table.assert_text_contains("10.0.0.5:3000");
+
+    let response = server
+        .post("/components/agents/approve")
+        .json(&json!({"agent_name": "laptop"}))
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    // The laptop moved networks, nothing listens there but the heartbeat counts as online
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("192.168.1.20:3000", "laptop-key"))
+        .await;
+    let body: HeartbeatResponse = response.json();
+    assert!(body.approved);
+
+    let table = server.get("/components/agents-table.html").await;
+    assert!(!table.text().contains("Awaiting approval"));
+    table.assert_text_contains("192.168.1.20:3000");
+
+    let pill = server.get("/components/agent-status-pill.html").await;
+    pill.assert_text_contains("1/1 Online");
+
+    // Someone else with the token can't take over the registration
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("203.0.113.9:3000", "stolen-key"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_rejected_agent_stays_out() {
+    let server = enrolment_server();
+
+    server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("10.0.0.5:3000", "laptop-key"))
+        .await
+        .assert_status_ok();
+
+    let response = server
+        .post("/components/agents/reject")
+        .json(&json!({"agent_name": "laptop"}))
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    let response = server
+        .post("/api/v1/heartbeat")
+        .add_header("X-Enrolment-Token", "enrol-me")
+        .json(&laptop_heartbeat("10.0.0.5:3000", "laptop-key"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let table = server.get("/components/agents-table.html").await;
+    assert!(!table.text().contains("Awaiting approval"));
+}
@@ -4,7 +4,7 @@ use axum_test::TestServer;
 use common::*;
 use serde_json::json;
 use stignore_lib::*;
-use wiremock::matchers::{header, method, path};
+use wiremock::matchers::{body_partial_json, header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 #[tokio::test]
@@ -87,7 +87,9 @@ async fn test_agent_modal_endpoint() {
 #[tokio::test]
 async fn test_ignore_item_endpoint() {
     let mock_server = setup_mock_agent_server().await;
-    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let mut config = create_test_config_with_mock_server(&mock_server.uri());
+    // The only agent holds the movie, without a minimum the ignore goes through
+    config.manager.minimum_copies = 0;
     let app = create_test_app(config);
     let server = TestServer::new(app).unwrap();
 
@@ -104,6 +106,210 @@ async fn test_ignore_item_endpoint() {
     assert_eq!(body["success"], true);
 }
 
+#[tokio::test]
+async fn test_tree_is_refetched_after_an_ignore() {
+    let mock_server = setup_mock_agent_server().await;
+    let mut config = create_test_config_with_mock_server(&mock_server.uri());
+    // The only agent holds the movie, without a minimum the ignore goes through
+    config.manager.minimum_copies = 0;
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let listings = || async {
+        mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == "/api/v1/categories")
+            .count()
+    };
+
+    server
+        .get("/components/itemlist.html")
+        .await
+        .assert_status_ok();
+    server
+        .get("/components/itemlist.html")
+        .await
+        .assert_status_ok();
+    assert_eq!(listings().await, 1);
+
+    let response = server
+        .post("/components/ignore")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "item_path": ["Movies", "Action", "movie.mkv"]
+        }))
+        .await;
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+
+    server
+        .get("/components/itemlist.html")
+        .await
+        .assert_status_ok();
+    assert_eq!(listings().await, 2);
+}
+
+#[tokio::test]
+async fn test_ignore_refused_when_it_would_drop_the_last_copy() {
+    let mock_server = setup_mock_agent_server().await;
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    // Movies/Action only exists on test-agent-1
+    let request_body = json!({
+        "agent_name": "test-agent-1",
+        "item_path": ["Movies", "Action"]
+    });
+    let response = server.post("/components/ignore").json(&request_body).await;
+
+    response.assert_status(axum::http::StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["below_minimum_copies"], true);
+    assert_eq!(body["minimum_copies"], 2);
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("no synced copies would remain")
+    );
+
+    let request_body = json!({
+        "agent_names": ["test-agent-1"],
+        "item_path": ["Movies", "Action"]
+    });
+    let response = server
+        .post("/components/bulk-ignore")
+        .json(&request_body)
+        .await;
+    response.assert_status(axum::http::StatusCode::CONFLICT);
+
+    // An explicit override goes through
+    let request_body = json!({
+        "agent_name": "test-agent-1",
+        "item_path": ["Movies", "Action"],
+        "override_minimum_copies": true
+    });
+    let response = server.post("/components/ignore").json(&request_body).await;
+
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+    assert!(
+        body["minimum_copies_override"]["reason"]
+            .as_str()
+            .unwrap()
+            .contains("no synced copies would remain")
+    );
+}
+
+#[tokio::test]
+async fn test_delete_refused_when_the_target_can_not_be_checked() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/items"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("disk error"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/delete"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Deleted"
+        })))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/delete")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "item_path": ["Movies", "Action"]
+        }))
+        .await;
+
+    response.assert_status(axum::http::StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("couldn't tell whether test-agent-1 holds a synced copy")
+    );
+}
+
+#[tokio::test]
+async fn test_delete_refused_for_a_child_below_the_minimum() {
+    let mock_server = MockServer::start().await;
+
+    // The category is on both agents but Action only on the first
+    for (api_key, children) in [
+        ("test-key-1", vec!["Action", "Drama"]),
+        ("test-key-2", vec!["Drama"]),
+    ] {
+        let items = children
+            .into_iter()
+            .map(|name| ItemGroup {
+                id: name.to_string(),
+                name: name.to_string(),
+                size_kb: 1,
+                ..Default::default()
+            })
+            .collect();
+        Mock::given(method("POST"))
+            .and(path("/api/v1/items"))
+            .and(header("X-API-Key", api_key))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(AgentItemInfoResponse {
+                    item: ItemGroup {
+                        id: "Movies".to_string(),
+                        name: "Movies".to_string(),
+                        items,
+                        ..Default::default()
+                    },
+                }),
+            )
+            .mount(&mock_server)
+            .await;
+    }
+
+    let mut config = create_test_config_with_mock_server(&mock_server.uri());
+    config.manager.minimum_copies = 1;
+    config.agents.push(Agent {
+        name: "test-agent-2".to_string(),
+        hostname: config.agents[0].hostname.clone(),
+        api_key: "test-key-2".to_string(),
+        timeout_seconds: None,
+    });
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let request_body = json!({
+        "agent_names": ["test-agent-1"],
+        "item_path": ["Movies"]
+    });
+    let response = server
+        .post("/components/bulk-delete")
+        .json(&request_body)
+        .await;
+
+    response.assert_status(axum::http::StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json();
+    assert!(body["message"].as_str().unwrap().contains("of 'Action'"));
+}
+
 #[tokio::test]
 async fn test_delete_item_endpoint() {
     let mock_server = MockServer::start().await;
@@ -140,20 +346,28 @@ async fn test_delete_item_endpoint() {
 async fn test_delete_details_endpoint_success() {
     let mock_server = MockServer::start().await;
 
-    // Mock items endpoint
+    // The details come from a dry run delete on the agent
     Mock::given(method("POST"))
-        .and(path("/api/v1/items"))
+        .and(path("/api/v1/delete"))
         .and(header("X-API-Key", "test-key-1"))
+        .and(body_partial_json(json!({
+            "category_id": "temp",
+            "folder_path": ["file.tmp"],
+            "dry_run": true
+        })))
         .respond_with(ResponseTemplate::new(200).set_body_json(json!({
-            "item": {
-                "id": "file.tmp",
-                "name": "file.tmp",
-                "size_kb": 1024,
-                "items": [],
-                "leaf": true,
-                "copy_count": 1
+            "success": true,
+            "message": "Dry run: would delete 'file.tmp' (1 files) from category 'temp'",
+            "deleted_path": null,
+            "preview": {
+                "exists": true,
+                "is_dir": false,
+                "moves_to_trash": true,
+                "total_bytes": 1048576,
+                "files": ["file.tmp"]
             }
         })))
+        .expect(1)
         .mount(&mock_server)
         .await;
 
@@ -176,10 +390,12 @@ async fn test_delete_details_endpoint_success() {
     let body: serde_json::Value = response.json();
     assert_eq!(body["success"], true);
     assert_eq!(body["name"], "file.tmp");
-    assert_eq!(body["is_leaf"], true);
-    assert_eq!(body["is_dir"], true);
+    assert_eq!(body["exists"], true);
+    assert_eq!(body["is_dir"], false);
+    assert_eq!(body["moves_to_trash"], true);
     assert_eq!(body["size_kb"], 1024);
-    assert_eq!(body["item_count"], 0);
+    assert_eq!(body["file_count"], 1);
+    assert_eq!(body["files"], json!(["file.tmp"]));
     assert_eq!(body["agent_name"], "test-agent-1");
 }
 
@@ -301,6 +517,57 @@ async fn test_bulk_delete_endpoint_success() {
     assert_eq!(body["results"].as_array().unwrap().len(), 1);
 }
 
+#[tokio::test]
+async fn test_bulk_delete_endpoint_dry_run() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/delete"))
+        .and(header("X-API-Key", "test-key-1"))
+        .and(body_partial_json(json!({ "dry_run": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Dry run: would delete 'Action' (2 files) from category 'Movies'",
+            "preview": {
+                "exists": true,
+                "is_dir": true,
+                "moves_to_trash": false,
+                "total_bytes": 4096,
+                "files": ["Action/a.mkv", "Action/b.mkv"]
+            }
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/bulk-delete")
+        .json(&json!({
+            "agent_names": ["test-agent-1"],
+            "item_path": ["Movies", "Action"],
+            "dry_run": true
+        }))
+        .await;
+
+    response.assert_status_ok();
+
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+    assert!(body["message"].as_str().unwrap().contains("Previewed"));
+    let result = &body["results"][0];
+    assert!(result["message"].as_str().unwrap().contains("Dry run"));
+    assert_eq!(result["delete_preview"]["total_bytes"], 4096);
+    assert_eq!(
+        result["delete_preview"]["files"].as_array().unwrap().len(),
+        2
+    );
+    assert!(result["ignore_preview"].is_null());
+}
+
 #[tokio::test]
 async fn test_unignore_item_endpoint() {
     let mock_server = MockServer::start().await;
@@ -335,6 +602,80 @@ async fn test_unignore_item_endpoint() {
     assert_eq!(body["success"], true);
 }
 
+#[tokio::test]
+async fn test_unignore_of_a_hand_written_entry_needs_include_user_entries() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/unignore"))
+        .and(body_partial_json(json!({ "include_user_entries": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Item unignored successfully"
+        })))
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/unignore"))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "success": false,
+            "message": "'movie.mkv' was added to .stignore by hand, set include_user_entries to remove it anyway"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let body: serde_json::Value = server
+        .post("/components/unignore")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "item_path": ["Movies", "movie.mkv"]
+        }))
+        .await
+        .json();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["user_entry"], true);
+    assert!(body["message"].as_str().unwrap().contains("by hand"));
+
+    let body: serde_json::Value = server
+        .post("/components/unignore")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "item_path": ["Movies", "movie.mkv"],
+            "include_user_entries": true
+        }))
+        .await
+        .json();
+    assert_eq!(body["success"], true);
+    assert!(body.get("user_entry").is_none());
+
+    let body: serde_json::Value = server
+        .post("/components/bulk-unignore")
+        .json(&json!({
+            "agent_names": ["test-agent-1"],
+            "item_path": ["Movies", "movie.mkv"]
+        }))
+        .await
+        .json();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["results"][0]["user_entry"], true);
+
+    let body: serde_json::Value = server
+        .post("/components/bulk-unignore")
+        .json(&json!({
+            "agent_names": ["test-agent-1"],
+            "item_path": ["Movies", "movie.mkv"],
+            "include_user_entries": true
+        }))
+        .await
+        .json();
+    assert_eq!(body["success"], true);
+}
+
 #[tokio::test]
 async fn test_bulk_unignore_endpoint_success() {
     let mock_server = MockServer::start().await;
@@ -466,7 +807,9 @@ async fn test_component_error_handling() {
 #[tokio::test]
 async fn test_full_user_workflow_integration() {
     let mock_server = setup_mock_agent_server().await;
-    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let mut config = create_test_config_with_mock_server(&mock_server.uri());
+    // The only agent holds the movie, without a minimum the ignore goes through
+    config.manager.minimum_copies = 0;
     let app = create_test_app(config);
     let server = TestServer::new(app).unwrap();
 
@@ -564,6 +907,170 @@ async fn test_toggle_agent_endpoint() {
     assert_eq!(body["enabled"], true);
 }
 
+#[tokio::test]
+async fn test_disabled_agents_persist_across_restarts() {
+    let mock_server = setup_mock_agent_server().await;
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("disabled_agents.json");
+
+    let mut app_state = create_test_app_state(config.clone());
+    app_state.disabled_agents =
+        stignore_manager::disabled::DisabledAgents::load(&state_path).unwrap();
+    let server = TestServer::new(stignore_manager::create_app(app_state)).unwrap();
+
+    let response = server
+        .post("/components/agents/toggle")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "enabled": false,
+            "reason": "Replacing disks"
+        }))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    // A fresh manager reading the same state file still has the agent disabled
+    let mut restarted = create_test_app_state(config);
+    restarted.disabled_agents =
+        stignore_manager::disabled::DisabledAgents::load(&state_path).unwrap();
+    let server = TestServer::new(stignore_manager::create_app(restarted)).unwrap();
+
+    let overview = server.get("/components/agents-table.html").await;
+    overview.assert_status_ok();
+    overview.assert_text_contains("Disabled");
+    overview.assert_text_contains("by anonymous");
+    overview.assert_text_contains("Replacing disks");
+}
+
+#[tokio::test]
+async fn test_agents_can_be_added_edited_and_removed_at_runtime() {
+    let mock_server = setup_mock_agent_server().await;
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/agents/add")
+        .json(&json!({
+            "agent_name": "new-nas",
+            "hostname": "localhost:9999",
+            "api_key": "new-key"
+        }))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    let table = server.get("/components/agents-table.html").await;
+    table.assert_text_contains("new-nas");
+    table.assert_text_contains("localhost:9999");
+
+    // Adding the same name twice is refused
+    let response = server
+        .post("/components/agents/add")
+        .json(&json!({
+            "agent_name": "new-nas",
+            "hostname": "localhost:9998",
+            "api_key": "new-key"
+        }))
+        .await;
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+    assert!(body["message"].as_str().unwrap().contains("already exists"));
+
+    // Pointing it at the mock server makes it answer like the other agent
+    let response = server
+        .post("/components/agents/edit")
+        .json(&json!({
+            "agent_name": "new-nas",
+            "hostname": mock_server.uri(),
+            "api_key": "test-key-1",
+            "timeout_seconds": 2
+        }))
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    let pill = server.get("/components/agent-status-pill.html").await;
+    pill.assert_text_contains("2/2 Online");
+
+    let response = server
+        .post("/components/agents/remove")
+        .json(&json!({"agent_name": "new-nas"}))
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["success"], true);
+
+    let table = server.get("/components/agents-table.html").await;
+    assert!(!table.text().contains("new-nas"));
+}
+
+#[tokio::test]
+async fn test_dynamic_items_list_loose_files_after_directories() {
+    let mock_server = MockServer::start().await;
+
+    let category_response = AgentCategoryListingResponse {
+        items: vec![ItemGroup {
+            id: "Media".to_string(),
+            name: "Media".to_string(),
+            size_bytes: 3000,
+            items: vec![
+                ItemGroup {
+                    id: "aaa-notes.txt".to_string(),
+                    name: "aaa-notes.txt".to_string(),
+                    size_bytes: 1000,
+                    file_count: 1,
+                    copy_count: 1,
+                    ..Default::default()
+                },
+                ItemGroup {
+                    id: "Show".to_string(),
+                    name: "Show".to_string(),
+                    size_bytes: 2000,
+                    items: vec![ItemGroup {
+                        id: "episode.mkv".to_string(),
+                        name: "episode.mkv".to_string(),
+                        size_bytes: 2000,
+                        file_count: 1,
+                        copy_count: 1,
+                        ..Default::default()
+                    }],
+                    leaf: true,
+                    file_count: 1,
+                    copy_count: 1,
+                    ..Default::default()
+                },
+            ],
+            file_count: 2,
+            dir_count: 1,
+            copy_count: 1,
+            ..Default::default()
+        }],
+    };
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(category_response))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let server = TestServer::new(create_test_app(config)).unwrap();
+
+    let response = server
+        .get("/components/dynamic-items.html")
+        .add_query_param("parent_id", "Media")
+        .add_query_param("parent_path", "id_TWVkaWE")
+        .add_query_param("level", "2")
+        .await;
+    response.assert_status_ok();
+
+    let text = response.text();
+    let show_pos = text.find(">Show<").unwrap();
+    let file_pos = text.find(">aaa-notes.txt<").unwrap();
+    assert!(show_pos < file_pos, "files should follow directories");
+    assert_eq!(text.matches(r#"title="File""#).count(), 1);
+}
+
 #[tokio::test]
 async fn test_dynamic_items_sorting_options() {
     let mock_server = MockServer::start().await;
@@ -709,18 +1216,170 @@ async fn test_dynamic_items_sorting_options() {
 }
 
 #[tokio::test]
-async fn test_agent_status_pill_endpoint_all_online() {
-    let mock_server = setup_mock_agent_server().await;
-    let config = create_test_config_with_mock_server(&mock_server.uri());
-    let app = create_test_app(config);
-    let server = TestServer::new(app).unwrap();
+async fn test_dynamic_items_flag_copies_only_held_by_ignoring_agents() {
+    let mock_server = MockServer::start().await;
 
-    let response = server.get("/components/agent-status-pill.html").await;
-    response.assert_status_ok();
-    response.assert_text_contains("agent-status-pill");
-    response.assert_text_contains("status-healthy");
-    response.assert_text_contains("1/1 Online");
-    response.assert_text_contains("href=\"/agents\"");
+    let category_response = AgentCategoryListingResponse {
+        items: vec![ItemGroup {
+            id: "Media".to_string(),
+            name: "Media".to_string(),
+            size_kb: 300,
+            items: vec![
+                ItemGroup {
+                    id: "Media/Kept".to_string(),
+                    name: "Kept Folder".to_string(),
+                    size_kb: 100,
+                    ..Default::default()
+                },
+                ItemGroup {
+                    id: "Media/Ignored".to_string(),
+                    name: "Ignored Folder".to_string(),
+                    size_kb: 200,
+                    ignored: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }],
+    };
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(category_response))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config_with_mock_server(&mock_server.uri());
+    config.manager.minimum_copies = 1;
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .get("/components/dynamic-items.html")
+        .add_query_param("parent_id", "Media")
+        .add_query_param("parent_path", "id_TWVkaWE")
+        .add_query_param("level", "2")
+        .await;
+
+    response.assert_status_ok();
+    let text = response.text();
+    let kept = &text[text.find("Kept Folder").unwrap()..];
+    let ignored = &text[text.find("Ignored Folder").unwrap()..];
+    assert!(kept.contains(r#"data-insufficient="false""#));
+    assert!(ignored.contains(r#"data-insufficient="true""#));
+    assert!(text.contains("ignored on test-agent-1"));
+}
+
+#[tokio::test]
+async fn test_agent_status_pill_endpoint_all_online() {
+    let mock_server = setup_mock_agent_server().await;
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server.get("/components/agent-status-pill.html").await;
+    response.assert_status_ok();
+    response.assert_text_contains("agent-status-pill");
+    response.assert_text_contains("status-healthy");
+    response.assert_text_contains("1/1 Online");
+    response.assert_text_contains("href=\"/agents\"");
+}
+
+#[tokio::test]
+async fn test_agent_status_pill_flags_incompatible_agents() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/health"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(create_mock_health_response(AGENT_API_VERSION + 1)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    // The pill only needs the health endpoint, the category listing is never hit
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server.get("/components/agent-status-pill.html").await;
+    response.assert_status_ok();
+    response.assert_text_contains("status-warning");
+    response.assert_text_contains("1/1 Online");
+    response.assert_text_contains("1 incompatible");
+}
+
+#[tokio::test]
+async fn test_agent_status_pill_flags_agents_without_health_endpoint() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/health"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server.get("/components/agent-status-pill.html").await;
+    response.assert_status_ok();
+    response.assert_text_contains("status-warning");
+    response.assert_text_contains("1/1 Online");
+    response.assert_text_contains("1 incompatible");
+}
+
+#[tokio::test]
+async fn test_agents_table_uses_category_summaries() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/summary"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(create_mock_category_summary_response()),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/health"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(create_mock_health_response(AGENT_API_VERSION)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    // No full trees are transferred for the overview
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/items"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server.get("/components/agents-table.html").await;
+    response.assert_status_ok();
+    response.assert_text_contains("Active");
+    response.assert_text_contains("Movies");
+    response.assert_text_contains("1 items");
 }
 
 #[tokio::test]
@@ -758,13 +1417,14 @@ async fn test_agent_status_pill_endpoint_with_disabled_agent() {
         name: "disabled-agent".to_string(),
         hostname: "localhost:9999".to_string(),
         api_key: "key".to_string(),
+        timeout_seconds: None,
     });
 
     let app_state = create_test_app_state(config);
-    {
-        let mut disabled = app_state.disabled_agents.write().unwrap();
-        disabled.insert("disabled-agent".to_string());
-    }
+    app_state
+        .disabled_agents
+        .disable("disabled-agent", "admin", None)
+        .unwrap();
 
     let app = stignore_manager::create_app(app_state);
     let server = TestServer::new(app).unwrap();
@@ -775,3 +1435,422 @@ async fn test_agent_status_pill_endpoint_with_disabled_agent() {
     response.assert_text_contains("1/1 Online");
     response.assert_text_contains("1 disabled");
 }
+
+#[tokio::test]
+async fn test_infopanel_shows_ignored_via_parent() {
+    let mock_server = setup_mock_agent_server().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/ignore-status-bulk"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "category_id": "Movies",
+                "folder_path": ["Action", "movie.mkv"],
+                "ignored": true,
+                "matched_rule": {
+                    "pattern": "Action",
+                    "line": 3,
+                    "inherited_from": "Action"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let request_body = json!({
+        "category_id": "Movies",
+        "item_path": ["Movies", "Action", "movie.mkv"]
+    });
+
+    let response = server
+        .post("/components/infopanel.html")
+        .json(&request_body)
+        .await;
+
+    response.assert_status_ok();
+    response.assert_text_contains("Ignored via parent");
+    response.assert_text_contains("on line 3 of .stignore");
+}
+
+#[tokio::test]
+async fn test_trash_list_component() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/trash"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "enabled": true,
+            "retention_days": 30,
+            "items": [{
+                "id": "1700000000000-0",
+                "category_id": "Movies",
+                "folder_path": ["Action", "movie.mkv"],
+                "is_dir": false,
+                "size_kb": 2048,
+                "deleted_at": 1700000000,
+                "expires_at": 1702592000
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/trash.html")
+        .json(&json!({ "agent_name": "test-agent-1" }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_text_contains("Movies / Action / movie.mkv");
+    response.assert_text_contains("restoreTrashItem('test-agent-1', '1700000000000-0')");
+}
+
+#[tokio::test]
+async fn test_trash_restore_endpoint() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/trash/restore"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Restored 'Action/movie.mkv' to category 'Movies'"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/trash/restore")
+        .json(&json!({ "agent_name": "test-agent-1", "id": "1700000000000-0" }))
+        .await;
+
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+}
+
+#[tokio::test]
+async fn test_trash_purge_endpoint_reports_agent_failure() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/trash/purge"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "success": false,
+            "message": "Trash is not enabled on this agent"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/trash/purge")
+        .json(&json!({ "agent_name": "test-agent-1", "all": true }))
+        .await;
+
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("Trash is not enabled")
+    );
+}
+
+#[tokio::test]
+async fn test_trash_purge_endpoint_requires_an_item_or_all() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/trash/purge"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Purged 3 items from trash"
+        })))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/trash/purge")
+        .json(&json!({ "agent_name": "test-agent-1" }))
+        .await;
+
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+}
+
+#[tokio::test]
+async fn test_stignore_history_component() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/history"))
+        .and(header("X-API-Key", "test-key-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "enabled": true,
+            "retention": 100,
+            "categories": [
+                {"id": "movies", "name": "Movies", "revisions": []},
+                {"id": "tv", "name": "TV Shows", "revisions": [
+                    {
+                        "id": "1700000005000-2",
+                        "category_id": "tv",
+                        "created_at": 1700000005,
+                        "change": "ignore",
+                        "detail": "Show 1",
+                        "line_count": 1
+                    },
+                    {
+                        "id": "1700000000000-1",
+                        "category_id": "tv",
+                        "created_at": 1700000000,
+                        "change": "outside",
+                        "detail": "",
+                        "line_count": 0
+                    }
+                ]}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    // The first category is shown unless one is asked for
+    let response = server
+        .post("/components/history.html")
+        .json(&json!({ "agent_name": "test-agent-1" }))
+        .await;
+    response.assert_status_ok();
+    response.assert_text_contains("No changes to this category");
+
+    let response = server
+        .post("/components/history.html")
+        .json(&json!({ "agent_name": "test-agent-1", "category_id": "tv" }))
+        .await;
+    response.assert_status_ok();
+    response.assert_text_contains("Ignored Show 1");
+    response.assert_text_contains("Edited outside the manager");
+    // Each revision's changes are relative to the one before it
+    response.assert_text_contains(
+        "toggleHistoryDiff(this, 'test-agent-1', 'tv', '1700000000000-1', '1700000005000-2')",
+    );
+    response.assert_text_contains("revertStignore('test-agent-1', 'tv', '1700000000000-1')");
+}
+
+#[tokio::test]
+async fn test_stignore_diff_component() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/history/diff"))
+        .and(body_partial_json(json!({
+            "category_id": "tv",
+            "from": null,
+            "to": "1700000005000-2"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "",
+            "lines": [
+                {"op": "same", "text": "// shows"},
+                {"op": "added", "text": "Show 1"},
+                {"op": "removed", "text": "Show 2"}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/history/diff.html")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "category_id": "tv",
+            "from": null,
+            "to": "1700000005000-2"
+        }))
+        .await;
+    response.assert_status_ok();
+    response.assert_text_contains("<span class=\"text-success\">+ Show 1</span>");
+    response.assert_text_contains("<span class=\"text-danger\">- Show 2</span>");
+}
+
+#[tokio::test]
+async fn test_stignore_revert_endpoint() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v1/history/revert"))
+        .and(body_partial_json(json!({
+            "category_id": "tv",
+            "id": "1700000000000-1"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Reverted .stignore in category 'TV Shows' to revision 1700000000000-1"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/history/revert")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "category_id": "tv",
+            "id": "1700000000000-1"
+        }))
+        .await;
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("on test-agent-1")
+    );
+}
+
+#[tokio::test]
+async fn test_stignore_editor_component() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1/categories/tv/stignore"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Read .stignore in category 'TV Shows'",
+            "content": "Show 1\n// keep\n",
+            "etag": "\"e-00000000000000ab\"",
+            "managed_entries": [],
+            "user_entries": ["Show 1"]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/stignore-editor.html")
+        .json(&json!({"agent_name": "test-agent-1", "category_id": "tv"}))
+        .await;
+    response.assert_status_ok();
+    response.assert_text_contains("Show 1\n// keep\n</textarea>");
+    response.assert_text_contains("data-etag=\"&quot;e-00000000000000ab&quot;\"");
+    response.assert_text_contains("Overwrite anyway");
+    response.assert_text_contains("0 entries are kept by the agent");
+    response.assert_text_contains("1 was written by hand");
+}
+
+#[tokio::test]
+async fn test_stignore_save_sends_the_etag() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("PUT"))
+        .and(path("/api/v1/categories/tv/stignore"))
+        .and(header("If-Match", "\"e-00000000000000ab\""))
+        .and(body_partial_json(json!({"content": "Show 1\nShow 2\n"})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "message": "Saved .stignore in category 'TV Shows'",
+            "content": "Show 1\nShow 2\n",
+            "etag": "\"e-00000000000000cd\""
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/stignore/save")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "category_id": "tv",
+            "etag": "\"e-00000000000000ab\"",
+            "content": "Show 1\nShow 2\n"
+        }))
+        .await;
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["conflict"], false);
+    assert_eq!(body["etag"], "\"e-00000000000000cd\"");
+}
+
+#[tokio::test]
+async fn test_stignore_save_reports_a_conflict() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("PUT"))
+        .and(path("/api/v1/categories/tv/stignore"))
+        .respond_with(ResponseTemplate::new(412).set_body_json(json!({
+            "success": false,
+            "message": ".stignore was changed since it was read",
+            "content": "Show 3\n",
+            "etag": "\"7-00000000000000ef\""
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let config = create_test_config_with_mock_server(&mock_server.uri());
+    let app = create_test_app(config);
+    let server = TestServer::new(app).unwrap();
+
+    let response = server
+        .post("/components/stignore/save")
+        .json(&json!({
+            "agent_name": "test-agent-1",
+            "category_id": "tv",
+            "etag": "\"e-00000000000000ab\"",
+            "content": "Show 1\nShow 2\n"
+        }))
+        .await;
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["conflict"], true);
+    assert_eq!(body["content"], "Show 3\n");
+    assert_eq!(body["etag"], "\"7-00000000000000ef\"");
+}
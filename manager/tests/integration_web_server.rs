@@ -128,9 +128,9 @@ async fn test_app_state_creation() {
 async fn test_humansize_filter_integration() {
     // Test the humansize filter function directly
     let test_cases = vec![
-        500.0,     // 500 KB input
-        1024.0,    // 1024 KB = 1 MB
-        1048576.0, // 1024*1024 KB = 1 GB
+        500.0,        // 500 B input
+        1048576.0,    // 1024*1024 B = 1 MB
+        1073741824.0, // 1024*1024*1024 B = 1 GB
     ];
 
     let kwargs = tera::Kwargs::default();
@@ -153,6 +153,27 @@ async fn test_humansize_filter_integration() {
     }
 }
 
+#[tokio::test]
+async fn test_humansize_filter_units() {
+    let mut tera = tera::Tera::default();
+    tera.register_filter("humansize", stignore_manager::humansize_filter);
+    let mut context = tera::Context::new();
+    context.insert("bytes", &2800);
+    context.insert("kb", &2048);
+
+    let rendered = tera
+        .render_str(
+            r#"{{ bytes | humansize }} / {{ kb | humansize(unit="kb") }}"#,
+            &context,
+            false,
+        )
+        .unwrap();
+    assert_eq!(rendered, "2.7 KB / 2.0 MB");
+
+    let unknown = tera.render_str(r#"{{ kb | humansize(unit="mb") }}"#, &context, false);
+    assert!(unknown.is_err());
+}
+
 #[tokio::test]
 async fn test_navbar_sort_menu_rendering() {
     let config = create_test_config();